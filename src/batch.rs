@@ -0,0 +1,505 @@
+use crate::fractal::octave_weight;
+use crate::perlin::{
+    grad2_hashed, grad3_hashed, hash2, hash3, smooth_func, GRAD1, NOISE_PERM, N_PERM,
+};
+use crate::prelude::*;
+use crate::simd::{self, SimdLevel};
+
+//
+// Batch evaluation into buffers
+//
+
+// Sample (i, j, k) of a batch lies at origin + (i, j, k) * step. Every sample equals
+// the scalar noise evaluated with a fresh `UniformRandomGen::new(seed)` in the given
+// hash mode, which makes the noises that use the generator (random and worley)
+// independent of the order of evaluation. In the legacy mode Perlin and the random
+// noises reuse the lattice hashes and random values of a cell for all samples along
+// a row which fall into it, and 2D and 3D Perlin and Simplex noise use the SIMD
+// kernels where available. The other noises, and all noises in the V1 mode, are
+// evaluated sample by sample.
+
+/// Fills `out` with the 1D noise along a line.
+pub fn fill_1d(
+    out: &mut [f32],
+    noise: NoiseKind,
+    origin: f32,
+    step: f32,
+    seed: u32,
+    mode: HashMode,
+) {
+    let xs = positions(out.len(), origin, step);
+    noise_row(noise, out, &xs, &[], seed, mode);
+}
+
+/// Fills `out` with the 2D noise on a grid of `width` columns, stored row by row.
+pub fn fill_2d(
+    out: &mut [f32],
+    width: usize,
+    noise: NoiseKind,
+    origin: [f32; 2],
+    step: [f32; 2],
+    seed: u32,
+    mode: HashMode,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for (j, row) in rows(out, width, 1).enumerate() {
+        let y = origin[1] + j as f32 * step[1];
+        noise_row(noise, row, &xs, &[y], seed, mode);
+    }
+}
+
+/// Fills `out` with the 3D noise in a volume of `width` x `height` slices, stored
+/// slice by slice and row by row.
+#[allow(clippy::too_many_arguments)]
+pub fn fill_3d(
+    out: &mut [f32],
+    width: usize,
+    height: usize,
+    noise: NoiseKind,
+    origin: [f32; 3],
+    step: [f32; 3],
+    seed: u32,
+    mode: HashMode,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for (r, row) in rows(out, width, height).enumerate() {
+        let y = origin[1] + (r % height) as f32 * step[1];
+        let z = origin[2] + (r / height) as f32 * step[2];
+        noise_row(noise, row, &xs, &[y, z], seed, mode);
+    }
+}
+
+/// Fills `out` with a fractal of the 1D noise along a line. Every sample equals
+/// [`Fractal::eval_1d`] of the noise with a fresh `UniformRandomGen::new(fractal.seed())`
+/// in the hash mode `mode`.
+pub fn fill_fractal_1d(
+    out: &mut [f32],
+    noise: NoiseKind,
+    fractal: &Fractal,
+    origin: f32,
+    step: f32,
+    mode: HashMode,
+) {
+    let xs = positions(out.len(), origin, step);
+    fractal_row(noise, fractal, out, &xs, &[], mode);
+}
+
+/// Fills `out` with a fractal of the 2D noise on a grid, see [`fill_2d`].
+pub fn fill_fractal_2d(
+    out: &mut [f32],
+    width: usize,
+    noise: NoiseKind,
+    fractal: &Fractal,
+    origin: [f32; 2],
+    step: [f32; 2],
+    mode: HashMode,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for (j, row) in rows(out, width, 1).enumerate() {
+        let y = origin[1] + j as f32 * step[1];
+        fractal_row(noise, fractal, row, &xs, &[y], mode);
+    }
+}
+
+/// Fills `out` with a fractal of the 3D noise in a volume, see [`fill_3d`].
+#[allow(clippy::too_many_arguments)]
+pub fn fill_fractal_3d(
+    out: &mut [f32],
+    width: usize,
+    height: usize,
+    noise: NoiseKind,
+    fractal: &Fractal,
+    origin: [f32; 3],
+    step: [f32; 3],
+    mode: HashMode,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for (r, row) in rows(out, width, height).enumerate() {
+        let y = origin[1] + (r % height) as f32 * step[1];
+        let z = origin[2] + (r / height) as f32 * step[2];
+        fractal_row(noise, fractal, row, &xs, &[y, z], mode);
+    }
+}
+
+pub(crate) fn positions(n: usize, origin: f32, step: f32) -> Vec<f32> {
+    (0..n).map(|i| origin + i as f32 * step).collect()
+}
+
+fn rows(out: &mut [f32], width: usize, height: usize) -> std::slice::ChunksExactMut<'_, f32> {
+    assert!(
+        width > 0 && height > 0 && out.len().is_multiple_of(width * height),
+        "the buffer has to hold whole rows and slices"
+    );
+    out.chunks_exact_mut(width)
+}
+
+// Evaluates one row of samples at (xs[i], rest..), rest holding y and z.
+pub(crate) fn noise_row(
+    noise: NoiseKind,
+    out: &mut [f32],
+    xs: &[f32],
+    rest: &[f32],
+    seed: u32,
+    mode: HashMode,
+) {
+    let level = SimdLevel::detect();
+    match noise {
+        // The row functions and kernels hash the lattice with the legacy tables.
+        _ if mode != HashMode::Legacy => {
+            for (o, x) in out.iter_mut().zip(xs) {
+                let rng = &mut UniformRandomGen::new(seed).with_hash_mode(mode);
+                *o = match *rest {
+                    [] => noise.eval_1d(rng, *x, seed),
+                    [y] => noise.eval_2d(rng, *x, y, seed),
+                    [y, z] => noise.eval_3d(rng, *x, y, z, seed),
+                    _ => unreachable!(),
+                };
+            }
+        }
+        NoiseKind::Perlin | NoiseKind::Simplex
+            if level != SimdLevel::Scalar && !rest.is_empty() =>
+        {
+            simd_row(noise, level, out, xs, rest, seed)
+        }
+        NoiseKind::Perlin => match *rest {
+            [] => perlin_row_1d(out, xs, seed),
+            [y] => perlin_row_2d(out, xs, y, seed),
+            [y, z] => perlin_row_3d(out, xs, y, z, seed),
+            _ => unreachable!(),
+        },
+        NoiseKind::Random => random_row(out, xs, rest, seed),
+        NoiseKind::RandomFiltered => random_filtered_row(out, xs, rest, seed),
+        _ => {
+            for (o, x) in out.iter_mut().zip(xs) {
+                let rng = &mut UniformRandomGen::new(seed);
+                *o = match *rest {
+                    [] => noise.eval_1d(rng, *x, seed),
+                    [y] => noise.eval_2d(rng, *x, y, seed),
+                    [y, z] => noise.eval_3d(rng, *x, y, z, seed),
+                    _ => unreachable!(),
+                };
+            }
+        }
+    }
+}
+
+// Evaluates a row of fractal samples. The noises which do not use the generator are
+// evaluated octave by octave over the whole row, the others sample by sample as
+// their octaves depend on the generator state of the previous octaves.
+pub(crate) fn fractal_row(
+    noise: NoiseKind,
+    fractal: &Fractal,
+    out: &mut [f32],
+    xs: &[f32],
+    rest: &[f32],
+    mode: HashMode,
+) {
+    let seed = fractal.seed();
+    if !matches!(
+        noise,
+        NoiseKind::Perlin | NoiseKind::Simplex | NoiseKind::Musgrave
+    ) {
+        for (o, x) in out.iter_mut().zip(xs) {
+            let rng = &mut UniformRandomGen::new(seed).with_hash_mode(mode);
+            *o = match *rest {
+                [] => fractal.eval_1d(rng, *x, |rng, x, s| noise.eval_1d(rng, x, s)),
+                [y] => fractal.eval_2d(rng, *x, y, |rng, x, y, s| noise.eval_2d(rng, x, y, s)),
+                [y, z] => fractal.eval_3d(rng, *x, y, z, |rng, x, y, z, s| {
+                    noise.eval_3d(rng, x, y, z, s)
+                }),
+                _ => unreachable!(),
+            };
+        }
+        return;
+    }
+
+    let kind = fractal.kind();
+    let octaves = fractal.octaves();
+    let mut octave = vec![0.0; out.len()];
+    let mut scaled_xs = vec![0.0; xs.len()];
+    let mut scaled_rest = [0.0; 2];
+
+    let mut power = 1.0;
+    let mut freq = 1.0;
+    let mut norma = 0.0;
+    out.fill(if kind == FractalKind::Multiplicative {
+        1.0
+    } else {
+        0.0
+    });
+
+    for i in 0..octaves.ceil() as i32 {
+        for (s, x) in scaled_xs.iter_mut().zip(xs) {
+            *s = x * freq;
+        }
+        for (s, r) in scaled_rest.iter_mut().zip(rest) {
+            *s = r * freq;
+        }
+        noise_row(
+            noise,
+            &mut octave,
+            &scaled_xs,
+            &scaled_rest[..rest.len()],
+            seed,
+            mode,
+        );
+
+        let partial = octave_weight(octaves, i);
+        if kind == FractalKind::Multiplicative {
+            let offset = fractal.offset();
+            for (o, n) in out.iter_mut().zip(&octave) {
+                *o *= 1.0 + partial * ((n + offset) * power - 1.0);
+            }
+        } else {
+            let weight = partial * power;
+            for (o, n) in out.iter_mut().zip(&octave) {
+                let n = match kind {
+                    FractalKind::Turbulence => n.abs(),
+                    FractalKind::Ridged => {
+                        let ridge = 1.0 - n.abs();
+                        ridge * ridge
+                    }
+                    _ => *n,
+                };
+                *o += n * weight;
+            }
+            norma += weight;
+        }
+
+        power *= fractal.freq_falloff();
+        freq *= fractal.lacunarity();
+    }
+
+    if kind == FractalKind::Multiplicative {
+        let (lo, hi) = fractal_mul_bounds(octaves, fractal.freq_falloff(), fractal.offset());
+        for o in out.iter_mut() {
+            *o = if hi > lo { (*o - lo) / (hi - lo) } else { 0.0 };
+        }
+    } else {
+        for o in out.iter_mut() {
+            *o = if norma > 0.0 { *o / norma } else { 0.0 };
+        }
+    }
+}
+
+// Evaluates a 2D or 3D row of Perlin or Simplex noise with the SIMD kernels, which
+// give the same results as the scalar functions.
+fn simd_row(
+    noise: NoiseKind,
+    level: SimdLevel,
+    out: &mut [f32],
+    xs: &[f32],
+    rest: &[f32],
+    seed: u32,
+) {
+    let ys = vec![rest[0]; xs.len()];
+    match (noise, rest.get(1)) {
+        (NoiseKind::Perlin, None) => simd::perlin_2d(level, out, xs, &ys, seed, HashMode::Legacy),
+        (NoiseKind::Simplex, None) => simd::simplex_2d(level, out, xs, &ys, seed, HashMode::Legacy),
+        (NoiseKind::Perlin, Some(z)) => simd::perlin_3d(
+            level,
+            out,
+            xs,
+            &ys,
+            &vec![*z; xs.len()],
+            seed,
+            HashMode::Legacy,
+        ),
+        (NoiseKind::Simplex, Some(z)) => simd::simplex_3d(
+            level,
+            out,
+            xs,
+            &ys,
+            &vec![*z; xs.len()],
+            seed,
+            HashMode::Legacy,
+        ),
+        _ => unreachable!(),
+    }
+}
+
+//
+// Perlin rows
+//
+
+fn perlin_row_1d(out: &mut [f32], xs: &[f32], seed: u32) {
+    let offset = NOISE_PERM[(seed & (N_PERM - 1) as u32) as usize];
+    let mut cell = None;
+    let mut h = [0; 2];
+
+    for (o, x) in out.iter_mut().zip(xs) {
+        let ix = x.floor() as i32;
+        let fx = x - ix as f32;
+
+        if cell != Some(ix) {
+            let ix = ix + offset;
+            let ixp1 = (ix & (N_PERM - 1)) as usize;
+            let ixp2 = ((ix / N_PERM) & (N_PERM - 1)) as usize;
+            h = [
+                NOISE_PERM[NOISE_PERM[ixp1] as usize + ixp2] & 15,
+                NOISE_PERM[NOISE_PERM[ixp1 + 1] as usize + ixp2] & 15,
+            ];
+            cell = Some(ix - offset);
+        }
+
+        let wx = smooth_func(fx);
+        *o = (1.0 - wx) * GRAD1[h[0] as usize] + wx * GRAD1[h[1] as usize];
+    }
+}
+
+fn perlin_row_2d(out: &mut [f32], xs: &[f32], y: f32, seed: u32) {
+    let seed = (seed & (N_PERM as u32 - 1)) as usize;
+    let iy = y.floor() as i32;
+    let fy = y - iy as f32;
+    let iy = (iy + NOISE_PERM[seed + 1]) & (N_PERM - 1);
+    let wy = smooth_func(fy);
+
+    let mut cell = None;
+    let mut h = [0; 4];
+
+    for (o, x) in out.iter_mut().zip(xs) {
+        let ix = x.floor() as i32;
+        let fx = x - ix as f32;
+
+        if cell != Some(ix) {
+            cell = Some(ix);
+            let ix = (ix + NOISE_PERM[seed]) & (N_PERM - 1);
+            h = [
+                hash2(ix, iy),
+                hash2(ix, iy + 1),
+                hash2(ix + 1, iy),
+                hash2(ix + 1, iy + 1),
+            ];
+        }
+
+        let w00 = grad2_hashed(h[0], fx, fy);
+        let w01 = grad2_hashed(h[1], fx, fy - 1.0);
+        let w10 = grad2_hashed(h[2], fx - 1.0, fy);
+        let w11 = grad2_hashed(h[3], fx - 1.0, fy - 1.0);
+
+        let wx = smooth_func(fx);
+        *o = (1.0 - wx) * ((1.0 - wy) * w00 + wy * w01) + wx * ((1.0 - wy) * w10 + wy * w11);
+    }
+}
+
+fn perlin_row_3d(out: &mut [f32], xs: &[f32], y: f32, z: f32, seed: u32) {
+    let seed = (seed & (N_PERM as u32 - 1)) as usize;
+    let iy = y.floor() as i32;
+    let iz = z.floor() as i32;
+    let fy = y - iy as f32;
+    let fz = z - iz as f32;
+    let iy = (iy + NOISE_PERM[seed + 1]) & (N_PERM - 1);
+    let iz = (iz + NOISE_PERM[seed + 2]) & (N_PERM - 1);
+    let wy = smooth_func(fy);
+    let wz = smooth_func(fz);
+
+    let mut cell = None;
+    let mut h = [0; 8];
+
+    for (o, x) in out.iter_mut().zip(xs) {
+        let ix = x.floor() as i32;
+        let fx = x - ix as f32;
+
+        if cell != Some(ix) {
+            cell = Some(ix);
+            let ix = (ix + NOISE_PERM[seed]) & (N_PERM - 1);
+            for (c, h) in h.iter_mut().enumerate() {
+                let (cx, cy, cz) = ((c >> 2) as i32, ((c >> 1) & 1) as i32, (c & 1) as i32);
+                *h = hash3(ix + cx, iy + cy, iz + cz);
+            }
+        }
+
+        let w000 = grad3_hashed(h[0], fx, fy, fz) * (1.0 - wz);
+        let w001 = grad3_hashed(h[1], fx, fy, fz - 1.0) * wz;
+        let w010 = grad3_hashed(h[2], fx, fy - 1.0, fz) * (1.0 - wz);
+        let w011 = grad3_hashed(h[3], fx, fy - 1.0, fz - 1.0) * wz;
+        let w100 = grad3_hashed(h[4], fx - 1.0, fy, fz) * (1.0 - wz);
+        let w101 = grad3_hashed(h[5], fx - 1.0, fy, fz - 1.0) * wz;
+        let w110 = grad3_hashed(h[6], fx - 1.0, fy - 1.0, fz) * (1.0 - wz);
+        let w111 = grad3_hashed(h[7], fx - 1.0, fy - 1.0, fz - 1.0) * wz;
+
+        let wx = smooth_func(fx);
+        *o = (1.0 - wx) * ((1.0 - wy) * (w000 + w001) + wy * (w010 + w011))
+            + wx * ((1.0 - wy) * (w100 + w101) + wy * (w110 + w111));
+    }
+}
+
+//
+// Random rows
+//
+
+// The hash factors of the random noises per axis.
+const RANDOM_AXES: [u32; 3] = [3290387, 4433105, 6876199];
+
+fn random_row(out: &mut [f32], xs: &[f32], rest: &[f32], seed: u32) {
+    let mut base = seed;
+    for (r, k) in rest.iter().zip(&RANDOM_AXES[1..]) {
+        base = base.wrapping_add(((r + 0.00137).floor() as u32).wrapping_mul(*k));
+    }
+
+    let mut cell = None;
+    let mut value = 0.0;
+    for (o, x) in out.iter_mut().zip(xs) {
+        let ix = (x + 0.00137).floor() as u32;
+        if cell != Some(ix) {
+            cell = Some(ix);
+            let hash = base.wrapping_add(ix.wrapping_mul(RANDOM_AXES[0]));
+            value = UniformRandomGen::new(seed).get(hash) * 2.0 - 1.0;
+        }
+        *o = value;
+    }
+}
+
+fn random_filtered_row(out: &mut [f32], xs: &[f32], rest: &[f32], seed: u32) {
+    let dims = rest.len() + 1;
+    let corners = 1 << dims;
+
+    // The lattice hashes and the weights of the lower and upper corners per axis,
+    // x being the first axis and the most significant bit of a corner index.
+    let mut hash = [[0u32; 2]; 3];
+    let mut weight = [[0.0f32; 2]; 3];
+    for (a, r) in rest.iter().enumerate() {
+        let (h, w) = filtered_axis(*r, RANDOM_AXES[a + 1]);
+        hash[a + 1] = h;
+        weight[a + 1] = w;
+    }
+
+    let mut cell = None;
+    let mut values = [0.0; 8];
+    for (o, x) in out.iter_mut().zip(xs) {
+        let (h, w) = filtered_axis(*x, RANDOM_AXES[0]);
+        hash[0] = h;
+        weight[0] = w;
+
+        if cell != Some(h[0]) {
+            cell = Some(h[0]);
+            let mut rng = UniformRandomGen::new(seed);
+            for (c, v) in values.iter_mut().enumerate().take(corners) {
+                let mut corner_hash = seed;
+                for (a, h) in hash.iter().enumerate().take(dims) {
+                    corner_hash = corner_hash.wrapping_add(h[(c >> (dims - 1 - a)) & 1]);
+                }
+                *v = rng.get(corner_hash);
+            }
+        }
+
+        let mut val = 0.0;
+        for (c, v) in values.iter().enumerate().take(corners) {
+            let mut term = *v;
+            for (a, w) in weight.iter().enumerate().take(dims) {
+                term *= w[(c >> (dims - 1 - a)) & 1];
+            }
+            val = if c == 0 { term } else { val + term };
+        }
+        *o = val * 2.0 - 1.0;
+    }
+}
+
+// The lattice hashes and corner weights of one axis of the filtered random noise.
+fn filtered_axis(p: f32, factor: u32) -> ([u32; 2], [f32; 2]) {
+    let p = p + 0.00137;
+    let i = p.floor() as i32;
+    let lower = 1.0 + i as f32 - p;
+    let h = (i as u32).wrapping_mul(factor);
+    ([h, h.wrapping_add(factor)], [lower, 1.0 - lower])
+}
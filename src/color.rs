@@ -0,0 +1,259 @@
+//
+// Color gradients
+//
+
+/// An RGBA color with sRGB encoded channels in [0, 1] and straight alpha.
+pub type Rgba = [f32; 4];
+
+/// How colors between two stops are interpolated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Eases in and out of every stop.
+    Smoothstep,
+    /// The color of the previous stop, giving hard bands.
+    Constant,
+}
+
+/// The color space colors are interpolated in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// The sRGB encoded values, as most image editors do.
+    #[default]
+    Srgb,
+    /// Linear light, which keeps the brightness of mixed colors physically correct.
+    Linear,
+    /// The perceptual OKLab space, avoiding dull or hue shifted mid tones.
+    Oklab,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorStop {
+    pub position: f32,
+    pub color: Rgba,
+}
+
+/// A color ramp mapping values in [0, 1] to colors.
+///
+/// ```
+/// use noiselib::color::*;
+///
+/// let gradient = Gradient::new()
+///     .stop(0.0, [0.0, 0.0, 0.2, 1.0])
+///     .stop(0.6, color_from_hex("#e0c080").unwrap())
+///     .stop(1.0, [1.0, 1.0, 1.0, 1.0])
+///     .space(ColorSpace::Oklab);
+///
+/// let rgba = gradient.map_noise(0.25);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Gradient {
+    stops: Vec<ColorStop>,
+    interpolation: Interpolation,
+    space: ColorSpace,
+}
+
+impl Gradient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a color stop, keeping the stops ordered by position. A stop at the
+    /// position of an existing one is placed after it, giving a hard edge.
+    pub fn stop(mut self, position: f32, color: Rgba) -> Self {
+        let index = self.stops.partition_point(|s| s.position <= position);
+        self.stops.insert(index, ColorStop { position, color });
+        self
+    }
+
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn space(mut self, space: ColorSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops
+    }
+
+    /// The color at `t`. Values outside of the stops get the color of the nearest
+    /// stop, a gradient without stops is transparent black.
+    pub fn sample(&self, t: f32) -> Rgba {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return [0.0; 4],
+        };
+        if t.is_nan() || t <= first.position {
+            return first.color;
+        }
+        if t >= last.position {
+            return last.color;
+        }
+
+        let index = self.stops.partition_point(|s| s.position <= t);
+        let (a, b) = (&self.stops[index - 1], &self.stops[index]);
+        let f = (t - a.position) / (b.position - a.position);
+        let f = match self.interpolation {
+            Interpolation::Linear => f,
+            Interpolation::Smoothstep => f * f * (3.0 - 2.0 * f),
+            Interpolation::Constant => return a.color,
+        };
+        mix(a.color, b.color, f, self.space)
+    }
+
+    /// The color of `value` mapped from `range` onto [0, 1].
+    pub fn map(&self, value: f32, range: (f32, f32)) -> Rgba {
+        self.sample((value - range.0) / (range.1 - range.0))
+    }
+
+    /// The color of a noise value in [-1, 1].
+    pub fn map_noise(&self, value: f32) -> Rgba {
+        self.map(value, (-1.0, 1.0))
+    }
+
+    /// The color of `t` with 8-bit channels.
+    pub fn sample_u8(&self, t: f32) -> [u8; 4] {
+        self.sample(t)
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// Black to white.
+    pub fn grayscale() -> Self {
+        Self::new()
+            .stop(0.0, [0.0, 0.0, 0.0, 1.0])
+            .stop(1.0, [1.0, 1.0, 1.0, 1.0])
+    }
+
+    /// Deep water, shore, grass, rock and snow for heightmaps, with the shore line at 0.5.
+    pub fn terrain() -> Self {
+        Self::new()
+            .stop(0.0, hex("#0b1d4f"))
+            .stop(0.42, hex("#2a6fb0"))
+            .stop(0.5, hex("#d8c690"))
+            .stop(0.53, hex("#5b9a3c"))
+            .stop(0.7, hex("#356b2a"))
+            .stop(0.82, hex("#7a6a5a"))
+            .stop(0.92, hex("#b8b0a8"))
+            .stop(1.0, hex("#ffffff"))
+            .space(ColorSpace::Oklab)
+    }
+
+    /// Black through red and orange to pale yellow.
+    pub fn fire() -> Self {
+        Self::new()
+            .stop(0.0, hex("#000000"))
+            .stop(0.3, hex("#7a0a00"))
+            .stop(0.55, hex("#e23d00"))
+            .stop(0.8, hex("#ffa51f"))
+            .stop(1.0, hex("#fff7c2"))
+            .space(ColorSpace::Linear)
+    }
+
+    /// Creamy stone with dark veins at the centre of the range, for `sin`
+    /// modulated turbulence.
+    pub fn marble() -> Self {
+        Self::new()
+            .stop(0.0, hex("#f4efe6"))
+            .stop(0.4, hex("#d9d2c5"))
+            .stop(0.5, hex("#3b3f4a"))
+            .stop(0.6, hex("#d9d2c5"))
+            .stop(1.0, hex("#f4efe6"))
+            .interpolation(Interpolation::Smoothstep)
+            .space(ColorSpace::Oklab)
+    }
+}
+
+fn hex(code: &str) -> Rgba {
+    color_from_hex(code).expect("preset colors are valid")
+}
+
+/// Parses `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, the `#` being optional.
+pub fn color_from_hex(code: &str) -> Option<Rgba> {
+    let code = code.strip_prefix('#').unwrap_or(code);
+    if !code.is_ascii() {
+        return None;
+    }
+    let digits = match code.len() {
+        3 | 4 => 1,
+        6 | 8 => 2,
+        _ => return None,
+    };
+    let mut color = [1.0; 4];
+    for (i, c) in color.iter_mut().enumerate().take(code.len() / digits) {
+        let v = u8::from_str_radix(&code[i * digits..(i + 1) * digits], 16).ok()?;
+        *c = if digits == 1 { v * 17 } else { v } as f32 / 255.0;
+    }
+    Some(color)
+}
+
+fn mix(a: Rgba, b: Rgba, f: f32, space: ColorSpace) -> Rgba {
+    let lerp = |a: f32, b: f32| a + (b - a) * f;
+    let alpha = lerp(a[3], b[3]);
+    let rgb = |c: Rgba| [c[0], c[1], c[2]];
+
+    let [r, g, bl] = match space {
+        ColorSpace::Srgb => [lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])],
+        ColorSpace::Linear => {
+            let (a, b) = (rgb(a).map(srgb_to_linear), rgb(b).map(srgb_to_linear));
+            [lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])].map(linear_to_srgb)
+        }
+        ColorSpace::Oklab => {
+            let (a, b) = (srgb_to_oklab(rgb(a)), srgb_to_oklab(rgb(b)));
+            oklab_to_srgb([lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])])
+        }
+    };
+    [r, g, bl, alpha]
+}
+
+//
+// Color space conversions
+//
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts an sRGB color to OKLab `[L, a, b]`.
+pub fn srgb_to_oklab(c: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = c.map(srgb_to_linear);
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+/// Converts an OKLab color to sRGB.
+pub fn oklab_to_srgb(c: [f32; 3]) -> [f32; 3] {
+    let [l, a, b] = c;
+    let l_ = l + 0.396_337_78 * a + 0.215_803_76 * b;
+    let m_ = l - 0.105_561_346 * a - 0.063_854_17 * b;
+    let s_ = l - 0.089_484_18 * a - 1.291_485_5 * b;
+    let (l, m, s) = (l_ * l_ * l_, m_ * m_ * m_, s_ * s_ * s_);
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+    .map(linear_to_srgb)
+}
@@ -0,0 +1,1110 @@
+use crate::prelude::*;
+
+//
+// Expression language for noise formulas
+//
+
+/// A noise formula parsed at runtime, like `fbm(perlin, p * 4, 6) * 0.5 + worley(p)`.
+///
+/// Values are floats or vectors of 2 to 4 components. Arithmetic (`+ - * / % ^`)
+/// works component-wise, a float combined with a vector is applied to every
+/// component. Vectors are built with `vec2` to `vec4` and their components are
+/// accessed by swizzles like `p.x` or `p.zyx`.
+///
+/// Variables:
+/// - `p`: the sample position, with as many components as given to [`Expression::eval`].
+/// - `x`, `y`, `z`, `t`: the components of `p`.
+/// - `pi`.
+///
+/// Noise functions, evaluated in the dimension of their position:
+/// - `random(p, seed)`, `random_filtered(p, seed)`, `perlin(p, seed)`, `simplex(p, seed)`,
+///   `musgrave(p, seed)`, `worley_f1(p, seed)` (also `worley`). The seed defaults to 0.
+/// - `perlin_deriv(p, seed)`, `simplex_deriv(p, seed)`: the noise value followed by its
+///   derivatives, for 2D and 3D positions.
+///
+/// Fractals take the name of the noise as first argument, all arguments after the
+/// position are optional:
+/// - `fbm`, `turbulence`, `ridged`: `(noise, p, octaves, freq_falloff, lacunarity, seed)`.
+/// - `multiplicative`: `(noise, p, octaves, freq_falloff, lacunarity, offset, seed)`.
+/// - `erosion`: `(noise, p, octaves, freq_falloff, lacunarity, seed)` of perlin or simplex
+///   noise, for 2D and 3D positions.
+/// - `fbm_deriv`: the same arguments as `erosion`, returning the fBm value followed by
+///   its derivatives.
+///
+/// Math functions: `abs`, `sign`, `floor`, `ceil`, `fract`, `sqrt`, `exp`, `log`, `sin`,
+/// `cos`, `tan`, `min`, `max`, `pow`, `atan2`, `mod`, `step`, `clamp`, `mix`,
+/// `smoothstep`, `length`, `distance`, `dot`, `normalize`.
+///
+/// ```
+/// use noiselib::expr::Expression;
+/// use noiselib::prelude::*;
+///
+/// let expr = Expression::parse("fbm(perlin, p * 4, 6) * 0.5 + worley(p)").unwrap();
+/// let mut rng = UniformRandomGen::new(0);
+/// let value = expr.eval(&mut rng, &[0.5, 1.5]).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    root: Ast,
+}
+
+/// The value of an expression.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+}
+
+/// An error of parsing or evaluating an expression, at a 1-based column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExprError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+impl ExprError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        Self {
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// The error below the expression it occurred in, with a caret at its column.
+    pub fn show(&self, source: &str) -> String {
+        format!(
+            "{}\n{}^ {}",
+            source,
+            " ".repeat(self.column.saturating_sub(1)),
+            self.message
+        )
+    }
+}
+
+impl Value {
+    fn from_slice(c: &[f32]) -> Self {
+        match *c {
+            [x] => Value::Float(x),
+            [x, y] => Value::Vec2([x, y]),
+            [x, y, z] => Value::Vec3([x, y, z]),
+            [x, y, z, w] => Value::Vec4([x, y, z, w]),
+            _ => unreachable!("values have 1 to 4 components"),
+        }
+    }
+
+    /// The components of the value, a float having one.
+    pub fn components(&self) -> &[f32] {
+        match self {
+            Value::Float(x) => std::slice::from_ref(x),
+            Value::Vec2(c) => c,
+            Value::Vec3(c) => c,
+            Value::Vec4(c) => c,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            Value::Float(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        ["float", "vec2", "vec3", "vec4"][self.components().len() - 1]
+    }
+
+    fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        let c = self.components();
+        let mut out = [0.0; 4];
+        for (o, v) in out.iter_mut().zip(c) {
+            *o = f(*v);
+        }
+        Value::from_slice(&out[..c.len()])
+    }
+
+    // Combines two values component-wise, broadcasting floats. None if the sizes differ.
+    fn zip(self, other: Self, f: impl Fn(f32, f32) -> f32) -> Option<Self> {
+        let (a, b) = (self.components(), other.components());
+        let len = match (a.len(), b.len()) {
+            (1, n) | (n, 1) => n,
+            (n, m) if n == m => n,
+            _ => return None,
+        };
+        let get = |c: &[f32], i: usize| if c.len() == 1 { c[0] } else { c[i] };
+        let mut out = [0.0; 4];
+        for (i, o) in out.iter_mut().enumerate().take(len) {
+            *o = f(get(a, i), get(b, i));
+        }
+        Some(Value::from_slice(&out[..len]))
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Float(x) => write!(f, "{}", x),
+            v => {
+                write!(f, "{}(", v.type_name())?;
+                for (i, c) in v.components().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ExprError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            end: source.chars().count() + 1,
+            depth: 0,
+        };
+        let root = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(ExprError::new(
+                token.column,
+                format!("expected an operator, found {}", token.kind),
+            ));
+        }
+        Ok(Self { root })
+    }
+
+    /// Evaluates the expression at the position `p` of 1 to 4 components.
+    pub fn eval(&self, rng: &mut UniformRandomGen, p: &[f32]) -> Result<Value, ExprError> {
+        assert!(
+            (1..=4).contains(&p.len()),
+            "positions have 1 to 4 components"
+        );
+        eval(&self.root, rng, p)
+    }
+
+    /// Evaluates the expression on a grid of `width` x `height` cells, row by row.
+    /// Cell `(i, j)` is evaluated at `origin + (i, j) * step`, the number of
+    /// components of `origin` (2 to 4) being the dimension of `p`. The expression
+    /// has to return a float.
+    pub fn eval_grid(
+        &self,
+        rng: &mut UniformRandomGen,
+        width: usize,
+        height: usize,
+        origin: &[f32],
+        step: f32,
+    ) -> Result<Vec<f32>, ExprError> {
+        assert!(
+            (2..=4).contains(&origin.len()),
+            "grid origins have 2 to 4 components"
+        );
+        let mut p = [0.0; 4];
+        p[..origin.len()].copy_from_slice(origin);
+
+        let mut values = Vec::with_capacity(width * height);
+        for j in 0..height {
+            for i in 0..width {
+                p[0] = origin[0] + i as f32 * step;
+                p[1] = origin[1] + j as f32 * step;
+                let value = eval(&self.root, rng, &p[..origin.len()])?;
+                match value {
+                    Value::Float(v) => values.push(v),
+                    v => {
+                        return Err(ExprError::new(
+                            1,
+                            format!(
+                                "the expression returns a {}, expected a float",
+                                v.type_name()
+                            ),
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+impl std::str::FromStr for Expression {
+    type Err = ExprError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+//
+// Tokenizer
+//
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Number(f32),
+    Ident(String),
+    Symbol(char),
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Number(n) => write!(f, "number {}", n),
+            TokenKind::Ident(s) => write!(f, "'{}'", s),
+            TokenKind::Symbol(c) => write!(f, "'{}'", c),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExprError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let column = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: TokenKind::Ident(chars[start..i].iter().collect()),
+                column,
+            });
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = text
+                .parse::<f32>()
+                .map_err(|_| ExprError::new(column, format!("invalid number '{}'", text)))?;
+            tokens.push(Token {
+                kind: TokenKind::Number(value),
+                column,
+            });
+        } else if "+-*/%^(),.".contains(c) {
+            i += 1;
+            tokens.push(Token {
+                kind: TokenKind::Symbol(c),
+                column,
+            });
+        } else {
+            return Err(ExprError::new(
+                column,
+                format!("unexpected character '{}'", c),
+            ));
+        }
+    }
+
+    Ok(tokens)
+}
+
+//
+// Parser
+//
+
+#[derive(Clone, Debug, PartialEq)]
+struct Ast {
+    kind: AstKind,
+    column: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum AstKind {
+    Number(f32),
+    Position,
+    Component(usize),
+    Neg(Box<Ast>),
+    Binary(char, Box<Ast>, Box<Ast>),
+    Swizzle(Box<Ast>, Vec<usize>),
+    Call(Builtin, Vec<Ast>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Builtin {
+    Noise(NoiseKind),
+    NoiseDeriv(NoiseKind),
+    Fractal(NoiseKind, FractalKind),
+    Erosion(NoiseKind),
+    FbmDeriv(NoiseKind),
+    Vec(usize),
+    Math(&'static str),
+}
+
+// Math functions with their number of arguments.
+const MATH: [(&str, usize); 24] = [
+    ("abs", 1),
+    ("sign", 1),
+    ("floor", 1),
+    ("ceil", 1),
+    ("fract", 1),
+    ("sqrt", 1),
+    ("exp", 1),
+    ("log", 1),
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("length", 1),
+    ("normalize", 1),
+    ("min", 2),
+    ("max", 2),
+    ("pow", 2),
+    ("atan2", 2),
+    ("mod", 2),
+    ("step", 2),
+    ("distance", 2),
+    ("dot", 2),
+    ("clamp", 3),
+    ("mix", 3),
+    ("smoothstep", 3),
+];
+
+fn function_names() -> impl Iterator<Item = &'static str> {
+    NoiseKind::ALL
+        .into_iter()
+        .map(|n| n.name())
+        .chain(FractalKind::ALL.into_iter().map(|k| k.name()))
+        .chain([
+            "worley",
+            "perlin_deriv",
+            "simplex_deriv",
+            "erosion",
+            "fbm_deriv",
+            "vec2",
+            "vec3",
+            "vec4",
+        ])
+        .chain(MATH.iter().map(|(name, _)| *name))
+}
+
+// The name closest to `name` if it is a likely typo.
+fn suggest<'a>(name: &str, names: impl Iterator<Item = &'a str>) -> String {
+    fn distance(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut prev = row[0];
+            row[0] = i + 1;
+            for (j, cb) in b.iter().enumerate() {
+                let cost = if ca == *cb { prev } else { prev + 1 };
+                prev = row[j + 1];
+                row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+            }
+        }
+        row[b.len()]
+    }
+
+    names
+        .map(|n| (distance(name, n), n))
+        .filter(|(d, n)| *d <= 2 && *d < n.len())
+        .min()
+        .map(|(_, n)| format!(", did you mean '{}'?", n))
+        .unwrap_or_default()
+}
+
+// The deepest nesting of parentheses, calls and unary operators the parser accepts,
+// which keeps the recursive descent and evaluation from overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    end: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek().map(|t| &t.kind) == Some(&TokenKind::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ExprError> {
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::Symbol(c),
+                ..
+            }) if c == symbol => Ok(()),
+            Some(token) => Err(ExprError::new(
+                token.column,
+                format!("expected '{}', found {}", symbol, token.kind),
+            )),
+            None => Err(ExprError::new(
+                self.end,
+                format!("expected '{}' at the end of the expression", symbol),
+            )),
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.peek().map_or(self.end, |t| t.column)
+    }
+
+    fn expr(&mut self) -> Result<Ast, ExprError> {
+        let mut left = self.term()?;
+        loop {
+            let column = self.column();
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(left);
+            };
+            let right = self.term()?;
+            left = Ast {
+                kind: AstKind::Binary(op, Box::new(left), Box::new(right)),
+                column,
+            };
+        }
+    }
+
+    fn term(&mut self) -> Result<Ast, ExprError> {
+        let mut left = self.unary()?;
+        loop {
+            let column = self.column();
+            let op = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else if self.eat('%') {
+                '%'
+            } else {
+                return Ok(left);
+            };
+            let right = self.unary()?;
+            left = Ast {
+                kind: AstKind::Binary(op, Box::new(left), Box::new(right)),
+                column,
+            };
+        }
+    }
+
+    // Every nested expression passes through here, so this tracks the depth.
+    fn unary(&mut self) -> Result<Ast, ExprError> {
+        let column = self.column();
+        if self.depth == MAX_DEPTH {
+            return Err(ExprError::new(
+                column,
+                format!("the expression is nested deeper than {} levels", MAX_DEPTH),
+            ));
+        }
+        self.depth += 1;
+        let value = if self.eat('-') {
+            self.unary().map(|value| Ast {
+                kind: AstKind::Neg(Box::new(value)),
+                column,
+            })
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.power()
+        };
+        self.depth -= 1;
+        value
+    }
+
+    fn power(&mut self) -> Result<Ast, ExprError> {
+        let base = self.postfix()?;
+        let column = self.column();
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            Ok(Ast {
+                kind: AstKind::Binary('^', Box::new(base), Box::new(exponent)),
+                column,
+            })
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Ast, ExprError> {
+        let mut value = self.primary()?;
+        while self.eat('.') {
+            let (name, column) = match self.next() {
+                Some(Token {
+                    kind: TokenKind::Ident(name),
+                    column,
+                }) => (name, column),
+                Some(token) => {
+                    return Err(ExprError::new(
+                        token.column,
+                        format!("expected a swizzle like 'xy', found {}", token.kind),
+                    ))
+                }
+                None => return Err(ExprError::new(self.end, "expected a swizzle like 'xy'")),
+            };
+            let indices: Option<Vec<usize>> = name.chars().map(|c| "xyzw".find(c)).collect();
+            match indices {
+                Some(indices) if indices.len() <= 4 => {
+                    value = Ast {
+                        kind: AstKind::Swizzle(Box::new(value), indices),
+                        column,
+                    }
+                }
+                _ => {
+                    return Err(ExprError::new(
+                        column,
+                        format!(
+                            "invalid swizzle '{}', use 1 to 4 of 'x', 'y', 'z' and 'w'",
+                            name
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    fn primary(&mut self) -> Result<Ast, ExprError> {
+        let token = self
+            .next()
+            .ok_or_else(|| ExprError::new(self.end, "unexpected end of the expression"))?;
+        let column = token.column;
+
+        match token.kind {
+            TokenKind::Number(n) => Ok(Ast {
+                kind: AstKind::Number(n),
+                column,
+            }),
+            TokenKind::Symbol('(') => {
+                let value = self.expr()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            TokenKind::Ident(name) if self.eat('(') => self.call(&name, column),
+            TokenKind::Ident(name) => {
+                let kind = match name.as_str() {
+                    "p" => AstKind::Position,
+                    "x" => AstKind::Component(0),
+                    "y" => AstKind::Component(1),
+                    "z" => AstKind::Component(2),
+                    "t" => AstKind::Component(3),
+                    "pi" => AstKind::Number(std::f32::consts::PI),
+                    _ if function_names().any(|n| n == name) => {
+                        return Err(ExprError::new(
+                            column,
+                            format!("'{}' is a function, call it like {}(p)", name, name),
+                        ))
+                    }
+                    _ => {
+                        return Err(ExprError::new(
+                            column,
+                            format!(
+                                "unknown variable '{}'{}",
+                                name,
+                                suggest(&name, ["p", "x", "y", "z", "t", "pi"].into_iter())
+                            ),
+                        ))
+                    }
+                };
+                Ok(Ast { kind, column })
+            }
+            kind => Err(ExprError::new(
+                column,
+                format!("expected a value, found {}", kind),
+            )),
+        }
+    }
+
+    // Parses the noise name which is the first argument of fractals.
+    fn noise_arg(&mut self, function: &str) -> Result<(NoiseKind, usize), ExprError> {
+        let token = self.next();
+        let result = match &token {
+            Some(Token {
+                kind: TokenKind::Ident(name),
+                column,
+            }) => {
+                let name = if name == "worley" { "worley_f1" } else { name };
+                match NoiseKind::from_name(name) {
+                    Some(noise) => Ok((noise, *column)),
+                    None => Err(ExprError::new(
+                        *column,
+                        format!(
+                            "unknown noise '{}'{}",
+                            name,
+                            suggest(name, NoiseKind::ALL.into_iter().map(|n| n.name()))
+                        ),
+                    )),
+                }
+            }
+            Some(token) => Err(ExprError::new(
+                token.column,
+                format!(
+                    "{} expects a noise name like perlin as its first argument",
+                    function
+                ),
+            )),
+            None => Err(ExprError::new(self.end, "unexpected end of the expression")),
+        };
+        if result.is_ok() && !self.eat(',') {
+            return Err(ExprError::new(
+                self.column(),
+                format!("{} expects a position after the noise name", function),
+            ));
+        }
+        result
+    }
+
+    fn call(&mut self, name: &str, column: usize) -> Result<Ast, ExprError> {
+        let lookup = if name == "worley" { "worley_f1" } else { name };
+
+        let (builtin, min, max) = if let Some(noise) = NoiseKind::from_name(lookup) {
+            (Builtin::Noise(noise), 1, 2)
+        } else if let Some(kind) = FractalKind::from_name(name) {
+            let (noise, _) = self.noise_arg(name)?;
+            let max = if kind == FractalKind::Multiplicative {
+                6
+            } else {
+                5
+            };
+            (Builtin::Fractal(noise, kind), 1, max)
+        } else if name == "erosion" || name == "fbm_deriv" {
+            let (noise, noise_column) = self.noise_arg(name)?;
+            if !matches!(noise, NoiseKind::Perlin | NoiseKind::Simplex) {
+                return Err(ExprError::new(
+                    noise_column,
+                    format!("{} needs perlin or simplex noise, not {}", name, noise),
+                ));
+            }
+            if name == "erosion" {
+                (Builtin::Erosion(noise), 1, 5)
+            } else {
+                (Builtin::FbmDeriv(noise), 1, 5)
+            }
+        } else if name == "perlin_deriv" {
+            (Builtin::NoiseDeriv(NoiseKind::Perlin), 1, 2)
+        } else if name == "simplex_deriv" {
+            (Builtin::NoiseDeriv(NoiseKind::Simplex), 1, 2)
+        } else if let Some(n) = ["vec2", "vec3", "vec4"].iter().position(|v| *v == name) {
+            (Builtin::Vec(n + 2), 1, n + 2)
+        } else if let Some((math, n)) = MATH.iter().find(|(m, _)| *m == name) {
+            (Builtin::Math(math), *n, *n)
+        } else {
+            return Err(ExprError::new(
+                column,
+                format!(
+                    "unknown function '{}'{}",
+                    name,
+                    suggest(name, function_names())
+                ),
+            ));
+        };
+
+        let mut args = vec![];
+        if !self.eat(')') {
+            loop {
+                args.push(self.expr()?);
+                if self.eat(')') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+
+        if args.len() < min || args.len() > max {
+            let count = match (min, max) {
+                (min, max) if min == max => format!("{}", min),
+                (min, max) => format!("{} to {}", min, max),
+            };
+            // Fractals and erosion count their noise name as an argument.
+            let (count, given) = match builtin {
+                Builtin::Fractal(..) | Builtin::Erosion(_) | Builtin::FbmDeriv(_) => {
+                    (format!("{} to {}", min + 1, max + 1), args.len() + 1)
+                }
+                _ => (count, args.len()),
+            };
+            return Err(ExprError::new(
+                column,
+                format!("{} takes {} arguments, found {}", name, count, given),
+            ));
+        }
+
+        Ok(Ast {
+            kind: AstKind::Call(builtin, args),
+            column,
+        })
+    }
+}
+
+//
+// Evaluation
+//
+
+fn eval(ast: &Ast, rng: &mut UniformRandomGen, p: &[f32]) -> Result<Value, ExprError> {
+    let err = |message: String| Err(ExprError::new(ast.column, message));
+
+    match &ast.kind {
+        AstKind::Number(n) => Ok(Value::Float(*n)),
+        AstKind::Position => Ok(Value::from_slice(p)),
+        AstKind::Component(i) => match p.get(*i) {
+            Some(v) => Ok(Value::Float(*v)),
+            None => err(format!(
+                "'{}' is not available for {}D positions",
+                ["x", "y", "z", "t"][*i],
+                p.len()
+            )),
+        },
+        AstKind::Neg(value) => Ok(eval(value, rng, p)?.map(|v| -v)),
+        AstKind::Binary(op, a, b) => {
+            let (a, b) = (eval(a, rng, p)?, eval(b, rng, p)?);
+            let f = match op {
+                '+' => |a, b| a + b,
+                '-' => |a, b| a - b,
+                '*' => |a, b| a * b,
+                '/' => |a, b| a / b,
+                '%' => |a: f32, b: f32| a - b * (a / b).floor(),
+                _ => f32::powf,
+            };
+            match a.zip(b, f) {
+                Some(v) => Ok(v),
+                None => err(format!(
+                    "cannot apply '{}' to {} and {}",
+                    op,
+                    a.type_name(),
+                    b.type_name()
+                )),
+            }
+        }
+        AstKind::Swizzle(value, indices) => {
+            let value = eval(value, rng, p)?;
+            let c = value.components();
+            let mut out = [0.0; 4];
+            for (o, i) in out.iter_mut().zip(indices) {
+                match c.get(*i) {
+                    Some(v) => *o = *v,
+                    None => {
+                        return err(format!(
+                            "a {} has no component '{}'",
+                            value.type_name(),
+                            ['x', 'y', 'z', 'w'][*i]
+                        ))
+                    }
+                }
+            }
+            Ok(Value::from_slice(&out[..indices.len()]))
+        }
+        AstKind::Call(builtin, args) => {
+            let mut values = [Value::Float(0.0); 6];
+            for (value, arg) in values.iter_mut().zip(args) {
+                *value = eval(arg, rng, p)?;
+            }
+            call(*builtin, &values[..args.len()], args, ast.column, rng)
+        }
+    }
+}
+
+fn float_arg(
+    values: &[Value],
+    args: &[Ast],
+    i: usize,
+    name: &str,
+) -> Result<Option<f32>, ExprError> {
+    match values.get(i) {
+        None => Ok(None),
+        Some(Value::Float(v)) => Ok(Some(*v)),
+        Some(v) => Err(ExprError::new(
+            args[i].column,
+            format!("{} has to be a float, found {}", name, v.type_name()),
+        )),
+    }
+}
+
+fn seed_arg(values: &[Value], args: &[Ast], i: usize) -> Result<u32, ExprError> {
+    match float_arg(values, args, i, "the seed")? {
+        None => Ok(0),
+        Some(v) if (0.0..4294967296.0).contains(&v) && v.fract() == 0.0 => Ok(v as u32),
+        Some(v) => Err(ExprError::new(
+            args[i].column,
+            format!("invalid seed {}", v),
+        )),
+    }
+}
+
+fn call(
+    builtin: Builtin,
+    values: &[Value],
+    args: &[Ast],
+    call_column: usize,
+    rng: &mut UniformRandomGen,
+) -> Result<Value, ExprError> {
+    let column = args.first().map_or(call_column, |a| a.column);
+    let position = |dims: &[usize]| -> Result<[f32; 4], ExprError> {
+        let c = values[0].components();
+        if !dims.contains(&c.len()) {
+            return Err(ExprError::new(
+                column,
+                format!("the position cannot be a {}", values[0].type_name()),
+            ));
+        }
+        let mut p = [0.0; 4];
+        p[..c.len()].copy_from_slice(c);
+        Ok(p)
+    };
+    let dims = values.first().map_or(0, |v| v.components().len());
+
+    match builtin {
+        Builtin::Noise(noise) => {
+            let seed = seed_arg(values, args, 1)?;
+            let [x, y, z, t] = position(&[1, 2, 3, 4])?;
+            Ok(Value::Float(match dims {
+                1 => noise.eval_1d(rng, x, seed),
+                2 => noise.eval_2d(rng, x, y, seed),
+                3 => noise.eval_3d(rng, x, y, z, seed),
+                _ => noise.eval_4d(rng, x, y, z, t, seed),
+            }))
+        }
+        Builtin::NoiseDeriv(noise) => {
+            let seed = seed_arg(values, args, 1)?;
+            let [x, y, z, _] = position(&[2, 3])?;
+            Ok(match (noise, dims) {
+                (NoiseKind::Perlin, 2) => Value::Vec3(perlin_noise_deriv_2d(rng, x, y, seed)),
+                (NoiseKind::Perlin, _) => Value::Vec4(perlin_noise_deriv_3d(rng, x, y, z, seed)),
+                (_, 2) => Value::Vec3(simplex_noise_deriv_2d(rng, x, y, seed)),
+                _ => Value::Vec4(simplex_noise_deriv_3d(rng, x, y, z, seed)),
+            })
+        }
+        Builtin::Fractal(noise, kind) => {
+            let [x, y, z, t] = position(&[1, 2, 3, 4])?;
+            let defaults = Fractal::default();
+            let mut builder = Fractal::builder(kind)
+                .octaves(float_arg(values, args, 1, "octaves")?.unwrap_or(defaults.octaves()))
+                .freq_falloff(
+                    float_arg(values, args, 2, "freq_falloff")?.unwrap_or(defaults.freq_falloff()),
+                )
+                .lacunarity(
+                    float_arg(values, args, 3, "lacunarity")?.unwrap_or(defaults.lacunarity()),
+                );
+            let seed_index = if kind == FractalKind::Multiplicative {
+                builder = builder
+                    .offset(float_arg(values, args, 4, "offset")?.unwrap_or(defaults.offset()));
+                5
+            } else {
+                4
+            };
+            let fractal = builder
+                .seed(seed_arg(values, args, seed_index)?)
+                .build()
+                .map_err(|e| ExprError::new(call_column, e.to_string()))?;
+
+            Ok(Value::Float(match dims {
+                1 => fractal.eval_1d(rng, x, |rng, x, s| noise.eval_1d(rng, x, s)),
+                2 => fractal.eval_2d(rng, x, y, |rng, x, y, s| noise.eval_2d(rng, x, y, s)),
+                3 => fractal.eval_3d(rng, x, y, z, |rng, x, y, z, s| {
+                    noise.eval_3d(rng, x, y, z, s)
+                }),
+                _ => fractal.eval_4d(rng, x, y, z, t, |rng, x, y, z, t, s| {
+                    noise.eval_4d(rng, x, y, z, t, s)
+                }),
+            }))
+        }
+        Builtin::Erosion(noise) | Builtin::FbmDeriv(noise) => {
+            let [x, y, z, _] = position(&[2, 3])?;
+            let defaults = Fractal::default();
+            let octaves = float_arg(values, args, 1, "octaves")?.unwrap_or(defaults.octaves());
+            let freq_falloff =
+                float_arg(values, args, 2, "freq_falloff")?.unwrap_or(defaults.freq_falloff());
+            let lacunarity =
+                float_arg(values, args, 3, "lacunarity")?.unwrap_or(defaults.lacunarity());
+            Fractal::builder(FractalKind::Fbm)
+                .octaves(octaves)
+                .freq_falloff(freq_falloff)
+                .lacunarity(lacunarity)
+                .build()
+                .map_err(|e| ExprError::new(call_column, e.to_string()))?;
+            let seed = seed_arg(values, args, 4)?;
+
+            let (o, g, l) = (octaves, freq_falloff, lacunarity);
+            if let Builtin::FbmDeriv(_) = builtin {
+                return Ok(match (noise, dims) {
+                    (NoiseKind::Perlin, 2) => Value::Vec3(fractal_noise_add_deriv_2d(
+                        rng,
+                        x,
+                        y,
+                        perlin_noise_deriv_2d,
+                        o,
+                        g,
+                        l,
+                        seed,
+                    )),
+                    (NoiseKind::Perlin, _) => Value::Vec4(fractal_noise_add_deriv_3d(
+                        rng,
+                        x,
+                        y,
+                        z,
+                        perlin_noise_deriv_3d,
+                        o,
+                        g,
+                        l,
+                        seed,
+                    )),
+                    (_, 2) => Value::Vec3(fractal_noise_add_deriv_2d(
+                        rng,
+                        x,
+                        y,
+                        simplex_noise_deriv_2d,
+                        o,
+                        g,
+                        l,
+                        seed,
+                    )),
+                    _ => Value::Vec4(fractal_noise_add_deriv_3d(
+                        rng,
+                        x,
+                        y,
+                        z,
+                        simplex_noise_deriv_3d,
+                        o,
+                        g,
+                        l,
+                        seed,
+                    )),
+                });
+            }
+            Ok(Value::Float(match (noise, dims) {
+                (NoiseKind::Perlin, 2) => {
+                    fractal_noise_erosion_2d(rng, x, y, perlin_noise_deriv_2d, o, g, l, seed)
+                }
+                (NoiseKind::Perlin, _) => {
+                    fractal_noise_erosion_3d(rng, x, y, z, perlin_noise_deriv_3d, o, g, l, seed)
+                }
+                (_, 2) => {
+                    fractal_noise_erosion_2d(rng, x, y, simplex_noise_deriv_2d, o, g, l, seed)
+                }
+                _ => fractal_noise_erosion_3d(rng, x, y, z, simplex_noise_deriv_3d, o, g, l, seed),
+            }))
+        }
+        Builtin::Vec(n) => {
+            let mut c = vec![];
+            for v in values {
+                c.extend_from_slice(v.components());
+            }
+            if let [v] = c[..] {
+                c = vec![v; n];
+            }
+            if c.len() != n {
+                return Err(ExprError::new(
+                    column,
+                    format!("vec{} needs {} components, found {}", n, n, c.len()),
+                ));
+            }
+            Ok(Value::from_slice(&c))
+        }
+        Builtin::Math(name) => math(name, values, args),
+    }
+}
+
+fn math(name: &str, values: &[Value], args: &[Ast]) -> Result<Value, ExprError> {
+    let column = args[0].column;
+    let mismatch = |a: &Value, b: &Value| {
+        ExprError::new(
+            column,
+            format!(
+                "{} cannot combine {} and {}",
+                name,
+                a.type_name(),
+                b.type_name()
+            ),
+        )
+    };
+    let zip =
+        |a: Value, b: Value, f: fn(f32, f32) -> f32| a.zip(b, f).ok_or_else(|| mismatch(&a, &b));
+    let dot = |a: Value, b: Value| -> Result<f32, ExprError> {
+        Ok(zip(a, b, |a, b| a * b)?.components().iter().sum())
+    };
+
+    let v = values[0];
+    Ok(match name {
+        "abs" => v.map(f32::abs),
+        "sign" => v.map(|x| if x == 0.0 { 0.0 } else { x.signum() }),
+        "floor" => v.map(f32::floor),
+        "ceil" => v.map(f32::ceil),
+        "fract" => v.map(|x| x - x.floor()),
+        "sqrt" => v.map(f32::sqrt),
+        "exp" => v.map(f32::exp),
+        "log" => v.map(f32::ln),
+        "sin" => v.map(f32::sin),
+        "cos" => v.map(f32::cos),
+        "tan" => v.map(f32::tan),
+        "length" => Value::Float(dot(v, v)?.sqrt()),
+        "normalize" => {
+            let length = dot(v, v)?.sqrt();
+            v.map(|x| if length > 0.0 { x / length } else { 0.0 })
+        }
+        "min" => zip(v, values[1], f32::min)?,
+        "max" => zip(v, values[1], f32::max)?,
+        "pow" => zip(v, values[1], f32::powf)?,
+        "atan2" => zip(v, values[1], f32::atan2)?,
+        "mod" => zip(v, values[1], |a, b| a - b * (a / b).floor())?,
+        "step" => zip(v, values[1], |edge, x| if x < edge { 0.0 } else { 1.0 })?,
+        "distance" => {
+            let d = zip(v, values[1], |a, b| a - b)?;
+            Value::Float(dot(d, d)?.sqrt())
+        }
+        "dot" => Value::Float(dot(v, values[1])?),
+        "clamp" => zip(zip(v, values[1], f32::max)?, values[2], f32::min)?,
+        "mix" => {
+            let d = zip(values[1], v, |b, a| b - a)?;
+            zip(v, zip(d, values[2], |d, t| d * t)?, |a, d| a + d)?
+        }
+        _ => {
+            // smoothstep(edge0, edge1, x)
+            let range = zip(values[1], v, |e1, e0| e1 - e0)?;
+            let t = zip(zip(values[2], v, |x, e0| x - e0)?, range, |a, r| {
+                (a / r).clamp(0.0, 1.0)
+            })?;
+            t.map(|t| t * t * (3.0 - 2.0 * t))
+        }
+    })
+}
@@ -1,34 +1,32 @@
+#[cfg(all(not(feature = "std"), not(test)))]
+use crate::math::Float;
 use crate::prelude::UniformRandomGen;
 
 //
 // Multifractal
 //
 
+/// Fractional Brownian motion: the sum of all octaves divided by the sum of their
+/// amplitudes, which maps noises in [-1, 1] exactly onto [-1, 1].
 pub fn fractal_noise_add_1d<F: Fn(&mut UniformRandomGen, f32, u32) -> f32>(
     rng: &mut UniformRandomGen,
     x: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 0.0;
-
-    let mut power = 1.0;
-    let mut norma = power;
-
-    let mut x = x;
-
-    for _ in 0..octaves {
-        rnd_val += noise_func(rng, x, seed) * power;
-        norma += power;
-
-        power *= freq_falloff;
-        x *= lacunarity;
-    }
-
-    rnd_val / norma
+    fractal_octaves(
+        rng,
+        FractalKind::Fbm,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, seed),
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -37,29 +35,21 @@ pub fn fractal_noise_add_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> f32>(
     x: f32,
     y: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 0.0;
-
-    let mut power = 1.0;
-    let mut norma = power;
-
-    let mut x = x;
-    let mut y = y;
-
-    for _ in 0..octaves {
-        rnd_val += noise_func(rng, x, y, seed) * power;
-        norma += power;
-
-        power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
-    }
-
-    rnd_val / norma
+    fractal_octaves(
+        rng,
+        FractalKind::Fbm,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, seed),
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -69,31 +59,21 @@ pub fn fractal_noise_add_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) ->
     y: f32,
     z: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 0.0;
-
-    let mut power = 1.0;
-    let mut norma = power;
-
-    let mut x = x;
-    let mut y = y;
-    let mut z = z;
-
-    for _ in 0..octaves {
-        rnd_val += noise_func(rng, x, y, z, seed) * power;
-        norma += power;
-
-        power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
-        z *= lacunarity;
-    }
-
-    rnd_val / norma
+    fractal_octaves(
+        rng,
+        FractalKind::Fbm,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, z * freq, seed),
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -104,60 +84,44 @@ pub fn fractal_noise_add_4d<F: Fn(&mut UniformRandomGen, f32, f32, f32, f32, u32
     z: f32,
     t: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 0.0;
-
-    let mut power = 1.0;
-    let mut norma = power;
-
-    let mut x = x;
-    let mut y = y;
-    let mut z = z;
-    let mut t = t;
-
-    for _ in 0..octaves {
-        rnd_val += noise_func(rng, x, y, z, t, seed) * power;
-        norma += power;
-
-        power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
-        z *= lacunarity;
-        t *= lacunarity;
-    }
-
-    rnd_val / norma
+    fractal_octaves(
+        rng,
+        FractalKind::Fbm,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, z * freq, t * freq, seed),
+    )
 }
 
+/// Turbulence: the sum of the absolute octave values divided by the sum of their
+/// amplitudes, which maps noises in [-1, 1] exactly onto [0, 1].
 pub fn fractal_noise_add_abs_1d<F: Fn(&mut UniformRandomGen, f32, u32) -> f32>(
     rng: &mut UniformRandomGen,
     x: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 0.0;
-
-    let mut power = 1.0;
-    let mut norma = power;
-
-    let mut x = x;
-
-    for _ in 0..octaves {
-        rnd_val += noise_func(rng, x, seed).abs() * power;
-        norma += power;
-
-        power *= freq_falloff;
-        x *= lacunarity;
-    }
-
-    rnd_val / norma
+    fractal_octaves(
+        rng,
+        FractalKind::Turbulence,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, seed),
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -166,212 +130,960 @@ pub fn fractal_noise_add_abs_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> f
     x: f32,
     y: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 0.0;
-
-    let mut power = 1.0;
-    let mut norma = power;
+    fractal_octaves(
+        rng,
+        FractalKind::Turbulence,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, seed),
+    )
+}
 
-    let mut x = x;
-    let mut y = y;
+#[allow(clippy::too_many_arguments)]
+pub fn fractal_noise_add_abs_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    z: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    seed: u32,
+) -> f32 {
+    fractal_octaves(
+        rng,
+        FractalKind::Turbulence,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, z * freq, seed),
+    )
+}
 
-    for _ in 0..octaves {
-        rnd_val += noise_func(rng, x, y, seed).abs() * power;
-        norma += power;
+#[allow(clippy::too_many_arguments)]
+pub fn fractal_noise_add_abs_4d<F: Fn(&mut UniformRandomGen, f32, f32, f32, f32, u32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    z: f32,
+    t: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    seed: u32,
+) -> f32 {
+    fractal_octaves(
+        rng,
+        FractalKind::Turbulence,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, z * freq, t * freq, seed),
+    )
+}
 
-        power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
-    }
+/// Multiplicative fractal: the product of `(noise + offset) * amplitude` over all
+/// octaves, mapped linearly from its theoretical bounds (see [`fractal_mul_bounds`])
+/// onto [0, 1].
+#[allow(clippy::too_many_arguments)]
+pub fn fractal_noise_mul_1d<F: Fn(&mut UniformRandomGen, f32, u32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    offset: f32,
+    seed: u32,
+) -> f32 {
+    fractal_octaves(
+        rng,
+        FractalKind::Multiplicative,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        offset,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, seed),
+    )
+}
 
-    rnd_val / norma
+#[allow(clippy::too_many_arguments)]
+pub fn fractal_noise_mul_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    offset: f32,
+    seed: u32,
+) -> f32 {
+    fractal_octaves(
+        rng,
+        FractalKind::Multiplicative,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        offset,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, seed),
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
-pub fn fractal_noise_add_abs_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> f32>(
+pub fn fractal_noise_mul_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> f32>(
     rng: &mut UniformRandomGen,
     x: f32,
     y: f32,
     z: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
+    offset: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 0.0;
-
-    let mut power = 1.0;
-    let mut norma = power;
+    fractal_octaves(
+        rng,
+        FractalKind::Multiplicative,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        offset,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, z * freq, seed),
+    )
+}
 
-    let mut x = x;
-    let mut y = y;
-    let mut z = z;
+#[allow(clippy::too_many_arguments)]
+pub fn fractal_noise_mul_4d<F: Fn(&mut UniformRandomGen, f32, f32, f32, f32, u32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    z: f32,
+    t: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    offset: f32,
+    seed: u32,
+) -> f32 {
+    fractal_octaves(
+        rng,
+        FractalKind::Multiplicative,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        offset,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, z * freq, t * freq, seed),
+    )
+}
 
-    for _ in 0..octaves {
-        rnd_val += noise_func(rng, x, y, z, seed).abs() * power;
-        norma += power;
+/// Ridged fractal: the sum of `(1 - |noise|)^2` over all octaves divided by the
+/// sum of their amplitudes, which maps noises in [-1, 1] exactly onto [0, 1].
+pub fn fractal_noise_ridged_1d<F: Fn(&mut UniformRandomGen, f32, u32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    seed: u32,
+) -> f32 {
+    fractal_octaves(
+        rng,
+        FractalKind::Ridged,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, seed),
+    )
+}
 
-        power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
-        z *= lacunarity;
-    }
+#[allow(clippy::too_many_arguments)]
+pub fn fractal_noise_ridged_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    seed: u32,
+) -> f32 {
+    fractal_octaves(
+        rng,
+        FractalKind::Ridged,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, seed),
+    )
+}
 
-    rnd_val / norma
+#[allow(clippy::too_many_arguments)]
+pub fn fractal_noise_ridged_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    z: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    seed: u32,
+) -> f32 {
+    fractal_octaves(
+        rng,
+        FractalKind::Ridged,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, z * freq, seed),
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
-pub fn fractal_noise_add_abs_4d<F: Fn(&mut UniformRandomGen, f32, f32, f32, f32, u32) -> f32>(
+pub fn fractal_noise_ridged_4d<F: Fn(&mut UniformRandomGen, f32, f32, f32, f32, u32) -> f32>(
     rng: &mut UniformRandomGen,
     x: f32,
     y: f32,
     z: f32,
     t: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 0.0;
+    fractal_octaves(
+        rng,
+        FractalKind::Ridged,
+        octaves,
+        freq_falloff,
+        lacunarity,
+        0.0,
+        f32::INFINITY,
+        |rng, freq| noise_func(rng, x * freq, y * freq, z * freq, t * freq, seed),
+    )
+}
+
+//
+// Derivative fractals
+//
+
+/// Fractional Brownian motion of a noise with analytic derivatives, such as
+/// [`perlin_noise_deriv_2d`](crate::perlin::perlin_noise_deriv_2d), returned as
+/// `[value, d/dx, d/dy]`. The value is normalized like `fractal_noise_add_2d`.
+#[allow(clippy::too_many_arguments)]
+pub fn fractal_noise_add_deriv_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> [f32; 3]>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    noise_func: F,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    seed: u32,
+) -> [f32; 3] {
+    let mut rnd_val = [0.0; 3];
+    let mut norma = 0.0;
 
     let mut power = 1.0;
-    let mut norma = power;
+    let mut freq = 1.0;
 
-    let mut x = x;
-    let mut y = y;
-    let mut z = z;
-    let mut t = t;
+    for i in 0..octaves.ceil() as i32 {
+        let weight = octave_weight(octaves, i) * power;
+        let n = noise_func(rng, x * freq, y * freq, seed);
 
-    for _ in 0..octaves {
-        rnd_val += noise_func(rng, x, y, z, t, seed).abs() * power;
-        norma += power;
+        rnd_val[0] += n[0] * weight;
+        rnd_val[1] += n[1] * weight * freq;
+        rnd_val[2] += n[2] * weight * freq;
+        norma += weight;
 
         power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
-        z *= lacunarity;
-        t *= lacunarity;
+        freq *= lacunarity;
     }
 
-    rnd_val / norma
+    if norma > 0.0 {
+        rnd_val.map(|v| v / norma)
+    } else {
+        [0.0; 3]
+    }
 }
 
+/// The 3D version of [`fractal_noise_add_deriv_2d`], returned as
+/// `[value, d/dx, d/dy, d/dz]`.
 #[allow(clippy::too_many_arguments)]
-pub fn fractal_noise_mul_1d<F: Fn(&mut UniformRandomGen, f32, u32) -> f32>(
+pub fn fractal_noise_add_deriv_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> [f32; 4]>(
     rng: &mut UniformRandomGen,
     x: f32,
+    y: f32,
+    z: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
-    offset: f32,
     seed: u32,
-) -> f32 {
-    let mut rnd_val = 1.0;
+) -> [f32; 4] {
+    let mut rnd_val = [0.0; 4];
+    let mut norma = 0.0;
+
     let mut power = 1.0;
-    let mut x = x;
+    let mut freq = 1.0;
+
+    for i in 0..octaves.ceil() as i32 {
+        let weight = octave_weight(octaves, i) * power;
+        let n = noise_func(rng, x * freq, y * freq, z * freq, seed);
+
+        rnd_val[0] += n[0] * weight;
+        rnd_val[1] += n[1] * weight * freq;
+        rnd_val[2] += n[2] * weight * freq;
+        rnd_val[3] += n[3] * weight * freq;
+        norma += weight;
 
-    for _i in 0..octaves {
-        rnd_val *= (noise_func(rng, x, seed) + offset) * power;
         power *= freq_falloff;
-        x *= lacunarity;
+        freq *= lacunarity;
     }
 
-    rnd_val
+    if norma > 0.0 {
+        rnd_val.map(|v| v / norma)
+    } else {
+        [0.0; 4]
+    }
 }
 
+/// Erosion fractal after Inigo Quilez: fractional Brownian motion where each octave
+/// is divided by `1 + |d|^2`, `d` being the sum of the unscaled noise gradients
+/// of all octaves so far. Steep areas therefore receive less detail than flat ones,
+/// which resembles eroded terrain. Returns values in [-1, 1].
 #[allow(clippy::too_many_arguments)]
-pub fn fractal_noise_mul_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> f32>(
+pub fn fractal_noise_erosion_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> [f32; 3]>(
     rng: &mut UniformRandomGen,
     x: f32,
     y: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
-    offset: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 1.0;
+    let mut rnd_val = 0.0;
+    let mut norma = 0.0;
+    let mut d = [0.0; 2];
+
     let mut power = 1.0;
-    let mut x = x;
-    let mut y = y;
+    let mut freq = 1.0;
+
+    for i in 0..octaves.ceil() as i32 {
+        let partial = octave_weight(octaves, i);
+        let weight = partial * power;
+        let n = noise_func(rng, x * freq, y * freq, seed);
+
+        d[0] += n[1] * partial;
+        d[1] += n[2] * partial;
+
+        rnd_val += n[0] * weight / (1.0 + d[0] * d[0] + d[1] * d[1]);
+        norma += weight;
 
-    for _i in 0..octaves {
-        rnd_val *= (noise_func(rng, x, y, seed) + offset) * power;
         power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
+        freq *= lacunarity;
     }
 
-    rnd_val
+    if norma > 0.0 {
+        rnd_val / norma
+    } else {
+        0.0
+    }
 }
 
+/// The 3D version of [`fractal_noise_erosion_2d`].
 #[allow(clippy::too_many_arguments)]
-pub fn fractal_noise_mul_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> f32>(
+pub fn fractal_noise_erosion_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> [f32; 4]>(
     rng: &mut UniformRandomGen,
     x: f32,
     y: f32,
     z: f32,
     noise_func: F,
-    octaves: i32,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
-    offset: f32,
     seed: u32,
 ) -> f32 {
-    let mut rnd_val = 1.0;
+    let mut rnd_val = 0.0;
+    let mut norma = 0.0;
+    let mut d = [0.0; 3];
+
+    let mut power = 1.0;
+    let mut freq = 1.0;
+
+    for i in 0..octaves.ceil() as i32 {
+        let partial = octave_weight(octaves, i);
+        let weight = partial * power;
+        let n = noise_func(rng, x * freq, y * freq, z * freq, seed);
+
+        d[0] += n[1] * partial;
+        d[1] += n[2] * partial;
+        d[2] += n[3] * partial;
+
+        rnd_val += n[0] * weight / (1.0 + d[0] * d[0] + d[1] * d[1] + d[2] * d[2]);
+        norma += weight;
+
+        power *= freq_falloff;
+        freq *= lacunarity;
+    }
+
+    if norma > 0.0 {
+        rnd_val / norma
+    } else {
+        0.0
+    }
+}
+
+//
+// Shared octave loop
+//
+
+/// The theoretical minimum and maximum of the product computed by
+/// `fractal_noise_mul_*` before normalization, assuming the noise is in [-1, 1].
+pub fn fractal_mul_bounds(octaves: f32, freq_falloff: f32, offset: f32) -> (f32, f32) {
     let mut power = 1.0;
-    let mut x = x;
-    let mut y = y;
-    let mut z = z;
+    let (mut lo, mut hi) = (1.0, 1.0);
 
-    for _i in 0..octaves {
-        rnd_val *= (noise_func(rng, x, y, z, seed) + offset) * power;
+    for i in 0..octaves.ceil() as i32 {
+        let weight = octave_weight(octaves, i);
+        (lo, hi) = mul_interval(
+            lo,
+            hi,
+            1.0 + weight * ((offset - 1.0) * power - 1.0),
+            1.0 + weight * ((offset + 1.0) * power - 1.0),
+        );
         power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
-        z *= lacunarity;
     }
 
-    rnd_val
+    (lo, hi)
+}
+
+/// The fractional number of octaves which can be sampled without aliasing when a
+/// sample covers `filter_width` units of the input space, e.g. the footprint of a
+/// pixel derived from ray differentials. Octave `i` is at full strength below
+/// `fractal_nyquist_octaves() - 1` and reaches the Nyquist limit at
+/// `fractal_nyquist_octaves()`.
+pub fn fractal_nyquist_octaves(filter_width: f32, lacunarity: f32) -> f32 {
+    if filter_width > 0.0 {
+        (0.5 / filter_width).ln() / lacunarity.ln()
+    } else {
+        f32::INFINITY
+    }
+}
+
+// The weight of octave `i`: 1 for all full octaves, the fractional part of
+// `octaves` for the last, partial one.
+pub(crate) fn octave_weight(octaves: f32, i: i32) -> f32 {
+    (octaves - i as f32).clamp(0.0, 1.0)
+}
+
+// Exact product of the intervals [a0, a1] and [b0, b1].
+fn mul_interval(a0: f32, a1: f32, b0: f32, b1: f32) -> (f32, f32) {
+    let p = [a0 * b0, a0 * b1, a1 * b0, a1 * b1];
+    (
+        p.iter().copied().fold(f32::MAX, f32::min),
+        p.iter().copied().fold(f32::MIN, f32::max),
+    )
 }
 
+// Evaluates the octaves of every fractal mode. `sample` returns the noise at the
+// given frequency multiplier. A partial last octave is blended in by its weight,
+// for the multiplicative mode by blending its factor towards 1. Octaves past
+// `aa_octaves` are faded towards the centre of their range and not evaluated
+// once fully faded out.
 #[allow(clippy::too_many_arguments)]
-pub fn fractal_noise_mul_4d<F: Fn(&mut UniformRandomGen, f32, f32, f32, f32, u32) -> f32>(
+fn fractal_octaves<S: FnMut(&mut UniformRandomGen, f32) -> f32>(
     rng: &mut UniformRandomGen,
-    x: f32,
-    y: f32,
-    z: f32,
-    t: f32,
-    noise_func: F,
-    octaves: i32,
+    kind: FractalKind,
+    octaves: f32,
     freq_falloff: f32,
     lacunarity: f32,
     offset: f32,
-    seed: u32,
+    aa_octaves: f32,
+    mut sample: S,
 ) -> f32 {
-    let mut rnd_val = 1.0;
     let mut power = 1.0;
-    let mut x = x;
-    let mut y = y;
-    let mut z = z;
-    let mut t = t;
+    let mut freq = 1.0;
+
+    if kind == FractalKind::Multiplicative {
+        let mut rnd_val = 1.0;
+
+        for i in 0..octaves.ceil() as i32 {
+            let weight = octave_weight(octaves, i);
+            let fade = octave_weight(aa_octaves, i);
+            let n = match fade {
+                f if f >= 1.0 => sample(rng, freq),
+                f if f > 0.0 => sample(rng, freq) * f,
+                _ => 0.0,
+            };
+            rnd_val *= 1.0 + weight * ((n + offset) * power - 1.0);
+
+            power *= freq_falloff;
+            freq *= lacunarity;
+        }
+
+        let (lo, hi) = fractal_mul_bounds(octaves, freq_falloff, offset);
+        return if hi > lo {
+            (rnd_val - lo) / (hi - lo)
+        } else {
+            0.0
+        };
+    }
+
+    let mut rnd_val = 0.0;
+    let mut norma = 0.0;
+
+    for i in 0..octaves.ceil() as i32 {
+        let weight = octave_weight(octaves, i) * power;
+        let fade = octave_weight(aa_octaves, i);
+        let n = if fade > 0.0 { sample(rng, freq) } else { 0.0 };
+        let (n, centre) = match kind {
+            FractalKind::Turbulence => (n.abs(), 0.5),
+            FractalKind::Ridged => {
+                let ridge = 1.0 - n.abs();
+                (ridge * ridge, 0.5)
+            }
+            _ => (n, 0.0),
+        };
+        let n = if fade < 1.0 {
+            centre + (n - centre) * fade
+        } else {
+            n
+        };
+        rnd_val += n * weight;
+        norma += weight;
 
-    for _i in 0..octaves {
-        rnd_val *= (noise_func(rng, x, y, z, t, seed) + offset) * power;
         power *= freq_falloff;
-        x *= lacunarity;
-        y *= lacunarity;
-        z *= lacunarity;
-        t *= lacunarity;
+        freq *= lacunarity;
     }
 
-    rnd_val
+    if norma > 0.0 {
+        rnd_val / norma
+    } else {
+        0.0
+    }
+}
+
+//
+// Fractal settings
+//
+
+/// The highest octave count accepted by [`FractalBuilder::build`].
+pub const MAX_OCTAVES: f32 = 32.0;
+
+/// The way the octaves of a fractal are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FractalKind {
+    /// Fractional Brownian motion, see `fractal_noise_add_*`.
+    Fbm,
+    /// Sum of absolute octave values, see `fractal_noise_add_abs_*`.
+    Turbulence,
+    /// Product of offset octave values, see `fractal_noise_mul_*`.
+    Multiplicative,
+    /// Sum of inverted, squared absolute octave values, see `fractal_noise_ridged_*`.
+    Ridged,
+}
+
+impl FractalKind {
+    pub const ALL: [FractalKind; 4] = [
+        FractalKind::Fbm,
+        FractalKind::Turbulence,
+        FractalKind::Multiplicative,
+        FractalKind::Ridged,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FractalKind::Fbm => "fbm",
+            FractalKind::Turbulence => "turbulence",
+            FractalKind::Multiplicative => "multiplicative",
+            FractalKind::Ridged => "ridged",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// The exact output range of the fractal mode for noises in [-1, 1]:
+    /// [-1, 1] for [`FractalKind::Fbm`] and [0, 1] for all other modes.
+    pub fn range(self) -> (f32, f32) {
+        match self {
+            FractalKind::Fbm => (-1.0, 1.0),
+            _ => (0.0, 1.0),
+        }
+    }
+}
+
+/// A parameter rejected by [`FractalBuilder::build`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FractalError {
+    /// The octave count is outside of `[1, MAX_OCTAVES]`.
+    Octaves(f32),
+    /// The frequency falloff (gain) is not in `(0, 1]`.
+    FreqFalloff(f32),
+    /// The lacunarity is not a finite value greater than 1.
+    Lacunarity(f32),
+    /// The offset is not finite.
+    Offset(f32),
+}
+
+impl core::fmt::Display for FractalError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FractalError::Octaves(v) => {
+                write!(f, "octaves must be in [1, {}], got {}", MAX_OCTAVES, v)
+            }
+            FractalError::FreqFalloff(v) => write!(f, "freq_falloff must be in (0, 1], got {}", v),
+            FractalError::Lacunarity(v) => {
+                write!(f, "lacunarity must be greater than 1, got {}", v)
+            }
+            FractalError::Offset(v) => write!(f, "offset must be finite, got {}", v),
+        }
+    }
+}
+
+impl core::error::Error for FractalError {}
+
+/// Validated fractal parameters which evaluate any noise function of the crate.
+///
+/// ```
+/// use noiselib::prelude::*;
+///
+/// let fractal = Fractal::builder(FractalKind::Fbm)
+///     .octaves(6.0)
+///     .freq_falloff(0.5)
+///     .lacunarity(2.0)
+///     .seed(7)
+///     .build()
+///     .unwrap();
+///
+/// let mut rng = UniformRandomGen::new(7);
+/// let value = fractal.eval_2d(&mut rng, 0.3, 1.7, perlin_noise_2d);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fractal {
+    kind: FractalKind,
+    octaves: f32,
+    freq_falloff: f32,
+    lacunarity: f32,
+    offset: f32,
+    seed: u32,
+}
+
+impl Fractal {
+    pub fn builder(kind: FractalKind) -> FractalBuilder {
+        FractalBuilder::new(kind)
+    }
+
+    pub fn kind(&self) -> FractalKind {
+        self.kind
+    }
+
+    pub fn octaves(&self) -> f32 {
+        self.octaves
+    }
+
+    pub fn freq_falloff(&self) -> f32 {
+        self.freq_falloff
+    }
+
+    pub fn lacunarity(&self) -> f32 {
+        self.lacunarity
+    }
+
+    pub fn offset(&self) -> f32 {
+        self.offset
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// The output range of the `eval_*` functions, see [`FractalKind::range`].
+    pub fn range(&self) -> (f32, f32) {
+        self.kind.range()
+    }
+
+    pub fn eval_1d<F: Fn(&mut UniformRandomGen, f32, u32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        noise_func: F,
+    ) -> f32 {
+        let (o, g, l, s) = (self.octaves, self.freq_falloff, self.lacunarity, self.seed);
+        match self.kind {
+            FractalKind::Fbm => fractal_noise_add_1d(rng, x, noise_func, o, g, l, s),
+            FractalKind::Turbulence => fractal_noise_add_abs_1d(rng, x, noise_func, o, g, l, s),
+            FractalKind::Multiplicative => {
+                fractal_noise_mul_1d(rng, x, noise_func, o, g, l, self.offset, s)
+            }
+            FractalKind::Ridged => fractal_noise_ridged_1d(rng, x, noise_func, o, g, l, s),
+        }
+    }
+
+    pub fn eval_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        y: f32,
+        noise_func: F,
+    ) -> f32 {
+        let (o, g, l, s) = (self.octaves, self.freq_falloff, self.lacunarity, self.seed);
+        match self.kind {
+            FractalKind::Fbm => fractal_noise_add_2d(rng, x, y, noise_func, o, g, l, s),
+            FractalKind::Turbulence => fractal_noise_add_abs_2d(rng, x, y, noise_func, o, g, l, s),
+            FractalKind::Multiplicative => {
+                fractal_noise_mul_2d(rng, x, y, noise_func, o, g, l, self.offset, s)
+            }
+            FractalKind::Ridged => fractal_noise_ridged_2d(rng, x, y, noise_func, o, g, l, s),
+        }
+    }
+
+    pub fn eval_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        y: f32,
+        z: f32,
+        noise_func: F,
+    ) -> f32 {
+        let (o, g, l, s) = (self.octaves, self.freq_falloff, self.lacunarity, self.seed);
+        match self.kind {
+            FractalKind::Fbm => fractal_noise_add_3d(rng, x, y, z, noise_func, o, g, l, s),
+            FractalKind::Turbulence => {
+                fractal_noise_add_abs_3d(rng, x, y, z, noise_func, o, g, l, s)
+            }
+            FractalKind::Multiplicative => {
+                fractal_noise_mul_3d(rng, x, y, z, noise_func, o, g, l, self.offset, s)
+            }
+            FractalKind::Ridged => fractal_noise_ridged_3d(rng, x, y, z, noise_func, o, g, l, s),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn eval_4d<F: Fn(&mut UniformRandomGen, f32, f32, f32, f32, u32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        y: f32,
+        z: f32,
+        t: f32,
+        noise_func: F,
+    ) -> f32 {
+        let (o, g, l, s) = (self.octaves, self.freq_falloff, self.lacunarity, self.seed);
+        match self.kind {
+            FractalKind::Fbm => fractal_noise_add_4d(rng, x, y, z, t, noise_func, o, g, l, s),
+            FractalKind::Turbulence => {
+                fractal_noise_add_abs_4d(rng, x, y, z, t, noise_func, o, g, l, s)
+            }
+            FractalKind::Multiplicative => {
+                fractal_noise_mul_4d(rng, x, y, z, t, noise_func, o, g, l, self.offset, s)
+            }
+            FractalKind::Ridged => fractal_noise_ridged_4d(rng, x, y, z, t, noise_func, o, g, l, s),
+        }
+    }
+}
+
+impl Fractal {
+    /// Like [`Fractal::eval_1d`], but octaves above the Nyquist limit of a sample
+    /// covering `filter_width` units are faded out, see [`fractal_nyquist_octaves`].
+    /// Faded octaves are replaced by the centre of their range, so the overall
+    /// brightness stays stable with distance.
+    pub fn eval_footprint_1d<F: Fn(&mut UniformRandomGen, f32, u32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        filter_width: f32,
+        noise_func: F,
+    ) -> f32 {
+        self.footprint_octaves(rng, filter_width, |rng, freq| {
+            noise_func(rng, x * freq, self.seed)
+        })
+    }
+
+    /// Like [`Fractal::eval_2d`], but anti-aliased, see [`Fractal::eval_footprint_1d`].
+    pub fn eval_footprint_2d<F: Fn(&mut UniformRandomGen, f32, f32, u32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        y: f32,
+        filter_width: f32,
+        noise_func: F,
+    ) -> f32 {
+        self.footprint_octaves(rng, filter_width, |rng, freq| {
+            noise_func(rng, x * freq, y * freq, self.seed)
+        })
+    }
+
+    /// Like [`Fractal::eval_3d`], but anti-aliased, see [`Fractal::eval_footprint_1d`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn eval_footprint_3d<F: Fn(&mut UniformRandomGen, f32, f32, f32, u32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        y: f32,
+        z: f32,
+        filter_width: f32,
+        noise_func: F,
+    ) -> f32 {
+        self.footprint_octaves(rng, filter_width, |rng, freq| {
+            noise_func(rng, x * freq, y * freq, z * freq, self.seed)
+        })
+    }
+
+    /// Like [`Fractal::eval_4d`], but anti-aliased, see [`Fractal::eval_footprint_1d`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn eval_footprint_4d<F: Fn(&mut UniformRandomGen, f32, f32, f32, f32, u32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        y: f32,
+        z: f32,
+        t: f32,
+        filter_width: f32,
+        noise_func: F,
+    ) -> f32 {
+        self.footprint_octaves(rng, filter_width, |rng, freq| {
+            noise_func(rng, x * freq, y * freq, z * freq, t * freq, self.seed)
+        })
+    }
+
+    fn footprint_octaves<S: FnMut(&mut UniformRandomGen, f32) -> f32>(
+        &self,
+        rng: &mut UniformRandomGen,
+        filter_width: f32,
+        sample: S,
+    ) -> f32 {
+        fractal_octaves(
+            rng,
+            self.kind,
+            self.octaves,
+            self.freq_falloff,
+            self.lacunarity,
+            self.offset,
+            fractal_nyquist_octaves(filter_width, self.lacunarity),
+            sample,
+        )
+    }
+}
+
+impl Default for Fractal {
+    fn default() -> Self {
+        FractalBuilder::default()
+            .build()
+            .expect("default fractal settings are valid")
+    }
+}
+
+/// Builder for [`Fractal`], defaulting to 4 octaves, a frequency falloff of 0.5,
+/// a lacunarity of 2.0, an offset of 2.5 and seed 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FractalBuilder {
+    settings: Fractal,
+}
+
+impl FractalBuilder {
+    pub fn new(kind: FractalKind) -> Self {
+        Self {
+            settings: Fractal {
+                kind,
+                octaves: 4.0,
+                freq_falloff: 0.5,
+                lacunarity: 2.0,
+                offset: 2.5,
+                seed: 0,
+            },
+        }
+    }
+
+    pub fn kind(mut self, kind: FractalKind) -> Self {
+        self.settings.kind = kind;
+        self
+    }
+
+    /// The number of octaves. A fractional part blends in the last octave
+    /// partially, so the detail level can change continuously.
+    pub fn octaves(mut self, octaves: f32) -> Self {
+        self.settings.octaves = octaves;
+        self
+    }
+
+    /// The amplitude multiplier between successive octaves (often called gain).
+    pub fn freq_falloff(mut self, freq_falloff: f32) -> Self {
+        self.settings.freq_falloff = freq_falloff;
+        self
+    }
+
+    /// The frequency multiplier between successive octaves.
+    pub fn lacunarity(mut self, lacunarity: f32) -> Self {
+        self.settings.lacunarity = lacunarity;
+        self
+    }
+
+    /// The value added to each octave of [`FractalKind::Multiplicative`].
+    pub fn offset(mut self, offset: f32) -> Self {
+        self.settings.offset = offset;
+        self
+    }
+
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.settings.seed = seed;
+        self
+    }
+
+    /// Validates the parameters. A falloff above 1 or a lacunarity below 1 is
+    /// rejected, which catches the two being passed in swapped order.
+    pub fn build(self) -> Result<Fractal, FractalError> {
+        let s = self.settings;
+        if !(s.octaves >= 1.0 && s.octaves <= MAX_OCTAVES) {
+            return Err(FractalError::Octaves(s.octaves));
+        }
+        if !(s.freq_falloff > 0.0 && s.freq_falloff <= 1.0) {
+            return Err(FractalError::FreqFalloff(s.freq_falloff));
+        }
+        if !(s.lacunarity.is_finite() && s.lacunarity > 1.0) {
+            return Err(FractalError::Lacunarity(s.lacunarity));
+        }
+        if !s.offset.is_finite() {
+            return Err(FractalError::Offset(s.offset));
+        }
+        Ok(s)
+    }
+}
+
+impl Default for FractalBuilder {
+    fn default() -> Self {
+        Self::new(FractalKind::Fbm)
+    }
 }
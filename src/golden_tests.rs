@@ -0,0 +1,244 @@
+use crate::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+#[cfg(not(feature = "std"))]
+use std::{
+    format,
+    string::{String, ToString},
+    vec,
+};
+
+//
+// Golden output tests
+//
+
+// Every case hashes a fixed grid of samples and compares the hash with the value
+// checked into `GOLDEN_PATH`, so any change to the output of a noise, a fractal or
+// the random generator fails here instead of in shipped assets. When an output is
+// meant to change, regenerate the file with
+//
+//     NOISELIB_BLESS=1 cargo test golden
+//
+// and commit it together with the change.
+
+const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden/outputs.txt");
+const BLESS_VAR: &str = "NOISELIB_BLESS";
+
+const SEEDS: [u32; 3] = [0, 7, 12345];
+
+// FNV-1a over the bits of the samples.
+#[derive(Clone, Copy)]
+struct Hasher(u64);
+
+impl Hasher {
+    fn new() -> Self {
+        Hasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn add(&mut self, v: f32) {
+        for byte in v.to_bits().to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+// The grid coordinates along each axis, positive and off the lattice.
+fn axis(a: usize, i: usize) -> f32 {
+    const START: [f32; 4] = [0.37, 0.11, 0.53, 0.29];
+    const STEP: [f32; 4] = [0.73, 0.61, 0.79, 0.67];
+    START[a] + i as f32 * STEP[a]
+}
+
+// Hashes `f` over a grid of `dim` dimensions, all samples sharing one generator.
+fn hash_grid<F: FnMut(&mut UniformRandomGen, [f32; 4]) -> f32>(dim: usize, seed: u32, f: F) -> u64 {
+    hash_grid_mode(HashMode::Legacy, dim, seed, f)
+}
+
+fn hash_grid_mode<F: FnMut(&mut UniformRandomGen, [f32; 4]) -> f32>(
+    mode: HashMode,
+    dim: usize,
+    seed: u32,
+    mut f: F,
+) -> u64 {
+    let n: usize = match dim {
+        1 => 256,
+        2 => 32,
+        3 => 12,
+        _ => 6,
+    };
+    let rng = &mut UniformRandomGen::new(seed).with_hash_mode(mode);
+    let mut hasher = Hasher::new();
+    for index in 0..n.pow(dim as u32) {
+        let mut p = [0.0; 4];
+        let mut rest = index;
+        for (a, c) in p.iter_mut().enumerate().take(dim) {
+            *c = axis(a, rest % n);
+            rest /= n;
+        }
+        hasher.add(f(rng, p));
+    }
+    hasher.0
+}
+
+fn eval(noise: NoiseKind, rng: &mut UniformRandomGen, dim: usize, p: [f32; 4], seed: u32) -> f32 {
+    match dim {
+        1 => noise.eval_1d(rng, p[0], seed),
+        2 => noise.eval_2d(rng, p[0], p[1], seed),
+        3 => noise.eval_3d(rng, p[0], p[1], p[2], seed),
+        _ => noise.eval_4d(rng, p[0], p[1], p[2], p[3], seed),
+    }
+}
+
+// All cases by name.
+fn cases() -> BTreeMap<String, u64> {
+    let mut cases = BTreeMap::new();
+
+    for seed in SEEDS {
+        let rng = &mut UniformRandomGen::new(seed);
+        let mut hasher = Hasher::new();
+        for i in 0..1000 {
+            hasher.add(rng.get(i));
+            hasher.add(rng.get_());
+        }
+        cases.insert(format!("uniform_random_gen seed={}", seed), hasher.0);
+
+        let rng = &mut UniformRandomGen::new(seed);
+        let mut hasher = Hasher::new();
+        for _ in 0..1000 {
+            hasher.add(f32::from_bits(rng.next_u32()));
+            hasher.add(rng.next_f32());
+            hasher.add(rng.range_i32(-100..100) as f32);
+        }
+        cases.insert(format!("pcg32 seed={}", seed), hasher.0);
+
+        for noise in NoiseKind::ALL {
+            for dim in 1..=4 {
+                let hash = hash_grid(dim, seed, |rng, p| eval(noise, rng, dim, p, seed));
+                cases.insert(format!("{} {}d seed={}", noise, dim, seed), hash);
+                let hash = hash_grid_mode(HashMode::V1, dim, seed, |rng, p| {
+                    eval(noise, rng, dim, p, seed)
+                });
+                cases.insert(format!("{} v1 {}d seed={}", noise, dim, seed), hash);
+            }
+        }
+
+        let mut hasher = Hasher::new();
+        for i in -500..500 {
+            hasher.add(f32::from_bits(crate::hash::hash1(i, seed)));
+            hasher.add(f32::from_bits(crate::hash::hash2(i, -i, seed)));
+            hasher.add(f32::from_bits(crate::hash::hash3(i, 3, -i, seed)));
+            hasher.add(f32::from_bits(crate::hash::hash4(i, 5, 7, -i, seed)));
+        }
+        cases.insert(format!("lattice_hash seed={}", seed), hasher.0);
+
+        let sequence = SeedSequence::new(seed);
+        let mut hasher = Hasher::new();
+        for i in 0..100 {
+            hasher.add(f32::from_bits(sequence.child(i).seed()));
+            hasher.add(f32::from_bits(sequence.child("octave").child(i).seed()));
+        }
+        hasher.add(f32::from_bits(SeedSequence::from_name("world-42").seed()));
+        cases.insert(format!("seed_sequence seed={}", seed), hasher.0);
+
+        let hash = hash_grid(2, seed, |rng, p| {
+            let [v, dx, dy] = perlin_noise_deriv_2d(rng, p[0], p[1], seed);
+            v + dx * 3.0 + dy * 7.0
+        });
+        cases.insert(format!("perlin_deriv 2d seed={}", seed), hash);
+        let hash = hash_grid(3, seed, |rng, p| {
+            let [v, dx, dy, dz] = simplex_noise_deriv_3d(rng, p[0], p[1], p[2], seed);
+            v + dx * 3.0 + dy * 7.0 + dz * 11.0
+        });
+        cases.insert(format!("simplex_deriv 3d seed={}", seed), hash);
+
+        for kind in FractalKind::ALL {
+            let fractal = Fractal::builder(kind)
+                .octaves(4.5)
+                .seed(seed)
+                .build()
+                .unwrap();
+            for noise in NoiseKind::ALL {
+                let hash = hash_grid(2, seed, |rng, p| {
+                    fractal.eval_2d(rng, p[0], p[1], |rng, x, y, s| noise.eval_2d(rng, x, y, s))
+                });
+                cases.insert(format!("{} {} 2d seed={}", kind.name(), noise, seed), hash);
+                let hash = hash_grid(3, seed, |rng, p| {
+                    fractal.eval_3d(rng, p[0], p[1], p[2], |rng, x, y, z, s| {
+                        noise.eval_3d(rng, x, y, z, s)
+                    })
+                });
+                cases.insert(format!("{} {} 3d seed={}", kind.name(), noise, seed), hash);
+            }
+        }
+    }
+
+    cases
+}
+
+fn parse(text: &str) -> BTreeMap<String, u64> {
+    text.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, hash) = line.rsplit_once(' ')?;
+            Some((name.to_string(), u64::from_str_radix(hash, 16).ok()?))
+        })
+        .collect()
+}
+
+fn write(cases: &BTreeMap<String, u64>) -> String {
+    let mut text = String::new();
+    text.push_str("# Golden hashes of noise outputs, checked by src/golden_tests.rs.\n");
+    text.push_str("# Regenerate with: NOISELIB_BLESS=1 cargo test golden\n");
+    for (name, hash) in cases {
+        writeln!(text, "{} {:016x}", name, hash).unwrap();
+    }
+    text
+}
+
+#[test]
+fn golden_outputs() {
+    let actual = cases();
+
+    if std::env::var_os(BLESS_VAR).is_some() {
+        let path = std::path::Path::new(GOLDEN_PATH);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, write(&actual)).expect("failed to write the golden file");
+        return;
+    }
+
+    let text = std::fs::read_to_string(GOLDEN_PATH)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", GOLDEN_PATH, e));
+    let expected = parse(&text);
+
+    let mut failures = vec![];
+    for (name, hash) in &actual {
+        match expected.get(name) {
+            Some(golden) if golden == hash => {}
+            Some(golden) => {
+                failures.push(format!("{}: {:016x}, golden {:016x}", name, hash, golden))
+            }
+            None => failures.push(format!("{}: missing from the golden file", name)),
+        }
+    }
+    for name in expected.keys().filter(|name| !actual.contains_key(*name)) {
+        failures.push(format!("{}: no longer generated", name));
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} outputs differ from {}:\n{}\nRun `{}=1 cargo test golden` if the change is intended.",
+        failures.len(),
+        GOLDEN_PATH,
+        failures.join("\n"),
+        BLESS_VAR
+    );
+}
+
+#[test]
+fn golden_file_round_trips() {
+    let mut cases = BTreeMap::new();
+    cases.insert("perlin 2d seed=0".to_string(), 0x0123_4567_89ab_cdef);
+    cases.insert("fbm worley_f1 3d seed=7".to_string(), u64::MAX);
+    assert_eq!(parse(&write(&cases)), cases);
+}
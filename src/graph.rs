@@ -0,0 +1,731 @@
+use crate::prelude::*;
+
+//
+// Module graph
+//
+
+/// A node of a noise graph. Sources generate values, combiners and modifiers
+/// process the values of their source modules and transformers change the
+/// coordinates their source module is evaluated at.
+///
+/// ```
+/// use noiselib::graph::*;
+/// use noiselib::prelude::*;
+///
+/// let base = FractalSource::new(NoiseKind::Perlin, Fractal::default());
+/// let cells = ScaleBias::new(Source::new(NoiseKind::WorleyF1, 3), 0.5, 0.0);
+/// let graph = Turbulence::new(Add::new(base, cells), 0.2, 1.0, 3.0, 7);
+///
+/// let mut rng = UniformRandomGen::new(0);
+/// let value = graph.eval_2d(&mut rng, 0.25, 1.5);
+/// ```
+pub trait NoiseModule: Send + Sync {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32;
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32;
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32;
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32;
+
+    /// Evaluates the module in the dimension given by the number of coordinates.
+    /// Missing coordinates are 0, coordinates beyond the fourth are ignored.
+    fn eval(&self, rng: &mut UniformRandomGen, p: &[f32]) -> f32 {
+        let c = |i: usize| p.get(i).copied().unwrap_or(0.0);
+        match p.len() {
+            0 | 1 => self.eval_1d(rng, c(0)),
+            2 => self.eval_2d(rng, c(0), c(1)),
+            3 => self.eval_3d(rng, c(0), c(1), c(2)),
+            _ => self.eval_4d(rng, c(0), c(1), c(2), c(3)),
+        }
+    }
+}
+
+pub type BoxedModule = Box<dyn NoiseModule>;
+
+impl<M: NoiseModule + ?Sized> NoiseModule for Box<M> {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        (**self).eval_1d(rng, x)
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        (**self).eval_2d(rng, x, y)
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        (**self).eval_3d(rng, x, y, z)
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        (**self).eval_4d(rng, x, y, z, t)
+    }
+}
+
+// Shared nodes, used by several other nodes of a graph.
+impl<M: NoiseModule + ?Sized> NoiseModule for std::sync::Arc<M> {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        (**self).eval_1d(rng, x)
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        (**self).eval_2d(rng, x, y)
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        (**self).eval_3d(rng, x, y, z)
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        (**self).eval_4d(rng, x, y, z, t)
+    }
+}
+
+// Implements NoiseModule for nodes which only process the values of their
+// sources at the unchanged coordinates. `$eval` evaluates a source module.
+macro_rules! impl_pointwise {
+    ($ty:ty, |$node:ident, $eval:ident| $body:expr) => {
+        impl NoiseModule for $ty {
+            fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+                let $node = self;
+                let mut $eval = |m: &dyn NoiseModule| m.eval_1d(rng, x);
+                $body
+            }
+
+            fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+                let $node = self;
+                let mut $eval = |m: &dyn NoiseModule| m.eval_2d(rng, x, y);
+                $body
+            }
+
+            fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+                let $node = self;
+                let mut $eval = |m: &dyn NoiseModule| m.eval_3d(rng, x, y, z);
+                $body
+            }
+
+            fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+                let $node = self;
+                let mut $eval = |m: &dyn NoiseModule| m.eval_4d(rng, x, y, z, t);
+                $body
+            }
+        }
+    };
+}
+
+//
+// Sources
+//
+
+/// One of the noise functions of the crate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Source {
+    pub noise: NoiseKind,
+    pub seed: u32,
+}
+
+impl Source {
+    pub fn new(noise: NoiseKind, seed: u32) -> Self {
+        Self { noise, seed }
+    }
+}
+
+impl NoiseModule for Source {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        self.noise.eval_1d(rng, x, self.seed)
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        self.noise.eval_2d(rng, x, y, self.seed)
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        self.noise.eval_3d(rng, x, y, z, self.seed)
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        self.noise.eval_4d(rng, x, y, z, t, self.seed)
+    }
+}
+
+/// A fractal of one of the noise functions, using the seed of the [`Fractal`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FractalSource {
+    pub noise: NoiseKind,
+    pub fractal: Fractal,
+}
+
+impl FractalSource {
+    pub fn new(noise: NoiseKind, fractal: Fractal) -> Self {
+        Self { noise, fractal }
+    }
+}
+
+impl NoiseModule for FractalSource {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        let noise = self.noise;
+        self.fractal
+            .eval_1d(rng, x, |rng, x, seed| noise.eval_1d(rng, x, seed))
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        let noise = self.noise;
+        self.fractal
+            .eval_2d(rng, x, y, |rng, x, y, seed| noise.eval_2d(rng, x, y, seed))
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        let noise = self.noise;
+        self.fractal.eval_3d(rng, x, y, z, |rng, x, y, z, seed| {
+            noise.eval_3d(rng, x, y, z, seed)
+        })
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        let noise = self.noise;
+        self.fractal
+            .eval_4d(rng, x, y, z, t, |rng, x, y, z, t, seed| {
+                noise.eval_4d(rng, x, y, z, t, seed)
+            })
+    }
+}
+
+/// The same value everywhere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Constant(pub f32);
+
+impl_pointwise!(Constant, |node, _eval| node.0);
+
+//
+// Combiners
+//
+
+/// The sum of two modules.
+pub struct Add {
+    pub a: BoxedModule,
+    pub b: BoxedModule,
+}
+
+impl Add {
+    pub fn new(a: impl NoiseModule + 'static, b: impl NoiseModule + 'static) -> Self {
+        Self {
+            a: Box::new(a),
+            b: Box::new(b),
+        }
+    }
+}
+
+impl_pointwise!(Add, |node, eval| eval(&node.a) + eval(&node.b));
+
+/// The product of two modules.
+pub struct Multiply {
+    pub a: BoxedModule,
+    pub b: BoxedModule,
+}
+
+impl Multiply {
+    pub fn new(a: impl NoiseModule + 'static, b: impl NoiseModule + 'static) -> Self {
+        Self {
+            a: Box::new(a),
+            b: Box::new(b),
+        }
+    }
+}
+
+impl_pointwise!(Multiply, |node, eval| eval(&node.a) * eval(&node.b));
+
+/// The smaller value of two modules.
+pub struct Min {
+    pub a: BoxedModule,
+    pub b: BoxedModule,
+}
+
+impl Min {
+    pub fn new(a: impl NoiseModule + 'static, b: impl NoiseModule + 'static) -> Self {
+        Self {
+            a: Box::new(a),
+            b: Box::new(b),
+        }
+    }
+}
+
+impl_pointwise!(Min, |node, eval| eval(&node.a).min(eval(&node.b)));
+
+/// The larger value of two modules.
+pub struct Max {
+    pub a: BoxedModule,
+    pub b: BoxedModule,
+}
+
+impl Max {
+    pub fn new(a: impl NoiseModule + 'static, b: impl NoiseModule + 'static) -> Self {
+        Self {
+            a: Box::new(a),
+            b: Box::new(b),
+        }
+    }
+}
+
+impl_pointwise!(Max, |node, eval| eval(&node.a).max(eval(&node.b)));
+
+/// Linear interpolation between `a` and `b`. A control value of -1 selects `a`,
+/// a value of 1 selects `b`.
+pub struct Blend {
+    pub a: BoxedModule,
+    pub b: BoxedModule,
+    pub control: BoxedModule,
+}
+
+impl Blend {
+    pub fn new(
+        a: impl NoiseModule + 'static,
+        b: impl NoiseModule + 'static,
+        control: impl NoiseModule + 'static,
+    ) -> Self {
+        Self {
+            a: Box::new(a),
+            b: Box::new(b),
+            control: Box::new(control),
+        }
+    }
+}
+
+impl_pointwise!(Blend, |node, eval| {
+    let w = (eval(&node.control) + 1.0) * 0.5;
+    let a = eval(&node.a);
+    a + (eval(&node.b) - a) * w
+});
+
+/// Selects `b` where the control value is within `[lower, upper]` and `a`
+/// elsewhere. A `falloff` greater than 0 smoothly blends across the bounds.
+pub struct Select {
+    pub a: BoxedModule,
+    pub b: BoxedModule,
+    pub control: BoxedModule,
+    pub lower: f32,
+    pub upper: f32,
+    pub falloff: f32,
+}
+
+impl Select {
+    pub fn new(
+        a: impl NoiseModule + 'static,
+        b: impl NoiseModule + 'static,
+        control: impl NoiseModule + 'static,
+        lower: f32,
+        upper: f32,
+        falloff: f32,
+    ) -> Self {
+        Self {
+            a: Box::new(a),
+            b: Box::new(b),
+            control: Box::new(control),
+            lower,
+            upper,
+            falloff,
+        }
+    }
+
+    // The weight of `b` for a control value.
+    fn weight(&self, control: f32) -> f32 {
+        let falloff = self.falloff.min((self.upper - self.lower) * 0.5).max(0.0);
+        if falloff > 0.0 {
+            let s = smoothstep(self.lower - falloff, self.lower + falloff, control);
+            s * (1.0 - smoothstep(self.upper - falloff, self.upper + falloff, control))
+        } else if control >= self.lower && control <= self.upper {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl_pointwise!(Select, |node, eval| {
+    let w = node.weight(eval(&node.control));
+    if w <= 0.0 {
+        eval(&node.a)
+    } else if w >= 1.0 {
+        eval(&node.b)
+    } else {
+        let a = eval(&node.a);
+        a + (eval(&node.b) - a) * w
+    }
+});
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+//
+// Modifiers
+//
+
+/// `source * scale + bias`.
+pub struct ScaleBias {
+    pub source: BoxedModule,
+    pub scale: f32,
+    pub bias: f32,
+}
+
+impl ScaleBias {
+    pub fn new(source: impl NoiseModule + 'static, scale: f32, bias: f32) -> Self {
+        Self {
+            source: Box::new(source),
+            scale,
+            bias,
+        }
+    }
+}
+
+impl_pointwise!(ScaleBias, |node, eval| eval(&node.source) * node.scale
+    + node.bias);
+
+/// The source clamped to `[lower, upper]`.
+pub struct Clamp {
+    pub source: BoxedModule,
+    pub lower: f32,
+    pub upper: f32,
+}
+
+impl Clamp {
+    pub fn new(source: impl NoiseModule + 'static, lower: f32, upper: f32) -> Self {
+        Self {
+            source: Box::new(source),
+            lower,
+            upper,
+        }
+    }
+}
+
+impl_pointwise!(Clamp, |node, eval| eval(&node.source)
+    .max(node.lower)
+    .min(node.upper));
+
+/// The absolute value of the source.
+pub struct Abs {
+    pub source: BoxedModule,
+}
+
+impl Abs {
+    pub fn new(source: impl NoiseModule + 'static) -> Self {
+        Self {
+            source: Box::new(source),
+        }
+    }
+}
+
+impl_pointwise!(Abs, |node, eval| eval(&node.source).abs());
+
+/// The negated source.
+pub struct Invert {
+    pub source: BoxedModule,
+}
+
+impl Invert {
+    pub fn new(source: impl NoiseModule + 'static) -> Self {
+        Self {
+            source: Box::new(source),
+        }
+    }
+}
+
+impl_pointwise!(Invert, |node, eval| -eval(&node.source));
+
+/// Maps the source from [-1, 1] to [0, 1], raises it to `exponent` and maps it
+/// back to [-1, 1].
+pub struct Exponent {
+    pub source: BoxedModule,
+    pub exponent: f32,
+}
+
+impl Exponent {
+    pub fn new(source: impl NoiseModule + 'static, exponent: f32) -> Self {
+        Self {
+            source: Box::new(source),
+            exponent,
+        }
+    }
+}
+
+impl_pointwise!(Exponent, |node, eval| {
+    let v = ((eval(&node.source) + 1.0) * 0.5).max(0.0);
+    v.powf(node.exponent) * 2.0 - 1.0
+});
+
+//
+// Transformers
+//
+
+/// Evaluates the source at coordinates moved by `offset`, given as `[x, y, z, t]`.
+pub struct Translate {
+    pub source: BoxedModule,
+    pub offset: [f32; 4],
+}
+
+impl Translate {
+    pub fn new(source: impl NoiseModule + 'static, offset: [f32; 4]) -> Self {
+        Self {
+            source: Box::new(source),
+            offset,
+        }
+    }
+}
+
+impl NoiseModule for Translate {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        let o = self.offset;
+        self.source.eval_1d(rng, x + o[0])
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        let o = self.offset;
+        self.source.eval_2d(rng, x + o[0], y + o[1])
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        let o = self.offset;
+        self.source.eval_3d(rng, x + o[0], y + o[1], z + o[2])
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        let o = self.offset;
+        self.source
+            .eval_4d(rng, x + o[0], y + o[1], z + o[2], t + o[3])
+    }
+}
+
+/// Evaluates the source at coordinates multiplied by `scale`, given as `[x, y, z, t]`.
+pub struct Scale {
+    pub source: BoxedModule,
+    pub scale: [f32; 4],
+}
+
+impl Scale {
+    pub fn new(source: impl NoiseModule + 'static, scale: [f32; 4]) -> Self {
+        Self {
+            source: Box::new(source),
+            scale,
+        }
+    }
+
+    pub fn uniform(source: impl NoiseModule + 'static, scale: f32) -> Self {
+        Self::new(source, [scale; 4])
+    }
+}
+
+impl NoiseModule for Scale {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        let s = self.scale;
+        self.source.eval_1d(rng, x * s[0])
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        let s = self.scale;
+        self.source.eval_2d(rng, x * s[0], y * s[1])
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        let s = self.scale;
+        self.source.eval_3d(rng, x * s[0], y * s[1], z * s[2])
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        let s = self.scale;
+        self.source
+            .eval_4d(rng, x * s[0], y * s[1], z * s[2], t * s[3])
+    }
+}
+
+/// Evaluates the source at coordinates rotated around the x, y and z axes, in
+/// that order. The angles are in degrees. 2D coordinates are only rotated by the
+/// z angle, 1D coordinates are not rotated and `t` is left unchanged.
+pub struct Rotate {
+    pub source: BoxedModule,
+    matrix: [[f32; 3]; 3],
+    angles: [f32; 3],
+}
+
+impl Rotate {
+    pub fn new(source: impl NoiseModule + 'static, angles: [f32; 3]) -> Self {
+        let [cx, cy, cz] = angles.map(|a| a.to_radians().cos());
+        let [sx, sy, sz] = angles.map(|a| a.to_radians().sin());
+
+        // Rz * Ry * Rx
+        let matrix = [
+            [cy * cz, sx * sy * cz - cx * sz, cx * sy * cz + sx * sz],
+            [cy * sz, sx * sy * sz + cx * cz, cx * sy * sz - sx * cz],
+            [-sy, sx * cy, cx * cy],
+        ];
+
+        Self {
+            source: Box::new(source),
+            matrix,
+            angles,
+        }
+    }
+
+    /// The rotation angles in degrees.
+    pub fn angles(&self) -> [f32; 3] {
+        self.angles
+    }
+
+    fn rotate(&self, x: f32, y: f32, z: f32) -> [f32; 3] {
+        self.matrix.map(|row| row[0] * x + row[1] * y + row[2] * z)
+    }
+}
+
+impl NoiseModule for Rotate {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        self.source.eval_1d(rng, x)
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        let (s, c) = self.angles[2].to_radians().sin_cos();
+        self.source.eval_2d(rng, c * x - s * y, s * x + c * y)
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        let [x, y, z] = self.rotate(x, y, z);
+        self.source.eval_3d(rng, x, y, z)
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        let [x, y, z] = self.rotate(x, y, z);
+        self.source.eval_4d(rng, x, y, z, t)
+    }
+}
+
+// Offsets of the per axis displacement noises of Turbulence.
+const TURBULENCE_OFFSETS: [f32; 4] = [0.189422, 0.993265, 0.478306, 0.781532];
+
+/// Randomly displaces the coordinates of the source by up to `power` units,
+/// using a fractal add of Perlin noise with the given frequency and number of
+/// octaves (roughness) per axis.
+pub struct Turbulence {
+    pub source: BoxedModule,
+    pub power: f32,
+    pub frequency: f32,
+    pub roughness: f32,
+    pub seed: u32,
+}
+
+impl Turbulence {
+    pub fn new(
+        source: impl NoiseModule + 'static,
+        power: f32,
+        frequency: f32,
+        roughness: f32,
+        seed: u32,
+    ) -> Self {
+        Self {
+            source: Box::new(source),
+            power,
+            frequency,
+            roughness,
+            seed,
+        }
+    }
+
+    // The displacement along `axis` for a point given by one to four coordinates.
+    fn displacement(&self, rng: &mut UniformRandomGen, axis: usize, p: &[f32]) -> f32 {
+        let o = TURBULENCE_OFFSETS[axis] + axis as f32 * 17.0;
+        let c = |i: usize| (p[i] + o) * self.frequency;
+        let (octaves, seed) = (self.roughness, self.seed);
+
+        let n = match p.len() {
+            1 => fractal_noise_add_1d(rng, c(0), perlin_noise_1d, octaves, 0.5, 2.0, seed),
+            2 => fractal_noise_add_2d(rng, c(0), c(1), perlin_noise_2d, octaves, 0.5, 2.0, seed),
+            3 => {
+                let (x, y, z) = (c(0), c(1), c(2));
+                fractal_noise_add_3d(rng, x, y, z, perlin_noise_3d, octaves, 0.5, 2.0, seed)
+            }
+            _ => {
+                let (x, y, z, t) = (c(0), c(1), c(2), c(3));
+                fractal_noise_add_4d(rng, x, y, z, t, perlin_noise_4d, octaves, 0.5, 2.0, seed)
+            }
+        };
+        n * self.power
+    }
+}
+
+impl NoiseModule for Turbulence {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        let dx = self.displacement(rng, 0, &[x]);
+        self.source.eval_1d(rng, x + dx)
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        let p = [x, y];
+        let dx = self.displacement(rng, 0, &p);
+        let dy = self.displacement(rng, 1, &p);
+        self.source.eval_2d(rng, x + dx, y + dy)
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        let p = [x, y, z];
+        let dx = self.displacement(rng, 0, &p);
+        let dy = self.displacement(rng, 1, &p);
+        let dz = self.displacement(rng, 2, &p);
+        self.source.eval_3d(rng, x + dx, y + dy, z + dz)
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        let p = [x, y, z, t];
+        let dx = self.displacement(rng, 0, &p);
+        let dy = self.displacement(rng, 1, &p);
+        let dz = self.displacement(rng, 2, &p);
+        let dt = self.displacement(rng, 3, &p);
+        self.source.eval_4d(rng, x + dx, y + dy, z + dz, t + dt)
+    }
+}
+
+/// Displaces the coordinates of the source by the values of other modules.
+/// `axes[0]` displaces x, `axes[1]` y and so on. Axes without a module are
+/// left unchanged.
+pub struct Displace {
+    pub source: BoxedModule,
+    pub axes: Vec<BoxedModule>,
+}
+
+impl Displace {
+    pub fn new(source: impl NoiseModule + 'static, axes: Vec<BoxedModule>) -> Self {
+        Self {
+            source: Box::new(source),
+            axes,
+        }
+    }
+
+    fn displaced<const N: usize>(
+        &self,
+        rng: &mut UniformRandomGen,
+        p: [f32; N],
+        eval: impl Fn(&dyn NoiseModule, &mut UniformRandomGen, [f32; N]) -> f32,
+    ) -> [f32; N] {
+        let mut out = p;
+        for (o, m) in out.iter_mut().zip(&self.axes) {
+            *o += eval(m.as_ref(), rng, p);
+        }
+        out
+    }
+}
+
+impl NoiseModule for Displace {
+    fn eval_1d(&self, rng: &mut UniformRandomGen, x: f32) -> f32 {
+        let [x] = self.displaced(rng, [x], |m, rng, [x]| m.eval_1d(rng, x));
+        self.source.eval_1d(rng, x)
+    }
+
+    fn eval_2d(&self, rng: &mut UniformRandomGen, x: f32, y: f32) -> f32 {
+        let [x, y] = self.displaced(rng, [x, y], |m, rng, [x, y]| m.eval_2d(rng, x, y));
+        self.source.eval_2d(rng, x, y)
+    }
+
+    fn eval_3d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32) -> f32 {
+        let [x, y, z] = self.displaced(rng, [x, y, z], |m, rng, [x, y, z]| m.eval_3d(rng, x, y, z));
+        self.source.eval_3d(rng, x, y, z)
+    }
+
+    fn eval_4d(&self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, t: f32) -> f32 {
+        let [x, y, z, t] = self.displaced(rng, [x, y, z, t], |m, rng, [x, y, z, t]| {
+            m.eval_4d(rng, x, y, z, t)
+        });
+        self.source.eval_4d(rng, x, y, z, t)
+    }
+}
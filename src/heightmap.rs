@@ -0,0 +1,221 @@
+use crate::prelude::*;
+use std::io::{self, Write};
+
+//
+// Heightmaps
+//
+
+/// A grid of heights, stored row by row from the top (north) edge.
+///
+/// ```
+/// use noiselib::heightmap::Heightmap;
+/// use noiselib::prelude::*;
+///
+/// let mut rng = UniformRandomGen::new(1);
+/// let fractal = Fractal::default();
+/// let map = Heightmap::sample(&mut rng, 65, 65, [0.0, 0.0], [4.0, 4.0], |rng, x, y| {
+///     fractal.eval_2d(rng, x, y, perlin_noise_2d)
+/// });
+///
+/// let mut raw = vec![];
+/// map.write_raw16(&mut raw, (-1.0, 1.0)).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightmap {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn new(width: usize, height: usize, values: Vec<f32>) -> Self {
+        assert_eq!(
+            values.len(),
+            width * height,
+            "expected {} x {} heights",
+            width,
+            height
+        );
+        Self {
+            width,
+            height,
+            values,
+        }
+    }
+
+    /// Samples `f` over the region starting at `origin` and covering `extent` noise
+    /// units. The first and last row and column lie on the edges of the region, so
+    /// neighbouring regions share their border heights and tile without seams.
+    pub fn sample<F: FnMut(&mut UniformRandomGen, f32, f32) -> f32>(
+        rng: &mut UniformRandomGen,
+        width: usize,
+        height: usize,
+        origin: [f32; 2],
+        extent: [f32; 2],
+        mut f: F,
+    ) -> Self {
+        let step = |extent: f32, n: usize| extent / (n.max(2) - 1) as f32;
+        let (dx, dy) = (step(extent[0], width), step(extent[1], height));
+
+        let mut values = Vec::with_capacity(width * height);
+        for j in 0..height {
+            for i in 0..width {
+                values.push(f(rng, origin[0] + i as f32 * dx, origin[1] + j as f32 * dy));
+            }
+        }
+        Self::new(width, height, values)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.values[y * self.width + x]
+    }
+
+    /// The smallest and largest height, `(0, 0)` for an empty map.
+    pub fn min_max(&self) -> (f32, f32) {
+        if self.values.is_empty() {
+            return (0.0, 0.0);
+        }
+        self.values
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)))
+    }
+
+    // The height at clamped coordinates.
+    fn at(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.get(x, y)
+    }
+
+    /// Writes the heights as little-endian 16-bit integers without a header, mapping
+    /// `range` onto [0, 65535] and clamping heights outside of it. This is the RAW
+    /// format of the Unity and Unreal terrain importers, which expect square maps of
+    /// `2^n + 1` heights per side.
+    pub fn write_raw16<W: Write>(&self, mut writer: W, range: (f32, f32)) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(self.values.len() * 2);
+        for v in &self.values {
+            let t = (v - range.0) / (range.1 - range.0);
+            let h = (t.clamp(0.0, 1.0) * 65535.0).round() as u16;
+            bytes.extend_from_slice(&h.to_le_bytes());
+        }
+        writer.write_all(&bytes)
+    }
+
+    /// Writes the heights as an ESRI ASCII grid with square cells of `cell_size`,
+    /// the lower left corner of the grid being at `lower_left`.
+    pub fn write_esri_ascii<W: Write>(
+        &self,
+        mut writer: W,
+        lower_left: [f64; 2],
+        cell_size: f64,
+    ) -> io::Result<()> {
+        writeln!(writer, "ncols {}", self.width)?;
+        writeln!(writer, "nrows {}", self.height)?;
+        writeln!(writer, "xllcorner {}", lower_left[0])?;
+        writeln!(writer, "yllcorner {}", lower_left[1])?;
+        writeln!(writer, "cellsize {}", cell_size)?;
+        writeln!(writer, "NODATA_value -9999")?;
+
+        for row in self.values.chunks(self.width.max(1)) {
+            let mut line = String::new();
+            for (i, v) in row.iter().enumerate() {
+                if i > 0 {
+                    line.push(' ');
+                }
+                if v.is_finite() {
+                    line.push_str(&v.to_string());
+                } else {
+                    line.push_str("-9999");
+                }
+            }
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the heightmap as a triangulated Wavefront OBJ mesh. The mesh lies in
+    /// the x/z plane with y up, neighbouring heights being `spacing` apart and the
+    /// heights multiplied by `height_scale`. Vertices get normals and UVs covering
+    /// [0, 1], the top row of the map having v = 1.
+    pub fn write_obj<W: Write>(
+        &self,
+        mut writer: W,
+        spacing: f32,
+        height_scale: f32,
+    ) -> io::Result<()> {
+        let (w, h) = (self.width, self.height);
+        let uv = |i: usize, n: usize| {
+            if n > 1 {
+                i as f32 / (n - 1) as f32
+            } else {
+                0.0
+            }
+        };
+
+        writeln!(writer, "# noiselib heightmap {} x {}", w, h)?;
+        for j in 0..h {
+            for i in 0..w {
+                let y = self.get(i, j) * height_scale;
+                writeln!(
+                    writer,
+                    "v {} {} {}",
+                    i as f32 * spacing,
+                    y,
+                    j as f32 * spacing
+                )?;
+            }
+        }
+        for j in 0..h {
+            for i in 0..w {
+                writeln!(writer, "vt {} {}", uv(i, w), 1.0 - uv(j, h))?;
+            }
+        }
+        for j in 0..h as isize {
+            for i in 0..w as isize {
+                // Central differences, one-sided at the borders.
+                let (x0, x1) = ((i - 1).max(0), (i + 1).min(w as isize - 1));
+                let (z0, z1) = ((j - 1).max(0), (j + 1).min(h as isize - 1));
+                let dx = (self.at(x1, j) - self.at(x0, j)) * height_scale
+                    / (((x1 - x0).max(1)) as f32 * spacing);
+                let dz = (self.at(i, z1) - self.at(i, z0)) * height_scale
+                    / (((z1 - z0).max(1)) as f32 * spacing);
+                let length = (dx * dx + 1.0 + dz * dz).sqrt();
+                writeln!(
+                    writer,
+                    "vn {} {} {}",
+                    -dx / length,
+                    1.0 / length,
+                    -dz / length
+                )?;
+            }
+        }
+
+        // OBJ indices start at 1, triangles are counter-clockwise seen from above.
+        let index = |i: usize, j: usize| j * w + i + 1;
+        for j in 0..h.saturating_sub(1) {
+            for i in 0..w.saturating_sub(1) {
+                let (a, b, c, d) = (
+                    index(i, j),
+                    index(i, j + 1),
+                    index(i + 1, j),
+                    index(i + 1, j + 1),
+                );
+                writeln!(writer, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c)?;
+                writeln!(writer, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", c, b, d)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,25 +1,79 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("noiselib needs either the `std` or the `libm` feature for its float math");
+
+// The noise and fractal functions only need core, the buffer, image and parsing
+// modules need std.
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod color;
+#[cfg(feature = "std")]
+pub mod expr;
 pub mod fractal;
+#[cfg(feature = "std")]
+pub mod graph;
+pub mod hash;
+#[cfg(feature = "std")]
+pub mod heightmap;
+pub mod looping;
+mod math;
 pub mod musgrave;
+pub mod noise;
+#[cfg(feature = "std")]
+pub mod normalmap;
+#[cfg(feature = "std")]
+pub mod output;
+#[cfg(feature = "std")]
+pub mod parallel;
 pub mod perlin;
+#[cfg(feature = "std")]
+pub mod preset;
 pub mod random;
+pub mod seed;
+#[cfg(feature = "std")]
+pub mod simd;
 pub mod simplex;
+#[cfg(feature = "std")]
+pub mod sphere;
+pub mod triplanar;
 pub mod uniform;
 pub mod worley;
 
 pub mod prelude {
     pub use crate::fractal::*;
+    pub use crate::hash::HashMode;
     pub use crate::musgrave::*;
+    pub use crate::noise::NoiseKind;
     pub use crate::perlin::*;
     pub use crate::random::*;
+    pub use crate::seed::SeedSequence;
     pub use crate::simplex::*;
     pub use crate::uniform::UniformRandomGen;
     pub use crate::worley::*;
 }
 
+// Tests use std even when the crate is built without it.
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+#[cfg(test)]
+mod golden_tests;
+#[cfg(test)]
+mod quality_tests;
+
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "std")]
+    use super::graph::*;
     use super::prelude::*;
+    #[cfg(feature = "std")]
+    use super::{batch, parallel, sphere};
+    use super::{hash, looping, triplanar};
     use image::{ImageBuffer, Luma};
+    #[cfg(not(feature = "std"))]
+    use std::vec::Vec;
 
     #[test]
     fn generate_random_image() {
@@ -137,7 +191,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_add_perlin_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -165,7 +219,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_add_musgrave_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -193,7 +247,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_add_simplex_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -221,7 +275,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_add_worley_f1_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -249,7 +303,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_addabs_perlin_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -277,7 +331,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_addabs_musgrave_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -305,7 +359,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_addabs_simplex_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -333,7 +387,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_addabs_worley_f1_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -361,7 +415,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_mul_perlin_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -391,7 +445,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_mul_musgrave_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -421,7 +475,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_mul_simplex_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -451,7 +505,7 @@ mod tests {
     }
     #[test]
     fn generate_fractal_mul_worley_f1_image() {
-        let octaves = 4;
+        let octaves = 4.0;
         let freq_falloff = 0.5;
         let lacunarity = 2.0;
         let seed = 1;
@@ -479,4 +533,1358 @@ mod tests {
         img.save("images/fractal_mul_worley_f1.png")
             .expect("Failed to save image");
     }
+    #[test]
+    fn generate_fractal_ridged_perlin_image() {
+        let seed = 1;
+        let fractal = Fractal::builder(FractalKind::Ridged)
+            .octaves(4.0)
+            .freq_falloff(0.5)
+            .lacunarity(2.0)
+            .seed(seed)
+            .build()
+            .unwrap();
+        let mut rng = UniformRandomGen::new(seed);
+
+        let width = 256;
+        let height = 256;
+        let img = ImageBuffer::from_fn(width, height, |x, y| {
+            let noise_val = fractal.eval_2d(
+                &mut rng,
+                x as f32 / width as f32 * 10.0,
+                y as f32 / height as f32 * 10.0,
+                perlin_noise_2d,
+            );
+            let normalized_val = ((noise_val + 1.0) / 2.0 * 255.0) as u8;
+            Luma([normalized_val])
+        });
+
+        img.save("images/fractal_ridged_perlin.png")
+            .expect("Failed to save image");
+    }
+    #[test]
+    fn fractal_builder_rejects_invalid_settings() {
+        let swapped = Fractal::builder(FractalKind::Fbm)
+            .freq_falloff(2.0)
+            .lacunarity(0.5)
+            .build();
+        assert_eq!(swapped, Err(FractalError::FreqFalloff(2.0)));
+
+        let octaves = Fractal::builder(FractalKind::Turbulence)
+            .octaves(0.5)
+            .build();
+        assert_eq!(octaves, Err(FractalError::Octaves(0.5)));
+
+        let lacunarity = Fractal::builder(FractalKind::Ridged)
+            .lacunarity(1.0)
+            .build();
+        assert_eq!(lacunarity, Err(FractalError::Lacunarity(1.0)));
+
+        assert!(Fractal::builder(FractalKind::Multiplicative)
+            .build()
+            .is_ok());
+    }
+    #[test]
+    fn fractal_normalization_reaches_range() {
+        let mut rng = UniformRandomGen::new(1);
+        let max = |_: &mut UniformRandomGen, _: f32, _: f32, _: u32| 1.0;
+        let min = |_: &mut UniformRandomGen, _: f32, _: f32, _: u32| -1.0;
+
+        for kind in [
+            FractalKind::Fbm,
+            FractalKind::Turbulence,
+            FractalKind::Multiplicative,
+            FractalKind::Ridged,
+        ] {
+            let fractal = Fractal::builder(kind).octaves(5.0).build().unwrap();
+            let (lo, hi) = fractal.range();
+
+            let top = fractal.eval_2d(&mut rng, 0.5, 0.5, max);
+            let bottom = fractal.eval_2d(&mut rng, 0.5, 0.5, min);
+            let zero = fractal.eval_2d(&mut rng, 0.5, 0.5, |_, _, _, _| 0.0);
+            let values = [top, bottom, zero];
+
+            assert!(values.iter().all(|v| *v >= lo - 1e-6 && *v <= hi + 1e-6));
+            match kind {
+                FractalKind::Fbm => assert_eq!((bottom, top), (-1.0, 1.0)),
+                FractalKind::Turbulence => assert_eq!((zero, top), (0.0, 1.0)),
+                FractalKind::Multiplicative => assert_eq!((bottom, top), (0.0, 1.0)),
+                FractalKind::Ridged => assert_eq!((top, zero), (0.0, 1.0)),
+            }
+        }
+    }
+    #[test]
+    fn fractal_fractional_octaves_are_continuous() {
+        let mut rng = UniformRandomGen::new(1);
+
+        for kind in [
+            FractalKind::Fbm,
+            FractalKind::Turbulence,
+            FractalKind::Multiplicative,
+            FractalKind::Ridged,
+        ] {
+            for octaves in [2.0, 3.0, 4.0, 5.0] {
+                let eval = |rng: &mut UniformRandomGen, octaves: f32| {
+                    let fractal = Fractal::builder(kind).octaves(octaves).build().unwrap();
+                    fractal.eval_2d(rng, 1.37, 2.71, perlin_noise_2d)
+                };
+                let below = eval(&mut rng, octaves - 0.0001);
+                let at = eval(&mut rng, octaves);
+                let above = eval(&mut rng, octaves + 0.0001);
+
+                assert!(
+                    (below - at).abs() < 1e-3,
+                    "{:?} {} {} {}",
+                    kind,
+                    octaves,
+                    below,
+                    at
+                );
+                assert!(
+                    (above - at).abs() < 1e-3,
+                    "{:?} {} {} {}",
+                    kind,
+                    octaves,
+                    above,
+                    at
+                );
+            }
+        }
+    }
+    #[test]
+    fn fractal_footprint_fades_octaves() {
+        let mut rng = UniformRandomGen::new(1);
+
+        for kind in [
+            FractalKind::Fbm,
+            FractalKind::Turbulence,
+            FractalKind::Multiplicative,
+            FractalKind::Ridged,
+        ] {
+            let fractal = Fractal::builder(kind).octaves(6.0).build().unwrap();
+            let (x, y) = (3.17, 0.42);
+
+            let sharp = fractal.eval_2d(&mut rng, x, y, simplex_noise_2d);
+            let tiny = fractal.eval_footprint_2d(&mut rng, x, y, 1e-6, simplex_noise_2d);
+            assert_eq!(sharp, tiny);
+
+            let (lo, hi) = fractal.range();
+            let blurred = fractal.eval_footprint_2d(&mut rng, x, y, 1e3, simplex_noise_2d);
+            let centre = fractal.eval_footprint_2d(&mut rng, x + 9.0, y, 1e3, simplex_noise_2d);
+            assert_eq!(blurred, centre);
+            assert!(blurred >= lo && blurred <= hi);
+        }
+    }
+    #[test]
+    fn generate_fractal_erosion_perlin_image() {
+        let octaves = 6.0;
+        let freq_falloff = 0.5;
+        let lacunarity = 2.0;
+        let seed = 1;
+        let mut rng = UniformRandomGen::new(seed);
+
+        let width = 256;
+        let height = 256;
+        let img = ImageBuffer::from_fn(width, height, |x, y| {
+            let noise_val = fractal_noise_erosion_2d(
+                &mut rng,
+                x as f32 / width as f32 * 10.0,
+                y as f32 / height as f32 * 10.0,
+                perlin_noise_deriv_2d,
+                octaves,
+                freq_falloff,
+                lacunarity,
+                seed,
+            );
+            let normalized_val = ((noise_val + 1.0) / 2.0 * 255.0) as u8;
+            Luma([normalized_val])
+        });
+
+        img.save("images/fractal_erosion_perlin.png")
+            .expect("Failed to save image");
+    }
+    #[test]
+    fn analytic_derivatives_match_finite_differences() {
+        let mut rng = UniformRandomGen::new(1);
+        let h = 2e-4;
+
+        // simplex_noise_3d has small discontinuities, so a derivative only has to
+        // match one of the one-sided differences.
+        let check = |d: f32, lo: f32, mid: f32, hi: f32| {
+            let tolerance = 0.02 + 0.01 * d.abs();
+            ((hi - mid) / h - d).abs() < tolerance || ((mid - lo) / h - d).abs() < tolerance
+        };
+
+        for i in 0..200 {
+            let x = (i as f32 * 0.173) % 8.0 - 4.1;
+            let y = (i as f32 * 0.311) % 8.0 - 3.7;
+            let z = (i as f32 * 0.097) % 8.0 - 3.9;
+
+            type Noise2 = fn(&mut UniformRandomGen, f32, f32, u32) -> f32;
+            let d2: [([f32; 3], Noise2); 2] = [
+                (perlin_noise_deriv_2d(&mut rng, x, y, 3), perlin_noise_2d),
+                (simplex_noise_deriv_2d(&mut rng, x, y, 3), simplex_noise_2d),
+            ];
+            for (d, f) in d2 {
+                let v = f(&mut rng, x, y, 3);
+                assert!((d[0] - v).abs() < 1e-5);
+                let (x0, x1) = (f(&mut rng, x - h, y, 3), f(&mut rng, x + h, y, 3));
+                let (y0, y1) = (f(&mut rng, x, y - h, 3), f(&mut rng, x, y + h, 3));
+                assert!(check(d[1], x0, v, x1), "{} {} {:?}", x, y, d);
+                assert!(check(d[2], y0, v, y1), "{} {} {:?}", x, y, d);
+            }
+
+            type Noise3 = fn(&mut UniformRandomGen, f32, f32, f32, u32) -> f32;
+            let d3: [([f32; 4], Noise3); 2] = [
+                (perlin_noise_deriv_3d(&mut rng, x, y, z, 3), perlin_noise_3d),
+                (
+                    simplex_noise_deriv_3d(&mut rng, x, y, z, 3),
+                    simplex_noise_3d,
+                ),
+            ];
+            for (d, f) in d3 {
+                let v = f(&mut rng, x, y, z, 3);
+                assert!((d[0] - v).abs() < 1e-5);
+                let (x0, x1) = (f(&mut rng, x - h, y, z, 3), f(&mut rng, x + h, y, z, 3));
+                let (y0, y1) = (f(&mut rng, x, y - h, z, 3), f(&mut rng, x, y + h, z, 3));
+                let (z0, z1) = (f(&mut rng, x, y, z - h, 3), f(&mut rng, x, y, z + h, 3));
+                assert!(check(d[1], x0, v, x1), "{} {} {} {:?}", x, y, z, d);
+                assert!(check(d[2], y0, v, y1), "{} {} {} {:?}", x, y, z, d);
+                assert!(check(d[3], z0, v, z1), "{} {} {} {:?}", x, y, z, d);
+            }
+        }
+    }
+    #[test]
+    #[cfg(feature = "std")]
+    fn generate_graph_image() {
+        let seed = 1;
+        let mut rng = UniformRandomGen::new(seed);
+
+        let fractal = Fractal::builder(FractalKind::Ridged)
+            .octaves(5.0)
+            .seed(seed)
+            .build()
+            .unwrap();
+        let ridges = FractalSource::new(NoiseKind::Simplex, fractal);
+        let cells = Invert::new(Source::new(NoiseKind::WorleyF1, seed));
+        let control = Scale::uniform(Source::new(NoiseKind::Perlin, seed), 0.3);
+        let graph = Turbulence::new(Blend::new(ridges, cells, control), 0.25, 0.8, 3.0, seed);
+
+        let width = 256;
+        let height = 256;
+        let img = ImageBuffer::from_fn(width, height, |x, y| {
+            let noise_val = graph.eval_2d(
+                &mut rng,
+                x as f32 / width as f32 * 10.0,
+                y as f32 / height as f32 * 10.0,
+            );
+            let normalized_val = ((noise_val + 1.0) / 2.0 * 255.0) as u8;
+            Luma([normalized_val])
+        });
+
+        img.save("images/graph.png").expect("Failed to save image");
+    }
+    #[test]
+    #[cfg(feature = "std")]
+    fn graph_modules_combine_values() {
+        let mut rng = UniformRandomGen::new(1);
+        let perlin = Source::new(NoiseKind::Perlin, 5);
+        let (x, y) = (1.3, 4.7);
+        let p = perlin_noise_2d(&mut rng, x, y, 5);
+
+        assert_eq!(perlin.eval_2d(&mut rng, x, y), p);
+        assert_eq!(
+            Add::new(perlin, Constant(0.5)).eval_2d(&mut rng, x, y),
+            p + 0.5
+        );
+        assert_eq!(Invert::new(perlin).eval_2d(&mut rng, x, y), -p);
+        assert_eq!(
+            Clamp::new(Constant(3.0), -1.0, 1.0).eval_2d(&mut rng, x, y),
+            1.0
+        );
+        assert_eq!(
+            ScaleBias::new(Constant(0.5), 2.0, 1.0).eval_3d(&mut rng, x, y, 0.0),
+            2.0
+        );
+
+        let blend = Blend::new(Constant(-1.0), Constant(1.0), Constant(0.0));
+        assert_eq!(blend.eval_1d(&mut rng, x), 0.0);
+
+        let select = Select::new(Constant(-1.0), Constant(1.0), Constant(0.2), 0.0, 0.5, 0.0);
+        assert_eq!(select.eval_4d(&mut rng, x, y, 0.0, 0.0), 1.0);
+
+        let moved = Translate::new(perlin, [1.0, 2.0, 0.0, 0.0]);
+        let p_moved = perlin_noise_2d(&mut rng, x + 1.0, y + 2.0, 5);
+        assert_eq!(moved.eval_2d(&mut rng, x, y), p_moved);
+
+        let turned = Rotate::new(perlin, [0.0, 0.0, 90.0]);
+        let p_turned = perlin_noise_2d(&mut rng, -y, x, 5);
+        assert!((turned.eval_2d(&mut rng, x, y) - p_turned).abs() < 1e-4);
+    }
+    #[test]
+    #[cfg(feature = "std")]
+    fn preset_round_trips_and_builds() {
+        let text = "# Hills with cells
+            dimension 2
+            hills = fractal(perlin, kind = ridged, octaves = 5.5, seed = 3)
+            cells = source(worley_f1, seed = 7)
+            mixed = blend(hills, cells, control = hills)
+            moved = translate(mixed, x = 0.25, t = 1e-3)
+            warped = displace(moved, y = cells)
+            output warped";
+        let preset: super::preset::Preset = text.parse().unwrap();
+        assert_eq!(preset.to_string().parse(), Ok(preset.clone()));
+
+        let fractal = Fractal::builder(FractalKind::Ridged)
+            .octaves(5.5)
+            .seed(3)
+            .build()
+            .unwrap();
+        let hills = FractalSource::new(NoiseKind::Perlin, fractal);
+        let cells = Source::new(NoiseKind::WorleyF1, 7);
+        let mixed = Blend::new(hills, cells, hills);
+        let moved = Translate::new(mixed, [0.25, 0.0, 0.0, 1e-3]);
+        let warped = Displace::new(moved, vec![Box::new(Constant(0.0)), Box::new(cells)]);
+
+        // Worley noise depends on the generator state, so both graphs get their own
+        let (mut rng_a, mut rng_b) = (UniformRandomGen::new(0), UniformRandomGen::new(0));
+        let module = preset.build();
+        for i in 0..16 {
+            let (x, y) = (i as f32 * 0.37, i as f32 * 0.91);
+            assert_eq!(
+                module.eval_2d(&mut rng_a, x, y),
+                warped.eval_2d(&mut rng_b, x, y)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn preset_builds_shared_nodes_once() {
+        // Every node is used twice by the next one, so building each use separately
+        // would take 2^64 steps.
+        let mut text = String::from("dimension 2\nn0 = constant(1)\n");
+        for k in 1..=64 {
+            text += &format!("n{} = add(n{}, n{})\n", k, k - 1, k - 1);
+        }
+        text += "output n64";
+        let mut preset: super::preset::Preset = text.parse().unwrap();
+        preset.build();
+
+        preset.output = "n10".to_string();
+        let module = preset.build();
+        assert_eq!(
+            module.eval_2d(&mut UniformRandomGen::new(0), 0.5, 0.5),
+            1024.0
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn preset_errors_report_locations() {
+        use super::preset::Preset;
+
+        let error = |text: &str| {
+            let e = Preset::parse(text).unwrap_err();
+            (e.line, e.column)
+        };
+        assert_eq!(
+            error("a = source(perlin)\nb = add(a, c)\noutput b"),
+            (2, 12)
+        );
+        assert_eq!(error("a = source(perln)\noutput a"), (1, 12));
+        assert_eq!(error("a = fractal(perlin, octaves = 40)\noutput a"), (1, 5));
+        assert_eq!(error("a = source(perlin, sed = 1)\noutput a"), (1, 26));
+        assert_eq!(error("a = constant(1)\na = constant(2)"), (2, 1));
+        assert_eq!(error("a = constant(1) $"), (1, 17));
+        assert_eq!(error("a = constant(1)"), (1, 1));
+        assert_eq!(error("a = source # 30° é\noutput a"), (1, 19));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn generate_expression_image() {
+        use super::expr::Expression;
+
+        let mut rng = UniformRandomGen::new(0);
+        let expr = Expression::parse(
+            "mix(ridged(perlin, p * 0.8, 5), fbm(simplex, p * 2, 4) * 0.5 + 0.5, smoothstep(-0.3, 0.3, simplex(p * 0.2)))",
+        )
+        .unwrap();
+
+        let width = 256;
+        let height = 256;
+        let values = expr
+            .eval_grid(&mut rng, width, height, &[0.0, 0.0], 10.0 / width as f32)
+            .unwrap();
+        let img = ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
+            let noise_val = values[y as usize * width + x as usize] * 2.0 - 1.0;
+            let normalized_val = ((noise_val + 1.0) / 2.0 * 255.0) as u8;
+            Luma([normalized_val])
+        });
+
+        img.save("images/expression.png")
+            .expect("Failed to save image");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn expressions_evaluate_and_report_errors() {
+        use super::expr::{Expression, Value};
+
+        let mut rng = UniformRandomGen::new(0);
+        let mut eval = |source: &str, p: &[f32]| {
+            Expression::parse(source)
+                .and_then(|e| e.eval(&mut rng, p))
+                .map_err(|e| e.column)
+        };
+
+        assert_eq!(eval("1 + 2 * 3 - 2 ^ 3", &[0.0]), Ok(Value::Float(-1.0)));
+        assert_eq!(eval("-2 ^ 2", &[0.0]), Ok(Value::Float(-4.0)));
+        assert_eq!(
+            eval("p.yx * 2 + vec2(x)", &[1.0, 2.0]),
+            Ok(Value::Vec2([5.0, 3.0]))
+        );
+        assert_eq!(
+            eval("clamp(vec3(-2, 0.5, 2), -1, 1)", &[0.0]),
+            Ok(Value::Vec3([-1.0, 0.5, 1.0]))
+        );
+        assert_eq!(
+            eval("perlin(p * 4, 3)", &[0.3, 0.7]),
+            Ok(Value::Float(perlin_noise_2d(
+                &mut UniformRandomGen::new(0),
+                1.2,
+                2.8,
+                3
+            )))
+        );
+
+        let fractal = Fractal::builder(FractalKind::Ridged)
+            .octaves(6.0)
+            .freq_falloff(0.6)
+            .lacunarity(2.5)
+            .build()
+            .unwrap();
+        let expected = fractal.eval_3d(
+            &mut UniformRandomGen::new(0),
+            0.5,
+            1.0,
+            1.5,
+            simplex_noise_3d,
+        );
+        assert_eq!(
+            eval("ridged(simplex, p, 6, 0.6, 2.5)", &[0.5, 1.0, 1.5]),
+            Ok(Value::Float(expected))
+        );
+
+        // Fractal arguments come in the order of the fractal functions and builder.
+        let fractal = Fractal::builder(FractalKind::Fbm)
+            .octaves(4.0)
+            .freq_falloff(0.5)
+            .lacunarity(2.0)
+            .build()
+            .unwrap();
+        let expected = fractal.eval_2d(&mut UniformRandomGen::new(0), 0.3, 0.7, perlin_noise_2d);
+        assert_eq!(
+            eval("fbm(perlin, p, 4, 0.5, 2)", &[0.3, 0.7]),
+            Ok(Value::Float(expected))
+        );
+        let expected = fractal_noise_add_deriv_2d(
+            &mut UniformRandomGen::new(0),
+            0.3,
+            0.7,
+            simplex_noise_deriv_2d,
+            5.0,
+            0.4,
+            2.2,
+            1,
+        );
+        assert_eq!(
+            eval("fbm_deriv(simplex, p, 5, 0.4, 2.2, 1)", &[0.3, 0.7]),
+            Ok(Value::Vec3(expected))
+        );
+
+        assert_eq!(eval("p + vec3(1)", &[0.0, 0.0]), Err(3));
+        assert_eq!(eval("fbm(perln, p)", &[0.0]), Err(5));
+        assert_eq!(eval("fbm(perlin, p, 50)", &[0.0]), Err(1));
+        assert_eq!(eval("sinn(p)", &[0.0]), Err(1));
+        assert_eq!(eval("z * 2", &[0.0, 0.0]), Err(1));
+        assert_eq!(eval("(1 + 2", &[0.0]), Err(7));
+        assert_eq!(eval("perlin(p, 4294967296)", &[0.0]), Err(11));
+        assert_eq!(eval("perlin(p, 1.5)", &[0.0]), Err(11));
+
+        // Nesting is limited instead of overflowing the stack.
+        let nested = format!("{}1{}", "(".repeat(200), ")".repeat(200));
+        assert_eq!(eval(&nested, &[0.0]), Ok(Value::Float(1.0)));
+        assert_eq!(eval(&format!("{}1", "(".repeat(100_000)), &[0.0]), Err(257));
+        assert_eq!(eval(&format!("{}1", "-".repeat(100_000)), &[0.0]), Err(257));
+
+        let error = Expression::parse("fbm(perlin, p, 4) + fmb(p)").unwrap_err();
+        assert!(error.message.contains("did you mean 'fbm'"));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn image_writers_write_headers_and_rows() {
+        use super::output::{BitDepth, ImageFormat};
+
+        let values = [0.0, 0.5, 1.0, 2.0, -1.0, 0.25];
+        let mut pgm = vec![];
+        ImageFormat::Pgm
+            .write(&mut pgm, 3, 2, &values, BitDepth::Eight)
+            .unwrap();
+        assert_eq!(pgm, b"P5\n3 2\n255\n\x00\x80\xff\xff\x00\x40");
+
+        let mut pfm = vec![];
+        ImageFormat::Pfm
+            .write(&mut pfm, 3, 2, &values, BitDepth::Eight)
+            .unwrap();
+        let header = b"Pf\n3 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        // PFM rows start at the bottom of the image
+        assert_eq!(pfm[header.len()..header.len() + 4], 2.0f32.to_le_bytes());
+
+        let mut ppm = vec![];
+        ImageFormat::Ppm
+            .write(&mut ppm, 3, 2, &values, BitDepth::Sixteen)
+            .unwrap();
+        assert_eq!(&ppm[..13], b"P6\n3 2\n65535\n");
+        assert_eq!(ppm.len(), 13 + 3 * 2 * 3 * 2);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn png_writer_decodes_with_image_crate() {
+        use super::output::{write_png, BitDepth};
+
+        // Large enough to need several stored deflate blocks.
+        let (width, height) = (300, 200);
+        let values: Vec<f32> = (0..width * height)
+            .map(|i| (i % 997) as f32 / 996.0)
+            .collect();
+
+        let mut png = vec![];
+        write_png(&mut png, width, height, &values, BitDepth::Sixteen).unwrap();
+        let img = image::load_from_memory(&png).unwrap().into_luma16();
+        assert_eq!(img.dimensions(), (width as u32, height as u32));
+        for (v, p) in values.iter().zip(img.pixels()) {
+            assert_eq!(p[0], (v * 65535.0).round() as u16);
+        }
+
+        let mut png = vec![];
+        write_png(&mut png, width, height, &values, BitDepth::Eight).unwrap();
+        let img = image::load_from_memory(&png).unwrap().into_luma8();
+        for (v, p) in values.iter().zip(img.pixels()) {
+            assert_eq!(p[0], (v * 255.0).round() as u8);
+        }
+
+        // PNG has no empty images.
+        for (width, height) in [(0, 4), (4, 0)] {
+            let error = write_png(&mut vec![], width, height, &[], BitDepth::Eight).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn generate_gradient_terrain_image() {
+        use super::color::Gradient;
+
+        let seed = 4;
+        let mut rng = UniformRandomGen::new(seed);
+        let gradient = Gradient::terrain();
+        let fractal = Fractal::builder(FractalKind::Fbm)
+            .octaves(6.0)
+            .seed(seed)
+            .build()
+            .unwrap();
+
+        let width = 256;
+        let height = 256;
+        let img = ImageBuffer::from_fn(width, height, |x, y| {
+            let noise_val = fractal.eval_2d(
+                &mut rng,
+                x as f32 / width as f32 * 4.0,
+                y as f32 / height as f32 * 4.0,
+                perlin_noise_2d,
+            );
+            image::Rgba(gradient.sample_u8((noise_val + 1.0) / 2.0))
+        });
+
+        img.save("images/gradient_terrain.png")
+            .expect("Failed to save image");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn gradients_interpolate_between_stops() {
+        use super::color::*;
+
+        let black = [0.0, 0.0, 0.0, 1.0];
+        let white = [1.0, 1.0, 1.0, 0.0];
+        let gradient = Gradient::new().stop(1.0, white).stop(0.0, black);
+
+        assert_eq!(gradient.sample(-1.0), black);
+        assert_eq!(gradient.sample(2.0), white);
+        assert_eq!(gradient.sample(0.25), [0.25, 0.25, 0.25, 0.75]);
+        assert_eq!(gradient.map_noise(0.0), [0.5, 0.5, 0.5, 0.5]);
+
+        let constant = gradient.clone().interpolation(Interpolation::Constant);
+        assert_eq!(constant.sample(0.99), black);
+        let smooth = gradient.clone().interpolation(Interpolation::Smoothstep);
+        assert_eq!(smooth.sample(0.25)[0], 0.15625);
+
+        // Mixing in linear light is brighter than mixing the encoded values.
+        let linear = gradient.clone().space(ColorSpace::Linear);
+        assert!((linear.sample(0.5)[0] - linear_to_srgb(0.5)).abs() < 1e-6);
+
+        let orange = color_from_hex("#ff8000").unwrap();
+        let lab = srgb_to_oklab([orange[0], orange[1], orange[2]]);
+        for (a, b) in oklab_to_srgb(lab).iter().zip(orange) {
+            assert!((a - b).abs() < 1e-3);
+        }
+        assert_eq!(
+            color_from_hex("#f0a8"),
+            Some([1.0, 0.0, 2.0 / 3.0, 8.0 / 15.0])
+        );
+        assert_eq!(color_from_hex("#12345"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn heightmap_exports_raw16_esri_and_obj() {
+        use super::heightmap::Heightmap;
+
+        let mut rng = UniformRandomGen::new(0);
+        let map = Heightmap::sample(&mut rng, 3, 2, [1.0, 2.0], [4.0, 1.0], |_, x, y| {
+            x * 0.1 - y
+        });
+        assert_eq!(map.get(2, 1), 5.0 * 0.1 - 3.0);
+        assert_eq!(map.min_max(), (0.1 - 3.0, 0.5 - 2.0));
+
+        let mut raw = vec![];
+        Heightmap::new(2, 1, vec![-1.0, 0.5])
+            .write_raw16(&mut raw, (-1.0, 1.0))
+            .unwrap();
+        assert_eq!(raw, [0x00, 0x00, 0xff, 0xbf]);
+
+        let mut esri = vec![];
+        Heightmap::new(2, 2, vec![1.0, 2.5, f32::NAN, 4.0])
+            .write_esri_ascii(&mut esri, [100.0, 200.0], 30.0)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(esri).unwrap(),
+            "ncols 2\nnrows 2\nxllcorner 100\nyllcorner 200\ncellsize 30\n\
+             NODATA_value -9999\n1 2.5\n-9999 4\n"
+        );
+
+        let mut obj = vec![];
+        Heightmap::new(3, 3, vec![0.0; 9])
+            .write_obj(&mut obj, 2.0, 10.0)
+            .unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        let count = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+        assert_eq!(
+            (count("v "), count("vt "), count("vn "), count("f ")),
+            (9, 9, 9, 8)
+        );
+        assert!(obj.contains("\nv 4 0 4\n"));
+        assert!(obj.contains("\nvt 0.5 0\n"));
+        assert!(obj
+            .lines()
+            .filter(|l| l.starts_with("vn "))
+            .all(|l| l == "vn -0 1 -0"));
+        assert!(obj.contains("\nf 1/1/1 4/4/4 2/2/2\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn generate_normalmap_perlin_image() {
+        use super::normalmap::NormalMap;
+
+        let seed = 2;
+        let mut rng = UniformRandomGen::new(seed);
+        let width = 256;
+        let height = 256;
+        let map = NormalMap::from_gradient(
+            &mut rng,
+            width,
+            height,
+            [0.0, 0.0],
+            4.0 / width as f32,
+            6.0,
+            |rng, x, y| {
+                fractal_noise_add_deriv_2d(rng, x, y, perlin_noise_deriv_2d, 3.0, 0.4, 2.0, seed)
+            },
+        );
+
+        let rgb = map.to_rgb8();
+        let img = ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
+            image::Rgb(rgb[y as usize * width + x as usize])
+        });
+
+        img.save("images/normalmap_perlin.png")
+            .expect("Failed to save image");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn normal_maps_match_gradients_and_wrap() {
+        use super::normalmap::NormalMap;
+
+        let flat = NormalMap::from_heights(&[0.5; 12], 4, 3, 4.0, false);
+        assert!(flat.normals().iter().all(|n| *n == [0.0, 0.0, 1.0]));
+
+        // A ramp rising to the right tilts the normals to the left, one rising
+        // towards the bottom row tilts them up.
+        let ramp: Vec<f32> = (0..9).map(|i| (i % 3) as f32).collect();
+        let n = NormalMap::from_heights(&ramp, 3, 3, 1.0, false).get(1, 1);
+        assert!((n[0] + 0.5f32.sqrt()).abs() < 1e-6 && n[1] == 0.0);
+        let ramp: Vec<f32> = (0..9).map(|i| (i / 3) as f32).collect();
+        assert!(NormalMap::from_heights(&ramp, 3, 3, 1.0, false).get(1, 1)[1] > 0.0);
+
+        // A wrapped tile has the normals of the same tile inside a repeated buffer.
+        let width = 16;
+        let wave = |i: usize| (std::f32::consts::TAU * (i % width) as f32 / width as f32).sin();
+        let tile: Vec<f32> = (0..width).map(wave).collect();
+        let repeated: Vec<f32> = (0..width * 3).map(wave).collect();
+        let wrapped = NormalMap::from_heights(&tile, width, 1, 1.0, true);
+        let inner = NormalMap::from_heights(&repeated, width * 3, 1, 1.0, false);
+        for i in 0..width {
+            assert_eq!(wrapped.get(i, 0), inner.get(width + i, 0));
+        }
+
+        let mut rng = UniformRandomGen::new(0);
+        let step = 0.01;
+        let analytic =
+            NormalMap::from_gradient(&mut rng, 8, 8, [0.3, 0.6], step, 50.0, |rng, x, y| {
+                perlin_noise_deriv_2d(rng, x, y, 3)
+            });
+        let sampled = NormalMap::from_noise(&mut rng, 8, 8, [0.3, 0.6], step, 50.0, |rng, x, y| {
+            perlin_noise_2d(rng, x, y, 3)
+        });
+        for (a, b) in analytic.normals().iter().zip(sampled.normals()) {
+            for (a, b) in a.iter().zip(b) {
+                assert!((a - b).abs() < 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn batches_match_scalar_noise() {
+        use super::batch::*;
+
+        let (w, h, d) = (9, 7, 3);
+        let (origin, step) = ([0.2, 1.35, 0.7], [0.37, 0.29, 0.61]);
+        let at = |i: usize, j: usize, k: usize| {
+            [
+                origin[0] + i as f32 * step[0],
+                origin[1] + j as f32 * step[1],
+                origin[2] + k as f32 * step[2],
+            ]
+        };
+
+        for (noise, mode) in NoiseKind::ALL
+            .into_iter()
+            .flat_map(|n| [(n, HashMode::Legacy), (n, HashMode::V1)])
+        {
+            let seed = 5;
+            let fresh = || UniformRandomGen::new(seed).with_hash_mode(mode);
+
+            let mut line = vec![0.0; w];
+            fill_1d(&mut line, noise, origin[0], step[0], seed, mode);
+            let mut grid = vec![0.0; w * h];
+            fill_2d(
+                &mut grid,
+                w,
+                noise,
+                [origin[0], origin[1]],
+                [step[0], step[1]],
+                seed,
+                mode,
+            );
+            let mut volume = vec![0.0; w * h * d];
+            fill_3d(&mut volume, w, h, noise, origin, step, seed, mode);
+
+            for k in 0..d {
+                for j in 0..h {
+                    for i in 0..w {
+                        let [x, y, z] = at(i, j, k);
+                        let index = (k * h + j) * w + i;
+                        assert_eq!(volume[index], noise.eval_3d(&mut fresh(), x, y, z, seed));
+                        if k == 0 {
+                            assert_eq!(grid[index], noise.eval_2d(&mut fresh(), x, y, seed));
+                        }
+                        if k == 0 && j == 0 {
+                            assert_eq!(line[index], noise.eval_1d(&mut fresh(), x, seed));
+                        }
+                    }
+                }
+            }
+
+            for kind in FractalKind::ALL {
+                let fractal = Fractal::builder(kind)
+                    .octaves(4.5)
+                    .lacunarity(2.0)
+                    .seed(seed)
+                    .build()
+                    .unwrap();
+
+                fill_fractal_1d(&mut line, noise, &fractal, origin[0], step[0], mode);
+                let o2 = [origin[0], origin[1]];
+                fill_fractal_2d(&mut grid, w, noise, &fractal, o2, [step[0], step[1]], mode);
+                fill_fractal_3d(&mut volume, w, h, noise, &fractal, origin, step, mode);
+
+                for k in 0..d {
+                    for j in 0..h {
+                        for i in 0..w {
+                            let [x, y, z] = at(i, j, k);
+                            let index = (k * h + j) * w + i;
+                            let expected =
+                                fractal.eval_3d(&mut fresh(), x, y, z, |rng, x, y, z, s| {
+                                    noise.eval_3d(rng, x, y, z, s)
+                                });
+                            assert_eq!(
+                                volume[index], expected,
+                                "{:?} {:?} {:?}",
+                                noise, kind, mode
+                            );
+                            if k == 0 {
+                                let expected =
+                                    fractal.eval_2d(&mut fresh(), x, y, |rng, x, y, s| {
+                                        noise.eval_2d(rng, x, y, s)
+                                    });
+                                assert_eq!(
+                                    grid[index], expected,
+                                    "{:?} {:?} {:?}",
+                                    noise, kind, mode
+                                );
+                            }
+                            if k == 0 && j == 0 {
+                                let expected = fractal
+                                    .eval_1d(&mut fresh(), x, |rng, x, s| noise.eval_1d(rng, x, s));
+                                assert_eq!(
+                                    line[index], expected,
+                                    "{:?} {:?} {:?}",
+                                    noise, kind, mode
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn simd_noise_matches_scalar_bits() {
+        use super::simd::*;
+
+        // Lattice points, negative coordinates, large offsets and points whose summed
+        // simplex lattice coordinates overflow an i32, sharing a vector with ordinary
+        // points, 41 points to leave a partial last vector.
+        let mut rng = UniformRandomGen::new(11);
+        let mut coord = |i: usize| match i % 4 {
+            0 => (i as f32 - 18.0) * 0.5,
+            1 => rng.get(i as u32) * 20.0 - 10.0,
+            2 => rng.get(i as u32) * 2e5 - 1e5,
+            _ => -(i as f32) * 0.37,
+        };
+        let xs: Vec<f32> = (0..37).map(&mut coord).collect();
+        let ys: Vec<f32> = (0..37).map(|i| coord(i + 1)).collect();
+        let zs: Vec<f32> = (0..37).map(|i| coord(i + 2)).collect();
+        let far = [
+            (9e8, 9e8, 9e8),
+            (-9e8, -9e8, -9e8),
+            (6e8, 6e8, 6e8),
+            (9e8, -9e8, 6e8),
+        ];
+        let xs: Vec<f32> = xs.into_iter().chain(far.iter().map(|p| p.0)).collect();
+        let ys: Vec<f32> = ys.into_iter().chain(far.iter().map(|p| p.1)).collect();
+        let zs: Vec<f32> = zs.into_iter().chain(far.iter().map(|p| p.2)).collect();
+
+        let mut out = vec![0.0; xs.len()];
+        for level in SimdLevel::ALL.into_iter().filter(|l| l.is_supported()) {
+            for (seed, mode) in [0, 3, 300]
+                .into_iter()
+                .flat_map(|s| [(s, HashMode::Legacy), (s, HashMode::V1)])
+            {
+                let scalar = &mut UniformRandomGen::new(0).with_hash_mode(mode);
+                perlin_2d(level, &mut out, &xs, &ys, seed, mode);
+                for i in 0..xs.len() {
+                    let expected = perlin_noise_2d(scalar, xs[i], ys[i], seed);
+                    assert_eq!(
+                        out[i].to_bits(),
+                        expected.to_bits(),
+                        "{:?} {:?}",
+                        level,
+                        mode
+                    );
+                }
+                perlin_3d(level, &mut out, &xs, &ys, &zs, seed, mode);
+                for i in 0..xs.len() {
+                    let expected = perlin_noise_3d(scalar, xs[i], ys[i], zs[i], seed);
+                    assert_eq!(
+                        out[i].to_bits(),
+                        expected.to_bits(),
+                        "{:?} {:?}",
+                        level,
+                        mode
+                    );
+                }
+                simplex_2d(level, &mut out, &xs, &ys, seed, mode);
+                for i in 0..xs.len() {
+                    let expected = simplex_noise_2d(scalar, xs[i], ys[i], seed);
+                    assert_eq!(
+                        out[i].to_bits(),
+                        expected.to_bits(),
+                        "{:?} {:?}",
+                        level,
+                        mode
+                    );
+                }
+                simplex_3d(level, &mut out, &xs, &ys, &zs, seed, mode);
+                for i in 0..xs.len() {
+                    let expected = simplex_noise_3d(scalar, xs[i], ys[i], zs[i], seed);
+                    assert_eq!(
+                        out[i].to_bits(),
+                        expected.to_bits(),
+                        "{:?} {:?}",
+                        level,
+                        mode
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn parallel_fills_do_not_depend_on_threads() {
+        let (w, h, d) = (70, 90, 3);
+        let fractal = Fractal::builder(FractalKind::Ridged)
+            .octaves(3.5)
+            .build()
+            .unwrap();
+
+        for mode in [HashMode::Legacy, HashMode::V1] {
+            let mut expected = vec![0.0; w * h * d];
+            batch::fill_fractal_3d(
+                &mut expected,
+                w,
+                h,
+                NoiseKind::Simplex,
+                &fractal,
+                [0.5, -2.0, 1.0],
+                [0.03, 0.05, 0.2],
+                mode,
+            );
+            let mut worley = vec![0.0; w * h];
+            batch::fill_2d(
+                &mut worley,
+                w,
+                NoiseKind::WorleyF1,
+                [1.0, 2.0],
+                [0.1, 0.1],
+                4,
+                mode,
+            );
+
+            for threads in [1, 2, 3, 8, 0] {
+                let mut out = vec![0.0; w * h * d];
+                parallel::fill_fractal_3d(
+                    &mut out,
+                    w,
+                    h,
+                    NoiseKind::Simplex,
+                    &fractal,
+                    [0.5, -2.0, 1.0],
+                    [0.03, 0.05, 0.2],
+                    mode,
+                    threads,
+                );
+                assert_eq!(out, expected);
+
+                let mut out = vec![0.0; w * h];
+                parallel::fill_2d(
+                    &mut out,
+                    w,
+                    NoiseKind::WorleyF1,
+                    [1.0, 2.0],
+                    [0.1, 0.1],
+                    4,
+                    mode,
+                    threads,
+                );
+                assert_eq!(out, worley);
+
+                let rng = UniformRandomGen::new(4).with_hash_mode(mode);
+                parallel::fill_2d_with(
+                    &mut out,
+                    w,
+                    [1.0, 2.0],
+                    [0.1, 0.1],
+                    &rng,
+                    threads,
+                    |rng, x, y| worley_f1_noise_2d(rng, x, y, 4),
+                );
+                assert_eq!(out, worley);
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_random_gen_distributions() {
+        // The output of the PCG32 reference implementation for seed 42 and stream 54.
+        let mut rng = UniformRandomGen::with_stream(42, 54);
+        let expected = [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b];
+        for value in expected {
+            assert_eq!(rng.next_u32(), value);
+        }
+
+        // The legacy hash is not affected by the PCG state.
+        let (mut a, mut b) = (UniformRandomGen::new(5), UniformRandomGen::new(5));
+        b.next_u64();
+        assert_eq!(a.get(17), b.get(17));
+        assert_eq!(a.get_(), b.get_());
+
+        let n = 20000;
+        let mean = |f: &mut dyn FnMut() -> f32| (0..n).map(|_| f() as f64).sum::<f64>() / n as f64;
+        let rng = &mut UniformRandomGen::new(9);
+        assert!((mean(&mut || rng.next_f32()) - 0.5).abs() < 0.01);
+        assert!((mean(&mut || rng.normal(2.0, 3.0)) - 2.0).abs() < 0.1);
+        assert!((mean(&mut || rng.exponential(4.0)) - 0.25).abs() < 0.01);
+        assert!((mean(&mut || rng.poisson(3.5) as f32) - 3.5).abs() < 0.05);
+        assert!((mean(&mut || rng.poisson(250.0) as f32) - 250.0).abs() < 0.5);
+        let variance = (0..n)
+            .map(|_| (rng.normal(0.0, 3.0) as f64).powi(2))
+            .sum::<f64>()
+            / n as f64;
+        assert!((variance - 9.0).abs() < 0.4);
+
+        for _ in 0..1000 {
+            let v = rng.range_i32(-3..4);
+            assert!((-3..4).contains(&v));
+            assert!(rng.range_u32(10..11) == 10);
+            let v = rng.range_f32(-2.0, -1.0);
+            assert!((-2.0..-1.0).contains(&v));
+            let [x, y] = rng.unit_circle();
+            assert!((x * x + y * y - 1.0).abs() < 1e-5);
+            let [x, y, z] = rng.unit_sphere();
+            assert!((x * x + y * y + z * z - 1.0).abs() < 1e-5);
+        }
+
+        let mut values: Vec<u32> = (0..100).collect();
+        rng.shuffle(&mut values);
+        assert_ne!(values, (0..100).collect::<Vec<_>>());
+        values.sort();
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn lattice_hash_avalanche() {
+        // Flipping any input bit of hash3 flips every output bit in about half the cases.
+        let samples = 1 << 16;
+        let rng = &mut UniformRandomGen::new(11);
+        let mut worst = 0.0f64;
+        for bit in 0..128 {
+            let mut flips = [0u32; 32];
+            for _ in 0..samples {
+                let mut input = [
+                    rng.next_u32(),
+                    rng.next_u32(),
+                    rng.next_u32(),
+                    rng.next_u32(),
+                ];
+                let h = |i: [u32; 4]| hash::hash3(i[0] as i32, i[1] as i32, i[2] as i32, i[3]);
+                let before = h(input);
+                input[bit / 32] ^= 1 << (bit % 32);
+                let changed = before ^ h(input);
+                for (o, flip) in flips.iter_mut().enumerate() {
+                    *flip += (changed >> o) & 1;
+                }
+            }
+            for flip in flips {
+                worst = worst.max((flip as f64 / samples as f64 - 0.5).abs());
+            }
+        }
+        assert!(worst < 0.01, "output bit bias {}", worst);
+
+        // A bijection of each coordinate, so no two cells of a row share a hash.
+        let mut row: Vec<u32> = (-5000..5000).map(|x| hash::hash2(x, 17, 3)).collect();
+        row.sort();
+        row.dedup();
+        assert_eq!(row.len(), 10000);
+        assert!((0..1000).all(|i| {
+            let v = hash::to_signed(hash::hash1(i, 0));
+            (-1.0..1.0).contains(&v)
+        }));
+    }
+
+    #[test]
+    fn hash_modes() {
+        let legacy = &mut UniformRandomGen::new(5);
+        let v1 = &mut UniformRandomGen::new(5).with_hash_mode(HashMode::V1);
+        assert_eq!(legacy.hash_mode(), HashMode::Legacy);
+        v1.reset(6);
+        assert_eq!(v1.hash_mode(), HashMode::V1);
+
+        for noise in NoiseKind::ALL {
+            let (x, y) = (3.3, 4.7);
+            assert_ne!(noise.eval_2d(legacy, x, y, 6), noise.eval_2d(v1, x, y, 6));
+            // The V1 hash only depends on the position, so it needs no fresh generator.
+            let a = noise.eval_3d(v1, x, y, 1.9, 6);
+            noise.eval_3d(v1, 40.1, 2.0, 7.5, 6);
+            assert_eq!(noise.eval_3d(v1, x, y, 1.9, 6), a);
+        }
+
+        // The permutation table repeats every 256 cells, the V1 hash does not.
+        let (x, y) = (10.5, 20.25);
+        assert_eq!(
+            perlin_noise_2d(legacy, x, y, 2),
+            perlin_noise_2d(legacy, x + 256.0, y, 2)
+        );
+        assert_ne!(
+            perlin_noise_2d(v1, x, y, 2),
+            perlin_noise_2d(v1, x + 256.0, y, 2)
+        );
+    }
+
+    #[test]
+    fn v1_hash_extreme_coordinates() {
+        // Cells at the limits of i32, where the coordinates saturate, wrap around the
+        // lattice instead of overflowing. Beyond them the cell offsets lose all
+        // precision, but the noises must not panic.
+        let rng = &mut UniformRandomGen::new(5).with_hash_mode(HashMode::V1);
+        for c in [3e9, -3e9] {
+            for noise in [
+                NoiseKind::Perlin,
+                NoiseKind::Simplex,
+                NoiseKind::RandomFiltered,
+            ] {
+                noise.eval_2d(rng, c, -c, 6);
+                noise.eval_3d(rng, c, c, -c, 6);
+                noise.eval_4d(rng, c, -c, c, -c, 6);
+            }
+        }
+        for c in [2147483648.0, -2147483648.0] {
+            for noise in [
+                NoiseKind::Perlin,
+                NoiseKind::Simplex,
+                NoiseKind::RandomFiltered,
+            ] {
+                let values = [
+                    noise.eval_2d(rng, c, -c, 6),
+                    noise.eval_3d(rng, c, c, -c, 6),
+                    noise.eval_4d(rng, c, -c, c, -c, 6),
+                ];
+                assert!(values.iter().all(|v| v.is_finite()), "{} at {}", noise, c);
+            }
+        }
+    }
+
+    #[test]
+    fn seed_sequence_children() {
+        let root = SeedSequence::from_name("world-42");
+        assert_eq!(root, SeedSequence::from_name("world-42"));
+        assert_ne!(root, SeedSequence::from_name("world-43"));
+        assert_eq!(
+            root.child("terrain").child(3),
+            root.child("terrain").child(3u8)
+        );
+        assert_ne!(root.child("1").seed(), root.child(1).seed());
+        assert_ne!(root.child("ab").child("c"), root.child("a").child("bc"));
+
+        // Children of adjacent labels share no structure: all seeds differ and every
+        // bit is set in about half of them.
+        let seeds: [u32; 4096] = root.seeds();
+        let mut sorted = seeds.to_vec();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), seeds.len());
+        for bit in 0..32 {
+            let ones = seeds.iter().filter(|s| (*s >> bit) & 1 == 1).count();
+            assert!((ones as f32 / seeds.len() as f32 - 0.5).abs() < 0.05);
+        }
+
+        let seed: u32 = root.child(7).into();
+        assert_eq!(seed, root.seeds::<8>()[7]);
+        assert_eq!(root.rng().next_u64(), root.rng().next_u64());
+        assert_ne!(root.rng().next_u64(), root.child(0).rng().next_u64());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sphere_maps_are_seamless() {
+        let noise = |rng: &mut UniformRandomGen, x, y, z| perlin_noise_3d(rng, x, y, z, 2);
+        let rng = &mut UniformRandomGen::new(2);
+
+        // The first and last column meet at the date line, and the top row circles
+        // the north pole at a diameter of 0.1 noise units.
+        let (w, h) = (256, 128);
+        let map = sphere::equirectangular(rng, w, h, 4.0, noise);
+        let (mut seam, mut inner) = (0.0f32, 0.0f32);
+        for row in map.chunks(w) {
+            seam = seam.max((row[0] - row[w - 1]).abs());
+            for i in 1..w {
+                inner = inner.max((row[i] - row[i - 1]).abs());
+            }
+        }
+        assert!(seam <= inner);
+        let (lo, hi) = map[..w]
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+        assert!(hi - lo < 0.2);
+
+        // Cube texels project back onto their face, and the edge texels of
+        // neighbouring faces are as close as neighbours within a face.
+        let size = 64;
+        let faces = sphere::cube_map(rng, size, 4.0, noise);
+        for face in sphere::CubeFace::ALL {
+            for (i, j) in [(0, 0), (5, 40), (size - 1, size / 2)] {
+                let d = sphere::cube_direction(face, size, i, j);
+                let (projected, u, v) = sphere::CubeFace::project(d);
+                assert_eq!(projected, face);
+                assert_eq!(
+                    sphere::cube_direction(face, size, i, j),
+                    face.direction(u, v)
+                );
+            }
+        }
+        // The right edge of +Z meets the left edge of +X.
+        let texel = |f: usize, i: usize, j: usize| faces[f][j * size + i];
+        let (mut seam, mut inner) = (0.0f32, 0.0f32);
+        for j in 0..size {
+            seam = seam.max((texel(4, size - 1, j) - texel(0, 0, j)).abs());
+            for i in 1..size {
+                inner = inner.max((texel(4, i, j) - texel(4, i - 1, j)).abs());
+            }
+        }
+        assert!(seam <= inner);
+
+        // Without displacement the normals are the directions.
+        let normals = sphere::equirectangular_normals(rng, 16, 8, 4.0, 0.0, noise);
+        for (k, n) in normals.iter().enumerate() {
+            let d = sphere::equirectangular_direction(16, 8, k % 16, k / 16);
+            assert!((0..3).all(|a| (n[a] - d[a]).abs() < 1e-5));
+        }
+        let normals = sphere::cube_map_normals(rng, 16, 4.0, 0.05, noise);
+        for n in normals.iter().flatten() {
+            let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            assert!((length - 1.0).abs() < 1e-5);
+        }
+
+        let (lat, lon) = sphere::lat_long(sphere::direction(0.4, -2.0));
+        assert!((lat - 0.4).abs() < 1e-5 && (lon + 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn looping_noise_repeats() {
+        let rng = &mut UniformRandomGen::new(3);
+        let noise2 = |rng: &mut UniformRandomGen, x, y| simplex_noise_2d(rng, x, y, 3);
+        let noise4 = |rng: &mut UniformRandomGen, x, y, z, t| simplex_noise_4d(rng, x, y, z, t, 3);
+
+        for t in [0.0, 0.75, 3.5] {
+            assert_eq!(
+                looping::loop_1d(rng, t, 6.0, noise2),
+                looping::loop_1d(rng, t + 6.0, 6.0, noise2)
+            );
+        }
+
+        let (frames, duration, size) = (24, 4.0, [8.0, 4.0]);
+        for (x, y) in [(0.25, 0.5), (3.0, 1.75)] {
+            let first = looping::tile_loop_2d(rng, x, y, size, 0.0, duration, noise4);
+            let time = looping::frame_time(frames, frames, duration);
+            assert_eq!(
+                looping::tile_loop_2d(rng, x, y, size, time, duration, noise4),
+                first
+            );
+            assert_eq!(
+                looping::tile_loop_2d(rng, x + 8.0, y - 4.0, size, duration, duration, noise4),
+                first
+            );
+            assert_eq!(
+                looping::loop_2d(rng, x, y, 1.0, duration, noise4),
+                looping::loop_2d(rng, x, y, 1.0 + duration, duration, noise4)
+            );
+            assert_eq!(
+                looping::tile_2d(rng, x, y, size, noise4),
+                looping::tile_2d(rng, x - 8.0, y + 4.0, size, noise4)
+            );
+
+            // The animation changes smoothly between frames, also from the last to
+            // the first.
+            let mut previous = first;
+            for frame in 1..=frames {
+                let time = looping::frame_time(frame, frames, duration);
+                let v = looping::tile_loop_2d(rng, x, y, size, time, duration, noise4);
+                assert!((v - previous).abs() < 0.5);
+                previous = v;
+            }
+        }
+    }
+
+    #[test]
+    fn triplanar_blends_planes() {
+        let rng = &mut UniformRandomGen::new(4);
+        let noise = |rng: &mut UniformRandomGen, u, v| perlin_noise_2d(rng, u, v, 4);
+        let deriv = |rng: &mut UniformRandomGen, u, v| perlin_noise_deriv_2d(rng, u, v, 4);
+        let p = [1.3, 2.6, 0.7];
+
+        // Axis aligned normals only see their own plane.
+        let up = triplanar::sample(rng, p, [0.0, -1.0, 0.0], 4.0, noise);
+        assert_eq!(up, perlin_noise_2d(rng, p[0], p[2], 4));
+        let w = triplanar::weights([0.6, -0.48, 0.64], 3.0);
+        assert!((w.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert!(w[2] > w[0] && w[0] > w[1]);
+
+        // For a fixed normal the blended gradient is the gradient of the blend.
+        let normal = [0.6, -0.48, 0.64];
+        let [value, gx, gy, gz] = triplanar::sample_gradient(rng, p, normal, 2.0, deriv);
+        assert!((value - triplanar::sample(rng, p, normal, 2.0, noise)).abs() < 1e-6);
+        let h = 1e-3;
+        for (axis, g) in [gx, gy, gz].into_iter().enumerate() {
+            let (mut p0, mut p1) = (p, p);
+            p0[axis] -= h;
+            p1[axis] += h;
+            let d = (triplanar::sample(rng, p1, normal, 2.0, noise)
+                - triplanar::sample(rng, p0, normal, 2.0, noise))
+                / (2.0 * h);
+            assert!((d - g).abs() < 0.01, "axis {}: {} vs {}", axis, d, g);
+        }
+
+        // Only the gradient along the surface tilts the normal.
+        let n = [0.0, 0.0, 1.0];
+        assert_eq!(triplanar::bump_normal(n, [0.0, 0.0, 5.0], 1.0), n);
+        let bumped = triplanar::bump_normal(n, [1.0, 0.0, 0.0], 1.0);
+        assert!(bumped[0] < 0.0 && bumped[2] > 0.0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "std"))]
+    fn libm_math_matches_std() {
+        use super::math::Float;
+
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-6 * b.abs().max(1.0);
+        for i in -200..200 {
+            let x = i as f32 * 0.137 + 0.01;
+            let y = x.abs() + 0.5;
+            assert_eq!(Float::floor(x), x.floor());
+            assert_eq!(Float::ceil(x), x.ceil());
+            assert_eq!(Float::rem_euclid(x, 2.5), x.rem_euclid(2.5));
+            let (sin, cos) = Float::sin_cos(x);
+            assert!(close(sin as f64, x.sin() as f64) && close(cos as f64, x.cos() as f64));
+            assert!(close(Float::exp(x * 0.1) as f64, (x * 0.1).exp() as f64));
+            assert!(close(Float::ln(y) as f64, y.ln() as f64));
+            assert!(close(Float::sqrt(y) as f64, y.sqrt() as f64));
+            assert!(close(Float::powf(y, 1.7) as f64, y.powf(1.7) as f64));
+
+            let (x, y) = (x as f64, y as f64);
+            assert_eq!(Float::floor(x), x.floor());
+            assert_eq!(Float::rem_euclid(x, 2.5), x.rem_euclid(2.5));
+            assert!(close(Float::ln(y), y.ln()));
+            assert!(close(Float::exp(x * 0.1), (x * 0.1).exp()));
+        }
+    }
 }
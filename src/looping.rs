@@ -0,0 +1,114 @@
+#[cfg(all(not(feature = "std"), not(test)))]
+use crate::math::Float;
+use crate::prelude::UniformRandomGen;
+use core::f32::consts::TAU;
+
+//
+// Looping and tiling noise
+//
+
+// A periodic coordinate is mapped onto a circle whose circumference is its period
+// in noise units, so features keep their size and the noise repeats exactly after
+// one period. Looping a 1D noise needs a 2D noise, looping the time of a 2D
+// animation needs a 4D noise, and tiling both axes of a 2D noise maps them onto a
+// torus in 4D.
+//
+// Tiling and looping at once would need 6 dimensions. `tile_loop_2d` instead moves
+// the torus through the 4D noise along a closed curve over time, which changes the
+// whole tile while keeping it seamless in space and time.
+//
+// Coordinates are wrapped into their period before they are mapped, so a period
+// later the noise returns the same bits and the last frame of an animation joins the
+// first. Scale time by a speed beforehand for faster or slower animations, the
+// duration scaled alike.
+
+/// The point of a coordinate `t` on the circle of circumference `period` around the
+/// origin, going around once per period.
+pub fn circle(t: f32, period: f32) -> [f32; 2] {
+    let (sin, cos) = (t.rem_euclid(period) / period * TAU).sin_cos();
+    let radius = period / TAU;
+    [radius * cos, radius * sin]
+}
+
+/// The time of `frame` of an animation of `frames` frames looping over `duration`.
+pub fn frame_time(frame: usize, frames: usize, duration: f32) -> f32 {
+    (frame % frames) as f32 / frames as f32 * duration
+}
+
+/// A 1D noise repeating every `period`, from a 2D noise on a circle.
+///
+/// ```
+/// use noiselib::looping;
+/// use noiselib::prelude::*;
+///
+/// let rng = &mut UniformRandomGen::new(1);
+/// let wobble = looping::loop_1d(rng, 2.5, 8.0, |rng, x, y| simplex_noise_2d(rng, x, y, 1));
+/// ```
+pub fn loop_1d<F: FnMut(&mut UniformRandomGen, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    t: f32,
+    period: f32,
+    mut noise: F,
+) -> f32 {
+    let [x, y] = circle(t, period);
+    noise(rng, x, y)
+}
+
+/// A 2D noise animated over `time` and looping every `duration`, from a 4D noise.
+pub fn loop_2d<F: FnMut(&mut UniformRandomGen, f32, f32, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    time: f32,
+    duration: f32,
+    mut noise: F,
+) -> f32 {
+    let [z, t] = circle(time, duration);
+    noise(rng, x, y, z, t)
+}
+
+/// A 2D noise tiling every `size[0]` along x and `size[1]` along y, from a 4D noise
+/// on a torus.
+pub fn tile_2d<F: FnMut(&mut UniformRandomGen, f32, f32, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    size: [f32; 2],
+    mut noise: F,
+) -> f32 {
+    let [a, b] = circle(x, size[0]);
+    let [c, d] = circle(y, size[1]);
+    noise(rng, a, b, c, d)
+}
+
+/// A 2D noise tiling every `size` like [`tile_2d`] and animated over `time`, looping
+/// every `duration`.
+///
+/// ```
+/// use noiselib::looping;
+/// use noiselib::prelude::*;
+///
+/// let rng = &mut UniformRandomGen::new(1);
+/// let (frames, duration) = (48, 6.0);
+/// for frame in 0..frames {
+///     let time = looping::frame_time(frame, frames, duration);
+///     let v = looping::tile_loop_2d(rng, 1.5, 0.5, [4.0, 4.0], time, duration, |rng, x, y, z, t| {
+///         simplex_noise_4d(rng, x, y, z, t, 1)
+///     });
+/// }
+/// ```
+pub fn tile_loop_2d<F: FnMut(&mut UniformRandomGen, f32, f32, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    size: [f32; 2],
+    time: f32,
+    duration: f32,
+    mut noise: F,
+) -> f32 {
+    let [a, b] = circle(x, size[0]);
+    let [c, d] = circle(y, size[1]);
+    // A closed curve moving the torus by one noise unit per unit of time.
+    let [p, q] = circle(time, duration).map(|v| v * core::f32::consts::FRAC_1_SQRT_2);
+    noise(rng, a + p, b + q, c + q, d - p)
+}
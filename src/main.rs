@@ -0,0 +1,274 @@
+use noiselib::expr::Expression;
+use noiselib::graph::*;
+use noiselib::output::{BitDepth, ImageFormat};
+use noiselib::prelude::*;
+use noiselib::preset::Preset;
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Renders a noise to an image file.
+
+Usage:
+    noiselib <noise> [options] -o <file>
+    noiselib --expr <expression> [options] -o <file>
+    noiselib --preset <file> [options] -o <file>
+
+Noises:
+    random, random_filtered, perlin, simplex, musgrave, worley_f1
+
+Options:
+    -o, --output <file>       The image to write, its extension selects the format
+    -f, --format <format>     The image format: pgm, ppm, pfm, png
+    -d, --depth <bits>        The bits per sample of pgm, ppm and png: 8, 16 [default: 8]
+    -s, --size <w>x<h>        The image size in pixels [default: 256x256]
+        --scale <units>       The noise units across the image width [default: 10]
+        --offset <x>,<y>      The noise position of the top left corner [default: 0,0]
+        --seed <seed>         The seed of the noise and its fractal [default: 0]
+    -z <z>                    Renders the 3D noise at the slice z
+    -t <t>                    Renders the 4D noise at the slice t, z defaulting to 0
+        --fractal <kind>      Sums octaves of the noise: fbm, turbulence, multiplicative, ridged
+        --octaves <n>         The number of octaves, may be fractional [default: 4]
+        --lacunarity <l>      The frequency factor between octaves [default: 2]
+        --falloff <f>         The amplitude factor between octaves [default: 0.5]
+        --fractal-offset <o>  The offset of multiplicative fractals [default: 2.5]
+        --range <min>,<max>   The values mapped to black and white, defaulting to
+                              the range of the noise or fractal, else -1,1
+    -h, --help                Prints this help
+";
+
+enum Generator {
+    Noise(NoiseKind),
+    Expression(Expression),
+    Preset(PathBuf),
+}
+
+struct Options {
+    generator: Option<Generator>,
+    output: Option<PathBuf>,
+    format: Option<ImageFormat>,
+    depth: BitDepth,
+    size: (usize, usize),
+    scale: f32,
+    offset: (f32, f32),
+    seed: u32,
+    z: Option<f32>,
+    t: Option<f32>,
+    fractal: Option<FractalKind>,
+    fractal_builder: FractalBuilder,
+    range: Option<(f32, f32)>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        print!("{}", USAGE);
+        return;
+    }
+
+    if let Err(error) = parse_args(&args).and_then(render) {
+        eprintln!("noiselib: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, option))
+}
+
+fn parse_pair<T: std::str::FromStr>(
+    option: &str,
+    value: &str,
+    sep: char,
+) -> Result<(T, T), String> {
+    let (a, b) = value
+        .split_once(sep)
+        .ok_or_else(|| format!("expected <a>{}<b> for {}, found '{}'", sep, option, value))?;
+    Ok((parse_number(option, a)?, parse_number(option, b)?))
+}
+
+fn set_generator(options: &mut Options, generator: Generator) -> Result<(), String> {
+    if options.generator.is_some() {
+        return Err("only one noise, expression or preset can be rendered".to_string());
+    }
+    options.generator = Some(generator);
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        generator: None,
+        output: None,
+        format: None,
+        depth: BitDepth::Eight,
+        size: (256, 256),
+        scale: 10.0,
+        offset: (0.0, 0.0),
+        seed: 0,
+        z: None,
+        t: None,
+        fractal: None,
+        fractal_builder: FractalBuilder::default(),
+        range: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            let noise =
+                NoiseKind::from_name(arg).ok_or_else(|| format!("unknown noise '{}'", arg))?;
+            set_generator(&mut options, Generator::Noise(noise))?;
+            continue;
+        }
+
+        let option = arg.as_str();
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", option))?;
+        let b = options.fractal_builder;
+
+        match option {
+            "-o" | "--output" => options.output = Some(PathBuf::from(value)),
+            "-f" | "--format" => {
+                let format = ImageFormat::from_name(value)
+                    .ok_or_else(|| format!("unknown image format '{}'", value))?;
+                options.format = Some(format);
+            }
+            "-d" | "--depth" => {
+                options.depth = BitDepth::from_bits(parse_number(option, value)?)
+                    .ok_or_else(|| format!("unsupported bit depth '{}'", value))?;
+            }
+            "-s" | "--size" => {
+                options.size = parse_pair(option, value, 'x')?;
+                if options.size.0 == 0 || options.size.1 == 0 {
+                    return Err(format!("invalid size '{}'", value));
+                }
+            }
+            "--scale" => options.scale = parse_number(option, value)?,
+            "--offset" => options.offset = parse_pair(option, value, ',')?,
+            "--seed" => options.seed = parse_number(option, value)?,
+            "-z" => options.z = Some(parse_number(option, value)?),
+            "-t" => options.t = Some(parse_number(option, value)?),
+            "--fractal" => {
+                let kind = FractalKind::from_name(value)
+                    .ok_or_else(|| format!("unknown fractal '{}'", value))?;
+                options.fractal = Some(kind);
+            }
+            "--octaves" => options.fractal_builder = b.octaves(parse_number(option, value)?),
+            "--lacunarity" => options.fractal_builder = b.lacunarity(parse_number(option, value)?),
+            "--falloff" => options.fractal_builder = b.freq_falloff(parse_number(option, value)?),
+            "--fractal-offset" => options.fractal_builder = b.offset(parse_number(option, value)?),
+            "--range" => {
+                let (lo, hi): (f32, f32) = parse_pair(option, value, ',')?;
+                if !(lo < hi && (hi - lo).is_finite()) {
+                    return Err(format!(
+                        "invalid range '{}', expected finite <min>,<max> with min < max",
+                        value
+                    ));
+                }
+                options.range = Some((lo, hi));
+            }
+            "--expr" => {
+                let expr = Expression::parse(value)
+                    .map_err(|e| format!("invalid expression\n{}", e.show(value)))?;
+                set_generator(&mut options, Generator::Expression(expr))?;
+            }
+            "--preset" => set_generator(&mut options, Generator::Preset(PathBuf::from(value)))?,
+            _ => return Err(format!("unknown option '{}', see --help", option)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn render(options: Options) -> Result<(), String> {
+    let generator = options
+        .generator
+        .ok_or("missing the noise, expression or preset to render, see --help")?;
+    let output = options.output.ok_or("missing the output file (-o)")?;
+    let format = match options.format {
+        Some(format) => format,
+        None => ImageFormat::from_path(&output).ok_or_else(|| {
+            format!(
+                "cannot tell the image format from '{}', use --format",
+                output.display()
+            )
+        })?,
+    };
+
+    let mut position = vec![0.0, 0.0];
+    if options.z.is_some() || options.t.is_some() {
+        position.push(options.z.unwrap_or(0.0));
+    }
+    if let Some(t) = options.t {
+        position.push(t);
+    }
+
+    let mut range = (-1.0, 1.0);
+    let module: Option<BoxedModule> = match &generator {
+        Generator::Noise(noise) => match options.fractal {
+            Some(kind) => {
+                let fractal = options
+                    .fractal_builder
+                    .kind(kind)
+                    .seed(options.seed)
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                range = fractal.range();
+                Some(Box::new(FractalSource::new(*noise, fractal)))
+            }
+            None => Some(Box::new(Source::new(*noise, options.seed))),
+        },
+        Generator::Preset(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+            let preset = Preset::parse(&text)
+                .map_err(|e| format!("invalid preset '{}': {}", path.display(), e))?;
+            if options.z.is_none() && options.t.is_none() {
+                position.resize(preset.dimension.max(2), 0.0);
+            }
+            Some(preset.build())
+        }
+        Generator::Expression(_) => None,
+    };
+    let (lo, hi) = options.range.unwrap_or(range);
+
+    let (width, height) = options.size;
+    let step = options.scale / width as f32;
+    let mut rng = UniformRandomGen::new(options.seed);
+    let mut values = Vec::with_capacity(width * height);
+
+    for j in 0..height {
+        for i in 0..width {
+            position[0] = options.offset.0 + i as f32 * step;
+            position[1] = options.offset.1 + j as f32 * step;
+            let value = match (&module, &generator) {
+                (Some(module), _) => module.eval(&mut rng, &position),
+                (None, Generator::Expression(expr)) => expr
+                    .eval(&mut rng, &position)
+                    .map_err(|e| e.to_string())?
+                    .as_float()
+                    .ok_or("the expression has to return a float")?,
+                _ => unreachable!(),
+            };
+            values.push(if format == ImageFormat::Pfm {
+                value
+            } else {
+                (value - lo) / (hi - lo)
+            });
+        }
+    }
+
+    let file = std::fs::File::create(&output)
+        .map_err(|e| format!("cannot create '{}': {}", output.display(), e))?;
+    format
+        .write(
+            std::io::BufWriter::new(file),
+            width,
+            height,
+            &values,
+            options.depth,
+        )
+        .map_err(|e| format!("cannot write '{}': {}", output.display(), e))
+}
@@ -0,0 +1,104 @@
+use crate::prelude::*;
+
+//
+// Runtime selection of the noise functions
+//
+
+/// The noise functions of the crate, for selecting one at runtime.
+///
+/// Worley F1 is only available in 2D. Its 1D version samples the line `y = 0`,
+/// its 3D and 4D versions ignore `z` and `t`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NoiseKind {
+    Random,
+    RandomFiltered,
+    Perlin,
+    Simplex,
+    Musgrave,
+    WorleyF1,
+}
+
+impl NoiseKind {
+    pub const ALL: [NoiseKind; 6] = [
+        NoiseKind::Random,
+        NoiseKind::RandomFiltered,
+        NoiseKind::Perlin,
+        NoiseKind::Simplex,
+        NoiseKind::Musgrave,
+        NoiseKind::WorleyF1,
+    ];
+
+    /// The name of the noise, matching the prefix of its functions.
+    pub fn name(self) -> &'static str {
+        match self {
+            NoiseKind::Random => "random",
+            NoiseKind::RandomFiltered => "random_filtered",
+            NoiseKind::Perlin => "perlin",
+            NoiseKind::Simplex => "simplex",
+            NoiseKind::Musgrave => "musgrave",
+            NoiseKind::WorleyF1 => "worley_f1",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn eval_1d(self, rng: &mut UniformRandomGen, x: f32, seed: u32) -> f32 {
+        match self {
+            NoiseKind::Random => random_noise_1d(rng, x, seed),
+            NoiseKind::RandomFiltered => random_noise_filtered_1d(rng, x, seed),
+            NoiseKind::Perlin => perlin_noise_1d(rng, x, seed),
+            NoiseKind::Simplex => simplex_noise_1d(rng, x, seed),
+            NoiseKind::Musgrave => musgrave_noise_1d(rng, x, seed),
+            NoiseKind::WorleyF1 => worley_f1_noise_2d(rng, x, 0.0, seed),
+        }
+    }
+
+    pub fn eval_2d(self, rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32) -> f32 {
+        match self {
+            NoiseKind::Random => random_noise_2d(rng, x, y, seed),
+            NoiseKind::RandomFiltered => random_noise_filtered_2d(rng, x, y, seed),
+            NoiseKind::Perlin => perlin_noise_2d(rng, x, y, seed),
+            NoiseKind::Simplex => simplex_noise_2d(rng, x, y, seed),
+            NoiseKind::Musgrave => musgrave_noise_2d(rng, x, y, seed),
+            NoiseKind::WorleyF1 => worley_f1_noise_2d(rng, x, y, seed),
+        }
+    }
+
+    pub fn eval_3d(self, rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, seed: u32) -> f32 {
+        match self {
+            NoiseKind::Random => random_noise_3d(rng, x, y, z, seed),
+            NoiseKind::RandomFiltered => random_noise_filtered_3d(rng, x, y, z, seed),
+            NoiseKind::Perlin => perlin_noise_3d(rng, x, y, z, seed),
+            NoiseKind::Simplex => simplex_noise_3d(rng, x, y, z, seed),
+            NoiseKind::Musgrave => musgrave_noise_3d(rng, x, y, z, seed),
+            NoiseKind::WorleyF1 => worley_f1_noise_2d(rng, x, y, seed),
+        }
+    }
+
+    pub fn eval_4d(
+        self,
+        rng: &mut UniformRandomGen,
+        x: f32,
+        y: f32,
+        z: f32,
+        t: f32,
+        seed: u32,
+    ) -> f32 {
+        match self {
+            NoiseKind::Random => random_noise_4d(rng, x, y, z, t, seed),
+            NoiseKind::RandomFiltered => random_noise_filtered_4d(rng, x, y, z, t, seed),
+            NoiseKind::Perlin => perlin_noise_4d(rng, x, y, z, t, seed),
+            NoiseKind::Simplex => simplex_noise_4d(rng, x, y, z, t, seed),
+            NoiseKind::Musgrave => musgrave_noise_4d(rng, x, y, z, t, seed),
+            NoiseKind::WorleyF1 => worley_f1_noise_2d(rng, x, y, seed),
+        }
+    }
+}
+
+impl core::fmt::Display for NoiseKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,210 @@
+use crate::heightmap::Heightmap;
+use crate::prelude::*;
+
+//
+// Normal maps
+//
+
+/// Tangent-space normals of a height field, stored row by row from the top.
+///
+/// Normals follow the OpenGL convention: x points right, y up (towards the top row)
+/// and z out of the surface. Negate y for DirectX style normal maps. Slopes are
+/// measured in height per pixel and multiplied by `strength`, so larger strengths
+/// give steeper normals.
+///
+/// ```
+/// use noiselib::normalmap::NormalMap;
+/// use noiselib::prelude::*;
+///
+/// let mut rng = UniformRandomGen::new(1);
+/// let map = NormalMap::from_gradient(&mut rng, 64, 64, [0.0, 0.0], 0.1, 2.0, |rng, x, y| {
+///     perlin_noise_deriv_2d(rng, x, y, 1)
+/// });
+/// let rgb = map.to_rgb8();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct NormalMap {
+    width: usize,
+    height: usize,
+    normals: Vec<[f32; 3]>,
+}
+
+// The normal of the slopes along the columns and rows, rows growing downwards.
+fn normal(dx: f32, dy: f32, strength: f32) -> [f32; 3] {
+    let (nx, ny) = (-dx * strength, dy * strength);
+    let length = (nx * nx + ny * ny + 1.0).sqrt();
+    [nx / length, ny / length, 1.0 / length]
+}
+
+impl NormalMap {
+    /// Normals of a buffer of `width` x `height` heights from central differences.
+    /// With `wrap` the buffer is treated as a tile, so the borders take their
+    /// neighbours from the opposite side and the normal map tiles seamlessly.
+    /// Otherwise one-sided differences are used at the borders.
+    pub fn from_heights(
+        heights: &[f32],
+        width: usize,
+        height: usize,
+        strength: f32,
+        wrap: bool,
+    ) -> Self {
+        assert_eq!(
+            heights.len(),
+            width * height,
+            "expected {} x {} heights",
+            width,
+            height
+        );
+
+        // The neighbours of `i` in a row or column of `n` and their distance.
+        let neighbours = |i: usize, n: usize| -> (usize, usize, f32) {
+            if wrap {
+                ((i + n - 1) % n, (i + 1) % n, 2.0)
+            } else {
+                let (a, b) = (i.saturating_sub(1), (i + 1).min(n - 1));
+                (a, b, (b - a).max(1) as f32)
+            }
+        };
+
+        let mut normals = Vec::with_capacity(width * height);
+        for j in 0..height {
+            let (up, down, dist_y) = neighbours(j, height);
+            for i in 0..width {
+                let (left, right, dist_x) = neighbours(i, width);
+                let dx = (heights[j * width + right] - heights[j * width + left]) / dist_x;
+                let dy = (heights[down * width + i] - heights[up * width + i]) / dist_y;
+                normals.push(normal(dx, dy, strength));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            normals,
+        }
+    }
+
+    /// Normals of a [`Heightmap`], see [`NormalMap::from_heights`].
+    pub fn from_heightmap(map: &Heightmap, strength: f32, wrap: bool) -> Self {
+        Self::from_heights(map.values(), map.width(), map.height(), strength, wrap)
+    }
+
+    /// Normals of any 2D noise, sampled at `origin + (i, j) * step` for pixel `(i, j)`,
+    /// from central differences one pixel apart. The normal map tiles whenever the
+    /// noise does over the sampled region.
+    pub fn from_noise<F: FnMut(&mut UniformRandomGen, f32, f32) -> f32>(
+        rng: &mut UniformRandomGen,
+        width: usize,
+        height: usize,
+        origin: [f32; 2],
+        step: f32,
+        strength: f32,
+        mut noise: F,
+    ) -> Self {
+        let mut normals = Vec::with_capacity(width * height);
+        for j in 0..height {
+            for i in 0..width {
+                let x = origin[0] + i as f32 * step;
+                let y = origin[1] + j as f32 * step;
+                let dx = (noise(rng, x + step, y) - noise(rng, x - step, y)) / 2.0;
+                let dy = (noise(rng, x, y + step) - noise(rng, x, y - step)) / 2.0;
+                normals.push(normal(dx, dy, strength));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            normals,
+        }
+    }
+
+    /// Normals of a 2D noise returning its analytic derivatives as
+    /// `[value, d/dx, d/dy]`, like [`perlin_noise_deriv_2d`] or
+    /// [`fractal_noise_add_deriv_2d`]. This needs a single sample per pixel and
+    /// has no finite difference error.
+    pub fn from_gradient<F: FnMut(&mut UniformRandomGen, f32, f32) -> [f32; 3]>(
+        rng: &mut UniformRandomGen,
+        width: usize,
+        height: usize,
+        origin: [f32; 2],
+        step: f32,
+        strength: f32,
+        mut noise: F,
+    ) -> Self {
+        let mut normals = Vec::with_capacity(width * height);
+        for j in 0..height {
+            for i in 0..width {
+                let x = origin[0] + i as f32 * step;
+                let y = origin[1] + j as f32 * step;
+                let [_, dx, dy] = noise(rng, x, y);
+                normals.push(normal(dx * step, dy * step, strength));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            normals,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn normals(&self) -> &[[f32; 3]] {
+        &self.normals
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> [f32; 3] {
+        self.normals[y * self.width + x]
+    }
+
+    /// The normals mapped from [-1, 1] to RGB in [0, 1], as stored in normal map
+    /// images. Write them with [`write_ppm`](crate::output::write_ppm).
+    pub fn to_rgb(&self) -> Vec<[f32; 3]> {
+        self.normals
+            .iter()
+            .map(|n| n.map(|c| c * 0.5 + 0.5))
+            .collect()
+    }
+
+    /// The normals as 8-bit RGB.
+    pub fn to_rgb8(&self) -> Vec<[u8; 3]> {
+        self.normals
+            .iter()
+            .map(|n| n.map(|c| ((c * 0.5 + 0.5) * 255.0).round() as u8))
+            .collect()
+    }
+}
+
+/// A bump map of a 2D noise: its values at `origin + (i, j) * step`, mapped from
+/// `range` onto [0, 1] and clamped, ready for the grayscale writers of
+/// [`output`](crate::output).
+pub fn bump_map<F: FnMut(&mut UniformRandomGen, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    width: usize,
+    height: usize,
+    origin: [f32; 2],
+    step: f32,
+    range: (f32, f32),
+    mut noise: F,
+) -> Vec<f32> {
+    let mut values = Vec::with_capacity(width * height);
+    for j in 0..height {
+        for i in 0..width {
+            let v = noise(
+                rng,
+                origin[0] + i as f32 * step,
+                origin[1] + j as f32 * step,
+            );
+            values.push(((v - range.0) / (range.1 - range.0)).clamp(0.0, 1.0));
+        }
+    }
+    values
+}
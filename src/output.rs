@@ -0,0 +1,348 @@
+use std::io::{self, Write};
+
+//
+// Image output without dependencies
+//
+
+/// The image formats the crate can write noise buffers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// Binary grayscale PGM.
+    Pgm,
+    /// Binary RGB PPM.
+    Ppm,
+    /// PFM, keeping the values as 32-bit floats.
+    Pfm,
+    /// Grayscale PNG with uncompressed (stored) deflate data.
+    Png,
+}
+
+/// The bits per sample of integer image formats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+impl BitDepth {
+    pub fn bits(self) -> u8 {
+        match self {
+            BitDepth::Eight => 8,
+            BitDepth::Sixteen => 16,
+        }
+    }
+
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            8 => Some(BitDepth::Eight),
+            16 => Some(BitDepth::Sixteen),
+            _ => None,
+        }
+    }
+
+    fn max_value(self) -> u16 {
+        match self {
+            BitDepth::Eight => 255,
+            BitDepth::Sixteen => 65535,
+        }
+    }
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 4] = [
+        ImageFormat::Pgm,
+        ImageFormat::Ppm,
+        ImageFormat::Pfm,
+        ImageFormat::Png,
+    ];
+
+    /// The name of the format, which is also its file extension.
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageFormat::Pgm => "pgm",
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Pfm => "pfm",
+            ImageFormat::Png => "png",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+
+    /// The format matching the extension of `path`, ignoring case.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::from_name(&extension)
+    }
+
+    /// Writes the `width` x `height` grayscale values, stored row by row from the top.
+    /// Integer formats map [0, 1] onto the range of `depth` and clamp values outside
+    /// of it, PFM stores the values unchanged. PPM repeats the value in all channels.
+    pub fn write<W: Write>(
+        self,
+        writer: W,
+        width: usize,
+        height: usize,
+        values: &[f32],
+        depth: BitDepth,
+    ) -> io::Result<()> {
+        match self {
+            ImageFormat::Pgm => write_pgm(writer, width, height, values, depth),
+            ImageFormat::Ppm => {
+                let rgb: Vec<[f32; 3]> = values.iter().map(|v| [*v; 3]).collect();
+                write_ppm(writer, width, height, &rgb, depth)
+            }
+            ImageFormat::Pfm => write_pfm(writer, width, height, values),
+            ImageFormat::Png => write_png(writer, width, height, values, depth),
+        }
+    }
+}
+
+impl std::fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn check_size<T>(width: usize, height: usize, values: &[T]) {
+    assert_eq!(
+        values.len(),
+        width * height,
+        "expected {} x {} values",
+        width,
+        height
+    );
+}
+
+/// Maps `value` from [0, 1] to [0, 255], clamping values outside.
+pub fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Maps `value` from [0, 1] to [0, 65535], clamping values outside.
+pub fn to_u16(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * 65535.0).round() as u16
+}
+
+// Appends the samples in big-endian order, as used by PNM and PNG.
+fn push_samples(bytes: &mut Vec<u8>, values: impl Iterator<Item = f32>, depth: BitDepth) {
+    for v in values {
+        match depth {
+            BitDepth::Eight => bytes.push(to_u8(v)),
+            BitDepth::Sixteen => bytes.extend_from_slice(&to_u16(v).to_be_bytes()),
+        }
+    }
+}
+
+//
+// PNM / PFM
+//
+
+/// Writes values in [0, 1] as a binary grayscale PGM image.
+pub fn write_pgm<W: Write>(
+    mut writer: W,
+    width: usize,
+    height: usize,
+    values: &[f32],
+    depth: BitDepth,
+) -> io::Result<()> {
+    check_size(width, height, values);
+    write!(writer, "P5\n{} {}\n{}\n", width, height, depth.max_value())?;
+    let mut bytes = Vec::with_capacity(values.len() * depth.bits() as usize / 8);
+    push_samples(&mut bytes, values.iter().copied(), depth);
+    writer.write_all(&bytes)
+}
+
+/// Writes RGB values in [0, 1] as a binary PPM image.
+pub fn write_ppm<W: Write>(
+    mut writer: W,
+    width: usize,
+    height: usize,
+    values: &[[f32; 3]],
+    depth: BitDepth,
+) -> io::Result<()> {
+    check_size(width, height, values);
+    write!(writer, "P6\n{} {}\n{}\n", width, height, depth.max_value())?;
+    let mut bytes = Vec::with_capacity(values.len() * 3 * depth.bits() as usize / 8);
+    push_samples(&mut bytes, values.iter().flatten().copied(), depth);
+    writer.write_all(&bytes)
+}
+
+fn write_pfm_samples<W: Write>(
+    mut writer: W,
+    magic: &str,
+    width: usize,
+    height: usize,
+    samples: &[f32],
+) -> io::Result<()> {
+    write!(writer, "{}\n{} {}\n-1.0\n", magic, width, height)?;
+    let mut bytes = Vec::with_capacity(samples.len() * 4);
+    let row_len = (samples.len() / height.max(1)).max(1);
+    for row in samples.chunks(row_len).rev() {
+        for v in row {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    writer.write_all(&bytes)
+}
+
+/// Writes the values as a grayscale PFM image. PFM stores its rows from the bottom,
+/// the values are written in little-endian order.
+pub fn write_pfm<W: Write>(
+    writer: W,
+    width: usize,
+    height: usize,
+    values: &[f32],
+) -> io::Result<()> {
+    check_size(width, height, values);
+    write_pfm_samples(writer, "Pf", width, height, values)
+}
+
+/// Writes RGB values as a color PFM image.
+pub fn write_pfm_rgb<W: Write>(
+    writer: W,
+    width: usize,
+    height: usize,
+    values: &[[f32; 3]],
+) -> io::Result<()> {
+    check_size(width, height, values);
+    let samples: Vec<f32> = values.iter().flatten().copied().collect();
+    write_pfm_samples(writer, "PF", width, height, &samples)
+}
+
+//
+// PNG
+//
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Stored deflate blocks hold at most this many bytes.
+const STORED_BLOCK: usize = 65535;
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+// A zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(STORED_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn write_png_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut body = Vec::with_capacity(data.len() + 4);
+    body.extend_from_slice(kind);
+    body.extend_from_slice(data);
+    writer.write_all(&body)?;
+    writer.write_all(&crc32(&body).to_be_bytes())
+}
+
+// Writes a PNG of `channels` samples per pixel. Color types: 0 gray, 6 RGBA.
+fn write_png_samples<W: Write>(
+    mut writer: W,
+    width: usize,
+    height: usize,
+    color_type: u8,
+    channels: usize,
+    samples: &[f32],
+    depth: BitDepth,
+) -> io::Result<()> {
+    if width == 0 || height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "empty image for PNG",
+        ));
+    }
+    let too_large = |_| io::Error::new(io::ErrorKind::InvalidInput, "image too large for PNG");
+    let png_width = u32::try_from(width).map_err(too_large)?;
+    let png_height = u32::try_from(height).map_err(too_large)?;
+
+    let mut header = vec![];
+    header.extend_from_slice(&png_width.to_be_bytes());
+    header.extend_from_slice(&png_height.to_be_bytes());
+    header.extend_from_slice(&[depth.bits(), color_type, 0, 0, 0]);
+
+    // Every scanline starts with its filter type, 0 being none.
+    let row_len = width * channels;
+    let mut raw = Vec::with_capacity(height * (1 + row_len * depth.bits() as usize / 8));
+    for row in samples.chunks(row_len).take(height) {
+        raw.push(0);
+        push_samples(&mut raw, row.iter().copied(), depth);
+    }
+
+    writer.write_all(&PNG_SIGNATURE)?;
+    write_png_chunk(&mut writer, b"IHDR", &header)?;
+    write_png_chunk(&mut writer, b"IDAT", &zlib_stored(&raw))?;
+    write_png_chunk(&mut writer, b"IEND", &[])
+}
+
+/// Writes values in [0, 1] as a grayscale PNG. The image data is stored without
+/// compression, which keeps the writer small at the cost of file size.
+pub fn write_png<W: Write>(
+    writer: W,
+    width: usize,
+    height: usize,
+    values: &[f32],
+    depth: BitDepth,
+) -> io::Result<()> {
+    check_size(width, height, values);
+    write_png_samples(writer, width, height, 0, 1, values, depth)
+}
+
+/// Writes RGBA colors with channels in [0, 1] as a PNG, for example the colors of a
+/// [`Gradient`](crate::color::Gradient).
+pub fn write_png_rgba<W: Write>(
+    writer: W,
+    width: usize,
+    height: usize,
+    values: &[[f32; 4]],
+    depth: BitDepth,
+) -> io::Result<()> {
+    check_size(width, height, values);
+    let samples: Vec<f32> = values.iter().flatten().copied().collect();
+    write_png_samples(writer, width, height, 6, 4, &samples, depth)
+}
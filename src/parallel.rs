@@ -0,0 +1,241 @@
+use crate::batch::{fractal_row, noise_row, positions};
+use crate::prelude::*;
+
+//
+// Multithreaded buffer generation
+//
+
+// The buffers are split into tiles of whole rows which the threads take in turn.
+// Every sample only depends on its position, the noises using the generator getting
+// a fresh `UniformRandomGen::new(seed)` in the given hash mode per sample as in the
+// `batch` module, and the functions of the `_with` fills a clone of the generator
+// passed in, so the output is identical for any number of threads and equals the
+// batch functions.
+//
+// Threads are spawned with `std::thread::scope` for every call. With the `rayon`
+// feature the tiles are processed on rayon's global pool instead, or on a pool of the
+// requested size. A thread count of 0 uses all available cores.
+
+// The number of samples per tile, rounded to whole rows.
+const TILE_SAMPLES: usize = 4096;
+
+/// Fills `out` with the 2D noise on a grid of `width` columns like
+/// [`batch::fill_2d`](crate::batch::fill_2d), using `threads` threads.
+#[allow(clippy::too_many_arguments)]
+pub fn fill_2d(
+    out: &mut [f32],
+    width: usize,
+    noise: NoiseKind,
+    origin: [f32; 2],
+    step: [f32; 2],
+    seed: u32,
+    mode: HashMode,
+    threads: usize,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for_each_tile(out, width, 1, threads, |j, row| {
+        let y = origin[1] + j as f32 * step[1];
+        noise_row(noise, row, &xs, &[y], seed, mode);
+    });
+}
+
+/// Fills `out` with the 3D noise in a volume of `width` x `height` slices like
+/// [`batch::fill_3d`](crate::batch::fill_3d), using `threads` threads.
+#[allow(clippy::too_many_arguments)]
+pub fn fill_3d(
+    out: &mut [f32],
+    width: usize,
+    height: usize,
+    noise: NoiseKind,
+    origin: [f32; 3],
+    step: [f32; 3],
+    seed: u32,
+    mode: HashMode,
+    threads: usize,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for_each_tile(out, width, height, threads, |r, row| {
+        let y = origin[1] + (r % height) as f32 * step[1];
+        let z = origin[2] + (r / height) as f32 * step[2];
+        noise_row(noise, row, &xs, &[y, z], seed, mode);
+    });
+}
+
+/// Fills `out` with a fractal of the 2D noise like
+/// [`batch::fill_fractal_2d`](crate::batch::fill_fractal_2d), using `threads` threads.
+#[allow(clippy::too_many_arguments)]
+pub fn fill_fractal_2d(
+    out: &mut [f32],
+    width: usize,
+    noise: NoiseKind,
+    fractal: &Fractal,
+    origin: [f32; 2],
+    step: [f32; 2],
+    mode: HashMode,
+    threads: usize,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for_each_tile(out, width, 1, threads, |j, row| {
+        let y = origin[1] + j as f32 * step[1];
+        fractal_row(noise, fractal, row, &xs, &[y], mode);
+    });
+}
+
+/// Fills `out` with a fractal of the 3D noise like
+/// [`batch::fill_fractal_3d`](crate::batch::fill_fractal_3d), using `threads` threads.
+#[allow(clippy::too_many_arguments)]
+pub fn fill_fractal_3d(
+    out: &mut [f32],
+    width: usize,
+    height: usize,
+    noise: NoiseKind,
+    fractal: &Fractal,
+    origin: [f32; 3],
+    step: [f32; 3],
+    mode: HashMode,
+    threads: usize,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for_each_tile(out, width, height, threads, |r, row| {
+        let y = origin[1] + (r % height) as f32 * step[1];
+        let z = origin[2] + (r / height) as f32 * step[2];
+        fractal_row(noise, fractal, row, &xs, &[y, z], mode);
+    });
+}
+
+/// Fills `out` with any 2D function, sample `(i, j)` being `f(&mut rng.clone(), x, y)`
+/// at `origin + (i, j) * step`.
+///
+/// ```
+/// use noiselib::parallel;
+/// use noiselib::prelude::*;
+///
+/// let fractal = Fractal::builder(FractalKind::Ridged).octaves(5.0).build().unwrap();
+/// let rng = UniformRandomGen::new(1).with_hash_mode(HashMode::V1);
+/// let mut buffer = vec![0.0; 256 * 256];
+/// parallel::fill_2d_with(&mut buffer, 256, [0.0, 0.0], [0.02, 0.02], &rng, 0, |rng, x, y| {
+///     fractal.eval_2d(rng, x, y, worley_f1_noise_2d)
+/// });
+/// ```
+pub fn fill_2d_with<F: Fn(&mut UniformRandomGen, f32, f32) -> f32 + Sync>(
+    out: &mut [f32],
+    width: usize,
+    origin: [f32; 2],
+    step: [f32; 2],
+    rng: &UniformRandomGen,
+    threads: usize,
+    f: F,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for_each_tile(out, width, 1, threads, |j, row| {
+        let y = origin[1] + j as f32 * step[1];
+        for (o, x) in row.iter_mut().zip(&xs) {
+            *o = f(&mut rng.clone(), *x, y);
+        }
+    });
+}
+
+/// Fills `out` with any 3D function, see [`fill_2d_with`].
+#[allow(clippy::too_many_arguments)]
+pub fn fill_3d_with<F: Fn(&mut UniformRandomGen, f32, f32, f32) -> f32 + Sync>(
+    out: &mut [f32],
+    width: usize,
+    height: usize,
+    origin: [f32; 3],
+    step: [f32; 3],
+    rng: &UniformRandomGen,
+    threads: usize,
+    f: F,
+) {
+    let xs = positions(width, origin[0], step[0]);
+    for_each_tile(out, width, height, threads, |r, row| {
+        let y = origin[1] + (r % height) as f32 * step[1];
+        let z = origin[2] + (r / height) as f32 * step[2];
+        for (o, x) in row.iter_mut().zip(&xs) {
+            *o = f(&mut rng.clone(), *x, y, z);
+        }
+    });
+}
+
+/// The number of threads used for a requested count, 0 meaning all available cores.
+pub fn thread_count(threads: usize) -> usize {
+    if threads > 0 {
+        threads
+    } else {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    }
+}
+
+// Calls `row(r, samples)` for every row of a buffer of slices of `height` rows of
+// `width` samples, distributing tiles of whole rows over the threads.
+fn for_each_tile<R: Fn(usize, &mut [f32]) + Sync>(
+    out: &mut [f32],
+    width: usize,
+    height: usize,
+    threads: usize,
+    row: R,
+) {
+    assert!(
+        width > 0 && height > 0 && out.len().is_multiple_of(width * height),
+        "the buffer has to hold whole rows and slices"
+    );
+    let tile_rows = (TILE_SAMPLES / width).max(1);
+    let tile = |index: usize, samples: &mut [f32]| {
+        for (r, samples) in samples.chunks_mut(width).enumerate() {
+            row(index * tile_rows + r, samples);
+        }
+    };
+    run_tiles(out, width * tile_rows, threads, tile);
+}
+
+#[cfg(not(feature = "rayon"))]
+fn run_tiles<T: Fn(usize, &mut [f32]) + Sync>(
+    out: &mut [f32],
+    tile_len: usize,
+    threads: usize,
+    tile: T,
+) {
+    let threads = thread_count(threads).min(out.len().div_ceil(tile_len));
+    if threads <= 1 {
+        for (index, samples) in out.chunks_mut(tile_len).enumerate() {
+            tile(index, samples);
+        }
+        return;
+    }
+
+    let tiles = std::sync::Mutex::new(out.chunks_mut(tile_len).enumerate());
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let next = tiles.lock().unwrap().next();
+                match next {
+                    Some((index, samples)) => tile(index, samples),
+                    None => break,
+                }
+            });
+        }
+    });
+}
+
+#[cfg(feature = "rayon")]
+fn run_tiles<T: Fn(usize, &mut [f32]) + Sync>(
+    out: &mut [f32],
+    tile_len: usize,
+    threads: usize,
+    tile: T,
+) {
+    use rayon::prelude::*;
+
+    let mut run = || {
+        out.par_chunks_mut(tile_len)
+            .enumerate()
+            .for_each(|(index, samples)| tile(index, samples))
+    };
+    if threads == 0 {
+        return run();
+    }
+    match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+        Ok(pool) => pool.install(run),
+        Err(_) => run(),
+    }
+}
@@ -1,3 +1,6 @@
+use crate::hash::{lattice1, Lattice2, Lattice3, Lattice4};
+#[cfg(all(not(feature = "std"), not(test)))]
+use crate::math::Float;
 use crate::prelude::UniformRandomGen;
 
 pub const N_GRAD: i32 = 16;
@@ -36,30 +39,35 @@ pub static NOISE_PERM: [i32; N_PERM as usize * 2] = [
     114, 11, 69, 208, 122, 48, 63, 135, 155, 230, 163, 252, 46, 62, 167, 104,
 ];
 
-fn smooth_func(t: f32) -> f32 {
+pub(crate) fn smooth_func(t: f32) -> f32 {
     t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
 }
 
-pub fn perlin_noise_1d(_rng: &mut UniformRandomGen, x: f32, seed: u32) -> f32 {
-    let mut ix = x.floor() as i32;
+pub fn perlin_noise_1d(rng: &mut UniformRandomGen, x: f32, seed: u32) -> f32 {
+    let ix = x.floor() as i32;
     let fx = x - ix as f32;
 
-    ix += NOISE_PERM[(seed & (N_PERM - 1) as u32) as usize];
-
-    let ixp1 = (ix & (N_PERM - 1)) as usize;
-    let ixp2 = ((ix / N_PERM) & (N_PERM - 1)) as usize;
-
-    let h1 = NOISE_PERM[NOISE_PERM[ixp1] as usize + ixp2] & 15;
-    let h2 = NOISE_PERM[NOISE_PERM[ixp1 + 1] as usize + ixp2] & 15;
+    let [h1, h2] = lattice1(rng.hash_mode(), ix, seed);
 
     let wx = smooth_func(fx);
 
-    (1.0 - wx) * GRAD1[h1 as usize] + wx * GRAD1[h2 as usize]
+    (1.0 - wx) * GRAD1[(h1 & 15) as usize] + wx * GRAD1[(h2 & 15) as usize]
 }
 
 #[inline(always)]
 pub fn grad2(ix: i32, iy: i32, fx: f32, fy: f32) -> f32 {
-    let h = NOISE_PERM[NOISE_PERM[ix as usize] as usize + iy as usize] & 7;
+    grad2_hashed(hash2(ix, iy), fx, fy)
+}
+
+// The lattice hash used by grad2, split off so batches can reuse it within a cell.
+#[inline(always)]
+pub(crate) fn hash2(ix: i32, iy: i32) -> i32 {
+    NOISE_PERM[NOISE_PERM[ix as usize] as usize + iy as usize]
+}
+
+#[inline(always)]
+pub(crate) fn grad2_hashed(h: i32, fx: f32, fy: f32) -> f32 {
+    let h = h & 7;
 
     let u = if h < 4 { fx } else { fy };
     let v = if h < 4 { fy } else { fx };
@@ -69,24 +77,19 @@ pub fn grad2(ix: i32, iy: i32, fx: f32, fy: f32) -> f32 {
     u_val + v_val
 }
 
-pub fn perlin_noise_2d(_rng: &mut UniformRandomGen, x: f32, y: f32, mut seed: u32) -> f32 {
-    let mut ix = x.floor() as i32;
-    let mut iy = y.floor() as i32;
+pub fn perlin_noise_2d(rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32) -> f32 {
+    let ix = x.floor() as i32;
+    let iy = y.floor() as i32;
 
     let fx = x - ix as f32;
     let fy = y - iy as f32;
 
-    seed &= N_PERM as u32 - 1;
-    ix += NOISE_PERM[seed as usize];
-    iy += NOISE_PERM[seed as usize + 1];
-
-    ix &= N_PERM - 1;
-    iy &= N_PERM - 1;
+    let lattice = Lattice2::new(rng.hash_mode(), ix, iy, seed);
 
-    let w00 = grad2(ix, iy, fx, fy);
-    let w01 = grad2(ix, iy + 1, fx, fy - 1.0);
-    let w10 = grad2(ix + 1, iy, fx - 1.0, fy);
-    let w11 = grad2(ix + 1, iy + 1, fx - 1.0, fy - 1.0);
+    let w00 = grad2_hashed(lattice.at(0, 0), fx, fy);
+    let w01 = grad2_hashed(lattice.at(0, 1), fx, fy - 1.0);
+    let w10 = grad2_hashed(lattice.at(1, 0), fx - 1.0, fy);
+    let w11 = grad2_hashed(lattice.at(1, 1), fx - 1.0, fy - 1.0);
 
     let wx = smooth_func(fx);
     let wy = smooth_func(fy);
@@ -96,16 +99,24 @@ pub fn perlin_noise_2d(_rng: &mut UniformRandomGen, x: f32, y: f32, mut seed: u3
 
 #[inline(always)]
 pub fn grad3(ix: i32, iy: i32, iz: i32, fx: f32, fy: f32, fz: f32) -> f32 {
-    let h = NOISE_PERM
-        [NOISE_PERM[NOISE_PERM[ix as usize] as usize + iy as usize] as usize + iz as usize]
-        & 15;
+    grad3_hashed(hash3(ix, iy, iz), fx, fy, fz)
+}
+
+#[inline(always)]
+pub(crate) fn hash3(ix: i32, iy: i32, iz: i32) -> i32 {
+    NOISE_PERM[NOISE_PERM[NOISE_PERM[ix as usize] as usize + iy as usize] as usize + iz as usize]
+}
+
+#[inline(always)]
+pub(crate) fn grad3_hashed(h: i32, fx: f32, fy: f32, fz: f32) -> f32 {
+    let h = h & 15;
     let u = if h < 8 || h == 12 || h == 13 { fx } else { fy };
     let v = if h < 4 || h == 12 || h == 13 { fy } else { fz };
 
     ((h & 1) * 2 - 1) as f32 * u + ((h & 2) * 2 - 1) as f32 * v
 }
 
-pub fn perlin_noise_3d(_rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, mut seed: u32) -> f32 {
+pub fn perlin_noise_3d(rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, seed: u32) -> f32 {
     let ix = x.floor() as i32;
     let iy = y.floor() as i32;
     let iz = z.floor() as i32;
@@ -114,21 +125,18 @@ pub fn perlin_noise_3d(_rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, mut
     let fy = y - iy as f32;
     let fz = z - iz as f32;
 
-    seed &= N_PERM as u32 - 1;
-    let ix = (ix + NOISE_PERM[seed as usize]) & (N_PERM - 1);
-    let iy = (iy + NOISE_PERM[seed as usize + 1]) & (N_PERM - 1);
-    let iz = (iz + NOISE_PERM[seed as usize + 2]) & (N_PERM - 1);
+    let lattice = Lattice3::new(rng.hash_mode(), ix, iy, iz, seed);
 
     let wz = smooth_func(fz);
 
-    let w000 = grad3(ix, iy, iz, fx, fy, fz) * (1.0 - wz);
-    let w001 = grad3(ix, iy, iz + 1, fx, fy, fz - 1.0) * wz;
-    let w010 = grad3(ix, iy + 1, iz, fx, fy - 1.0, fz) * (1.0 - wz);
-    let w011 = grad3(ix, iy + 1, iz + 1, fx, fy - 1.0, fz - 1.0) * wz;
-    let w100 = grad3(ix + 1, iy, iz, fx - 1.0, fy, fz) * (1.0 - wz);
-    let w101 = grad3(ix + 1, iy, iz + 1, fx - 1.0, fy, fz - 1.0) * wz;
-    let w110 = grad3(ix + 1, iy + 1, iz, fx - 1.0, fy - 1.0, fz) * (1.0 - wz);
-    let w111 = grad3(ix + 1, iy + 1, iz + 1, fx - 1.0, fy - 1.0, fz - 1.0) * wz;
+    let w000 = grad3_hashed(lattice.at(0, 0, 0), fx, fy, fz) * (1.0 - wz);
+    let w001 = grad3_hashed(lattice.at(0, 0, 1), fx, fy, fz - 1.0) * wz;
+    let w010 = grad3_hashed(lattice.at(0, 1, 0), fx, fy - 1.0, fz) * (1.0 - wz);
+    let w011 = grad3_hashed(lattice.at(0, 1, 1), fx, fy - 1.0, fz - 1.0) * wz;
+    let w100 = grad3_hashed(lattice.at(1, 0, 0), fx - 1.0, fy, fz) * (1.0 - wz);
+    let w101 = grad3_hashed(lattice.at(1, 0, 1), fx - 1.0, fy, fz - 1.0) * wz;
+    let w110 = grad3_hashed(lattice.at(1, 1, 0), fx - 1.0, fy - 1.0, fz) * (1.0 - wz);
+    let w111 = grad3_hashed(lattice.at(1, 1, 1), fx - 1.0, fy - 1.0, fz - 1.0) * wz;
 
     let wx = smooth_func(fx);
     let wy = smooth_func(fy);
@@ -144,6 +152,11 @@ pub fn grad4(ix: i32, iy: i32, iz: i32, it: i32, fx: f32, fy: f32, fz: f32, ft:
         [NOISE_PERM[NOISE_PERM[ix as usize] as usize + iy as usize] as usize + iz as usize]
         as usize
         + it as usize];
+    grad4_hashed(h, fx, fy, fz, ft)
+}
+
+#[inline(always)]
+pub(crate) fn grad4_hashed(h: i32, fx: f32, fy: f32, fz: f32, ft: f32) -> f32 {
     let mut sum = 0.0;
 
     if h & 1 != 0 {
@@ -163,57 +176,45 @@ pub fn grad4(ix: i32, iy: i32, iz: i32, it: i32, fx: f32, fy: f32, fz: f32, ft:
 }
 
 pub fn perlin_noise_4d(
-    _rng: &mut UniformRandomGen,
+    rng: &mut UniformRandomGen,
     x: f32,
     y: f32,
     z: f32,
     t: f32,
-    mut seed: u32,
+    seed: u32,
 ) -> f32 {
-    let mut ix = x.floor() as i32;
-    let mut iy = y.floor() as i32;
-    let mut iz = z.floor() as i32;
-    let mut it = t.floor() as i32;
+    let ix = x.floor() as i32;
+    let iy = y.floor() as i32;
+    let iz = z.floor() as i32;
+    let it = t.floor() as i32;
 
     let fx = x - ix as f32;
     let fy = y - iy as f32;
     let fz = z - iz as f32;
     let ft = t - it as f32;
 
-    seed &= N_PERM as u32 - 1;
-    ix += NOISE_PERM[seed as usize];
-    iy += NOISE_PERM[seed as usize + 1];
-    iz += NOISE_PERM[seed as usize + 2];
-    it += NOISE_PERM[seed as usize + 3];
-
-    ix &= N_PERM - 1;
-    iy &= N_PERM - 1;
-    iz &= N_PERM - 1;
-    it &= N_PERM - 1;
+    let lattice = Lattice4::new(rng.hash_mode(), ix, iy, iz, it, seed);
 
     let wt = smooth_func(ft);
 
-    let w0000 = grad4(ix, iy, iz, it, fx, fy, fz, ft) * (1.0 - wt);
-    let w0001 = grad4(ix, iy, iz, it + 1, fx, fy, fz, ft - 1.0) * wt;
-    let w0010 = grad4(ix, iy, iz + 1, it, fx, fy, fz - 1.0, ft) * (1.0 - wt);
-    let w0011 = grad4(ix, iy, iz + 1, it + 1, fx, fy, fz - 1.0, ft - 1.0) * wt;
-    let w0100 = grad4(ix, iy + 1, iz, it, fx, fy - 1.0, fz, ft) * (1.0 - wt);
-    let w0101 = grad4(ix, iy + 1, iz, it + 1, fx, fy - 1.0, fz, ft - 1.0) * wt;
-    let w0110 = grad4(ix, iy + 1, iz + 1, it, fx, fy - 1.0, fz - 1.0, ft) * (1.0 - wt);
-    let w0111 = grad4(ix, iy + 1, iz + 1, it + 1, fx, fy - 1.0, fz - 1.0, ft - 1.0) * wt;
-
-    let w1000 = grad4(ix + 1, iy, iz, it, fx - 1.0, fy, fz, ft) * (1.0 - wt);
-    let w1001 = grad4(ix + 1, iy, iz, it + 1, fx - 1.0, fy, fz, ft - 1.0) * wt;
-    let w1010 = grad4(ix + 1, iy, iz + 1, it, fx - 1.0, fy, fz - 1.0, ft) * (1.0 - wt);
-    let w1011 = grad4(ix + 1, iy, iz + 1, it + 1, fx - 1.0, fy, fz - 1.0, ft - 1.0) * wt;
-    let w1100 = grad4(ix + 1, iy + 1, iz, it, fx - 1.0, fy - 1.0, fz, ft) * (1.0 - wt);
-    let w1101 = grad4(ix + 1, iy + 1, iz, it + 1, fx - 1.0, fy - 1.0, fz, ft - 1.0) * wt;
-    let w1110 = grad4(ix + 1, iy + 1, iz + 1, it, fx - 1.0, fy - 1.0, fz - 1.0, ft) * (1.0 - wt);
-    let w1111 = grad4(
-        ix + 1,
-        iy + 1,
-        iz + 1,
-        it + 1,
+    let w0000 = grad4_hashed(lattice.at(0, 0, 0, 0), fx, fy, fz, ft) * (1.0 - wt);
+    let w0001 = grad4_hashed(lattice.at(0, 0, 0, 1), fx, fy, fz, ft - 1.0) * wt;
+    let w0010 = grad4_hashed(lattice.at(0, 0, 1, 0), fx, fy, fz - 1.0, ft) * (1.0 - wt);
+    let w0011 = grad4_hashed(lattice.at(0, 0, 1, 1), fx, fy, fz - 1.0, ft - 1.0) * wt;
+    let w0100 = grad4_hashed(lattice.at(0, 1, 0, 0), fx, fy - 1.0, fz, ft) * (1.0 - wt);
+    let w0101 = grad4_hashed(lattice.at(0, 1, 0, 1), fx, fy - 1.0, fz, ft - 1.0) * wt;
+    let w0110 = grad4_hashed(lattice.at(0, 1, 1, 0), fx, fy - 1.0, fz - 1.0, ft) * (1.0 - wt);
+    let w0111 = grad4_hashed(lattice.at(0, 1, 1, 1), fx, fy - 1.0, fz - 1.0, ft - 1.0) * wt;
+
+    let w1000 = grad4_hashed(lattice.at(1, 0, 0, 0), fx - 1.0, fy, fz, ft) * (1.0 - wt);
+    let w1001 = grad4_hashed(lattice.at(1, 0, 0, 1), fx - 1.0, fy, fz, ft - 1.0) * wt;
+    let w1010 = grad4_hashed(lattice.at(1, 0, 1, 0), fx - 1.0, fy, fz - 1.0, ft) * (1.0 - wt);
+    let w1011 = grad4_hashed(lattice.at(1, 0, 1, 1), fx - 1.0, fy, fz - 1.0, ft - 1.0) * wt;
+    let w1100 = grad4_hashed(lattice.at(1, 1, 0, 0), fx - 1.0, fy - 1.0, fz, ft) * (1.0 - wt);
+    let w1101 = grad4_hashed(lattice.at(1, 1, 0, 1), fx - 1.0, fy - 1.0, fz, ft - 1.0) * wt;
+    let w1110 = grad4_hashed(lattice.at(1, 1, 1, 0), fx - 1.0, fy - 1.0, fz - 1.0, ft) * (1.0 - wt);
+    let w1111 = grad4_hashed(
+        lattice.at(1, 1, 1, 1),
         fx - 1.0,
         fy - 1.0,
         fz - 1.0,
@@ -230,3 +231,153 @@ pub fn perlin_noise_4d(
         + wx * ((1.0 - wy) * ((1.0 - wz) * (w1000 + w1001) + wz * (w1010 + w1011))
             + wy * ((1.0 - wz) * (w1100 + w1101) + wz * (w1110 + w1111)))
 }
+
+//
+// Perlin noise with analytic derivatives
+//
+
+fn smooth_func_deriv(t: f32) -> f32 {
+    30.0 * t * t * (t * (t - 2.0) + 1.0)
+}
+
+/// The gradient vector selected by [`grad2`], so that `grad2` is the dot product
+/// of it with `(fx, fy)`.
+#[inline(always)]
+pub fn grad2_vec(ix: i32, iy: i32) -> [f32; 2] {
+    grad2_vec_hashed(hash2(ix, iy))
+}
+
+#[inline(always)]
+pub(crate) fn grad2_vec_hashed(h: i32) -> [f32; 2] {
+    let h = h & 7;
+
+    match (h < 4, h & 1 == 0) {
+        (true, true) => [1.8, 1.0],
+        (true, false) => [-1.0, -1.8],
+        (false, true) => [1.0, 1.8],
+        (false, false) => [-1.8, -1.0],
+    }
+}
+
+/// The gradient vector selected by [`grad3`], so that `grad3` is the dot product
+/// of it with `(fx, fy, fz)`.
+#[inline(always)]
+pub fn grad3_vec(ix: i32, iy: i32, iz: i32) -> [f32; 3] {
+    grad3_vec_hashed(hash3(ix, iy, iz))
+}
+
+#[inline(always)]
+pub(crate) fn grad3_vec_hashed(h: i32) -> [f32; 3] {
+    let h = h & 15;
+    let u = if h < 8 || h == 12 || h == 13 { 0 } else { 1 };
+    let v = if h < 4 || h == 12 || h == 13 { 1 } else { 2 };
+
+    let mut g = [0.0; 3];
+    g[u] = ((h & 1) * 2 - 1) as f32;
+    g[v] = ((h & 2) * 2 - 1) as f32;
+    g
+}
+
+/// [`perlin_noise_2d`] together with its analytic partial derivatives,
+/// returned as `[value, d/dx, d/dy]`.
+pub fn perlin_noise_deriv_2d(rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32) -> [f32; 3] {
+    let ix = x.floor() as i32;
+    let iy = y.floor() as i32;
+
+    let fx = x - ix as f32;
+    let fy = y - iy as f32;
+
+    let lattice = Lattice2::new(rng.hash_mode(), ix, iy, seed);
+    let (h00, h01, h10, h11) = (
+        lattice.at(0, 0),
+        lattice.at(0, 1),
+        lattice.at(1, 0),
+        lattice.at(1, 1),
+    );
+
+    let w00 = grad2_hashed(h00, fx, fy);
+    let w01 = grad2_hashed(h01, fx, fy - 1.0);
+    let w10 = grad2_hashed(h10, fx - 1.0, fy);
+    let w11 = grad2_hashed(h11, fx - 1.0, fy - 1.0);
+
+    let g00 = grad2_vec_hashed(h00);
+    let g01 = grad2_vec_hashed(h01);
+    let g10 = grad2_vec_hashed(h10);
+    let g11 = grad2_vec_hashed(h11);
+
+    let wx = smooth_func(fx);
+    let wy = smooth_func(fy);
+    let dwx = smooth_func_deriv(fx);
+    let dwy = smooth_func_deriv(fy);
+
+    let a = (1.0 - wy) * w00 + wy * w01;
+    let b = (1.0 - wy) * w10 + wy * w11;
+
+    let value = (1.0 - wx) * a + wx * b;
+    let dx = dwx * (b - a)
+        + (1.0 - wx) * ((1.0 - wy) * g00[0] + wy * g01[0])
+        + wx * ((1.0 - wy) * g10[0] + wy * g11[0]);
+    let dy = (1.0 - wx) * (dwy * (w01 - w00) + (1.0 - wy) * g00[1] + wy * g01[1])
+        + wx * (dwy * (w11 - w10) + (1.0 - wy) * g10[1] + wy * g11[1]);
+
+    [value, dx, dy]
+}
+
+/// [`perlin_noise_3d`] together with its analytic partial derivatives,
+/// returned as `[value, d/dx, d/dy, d/dz]`.
+pub fn perlin_noise_deriv_3d(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    z: f32,
+    seed: u32,
+) -> [f32; 4] {
+    let ix = x.floor() as i32;
+    let iy = y.floor() as i32;
+    let iz = z.floor() as i32;
+
+    let fx = x - ix as f32;
+    let fy = y - iy as f32;
+    let fz = z - iz as f32;
+
+    let lattice = Lattice3::new(rng.hash_mode(), ix, iy, iz, seed);
+
+    let wx = smooth_func(fx);
+    let wy = smooth_func(fy);
+    let wz = smooth_func(fz);
+    let dwx = smooth_func_deriv(fx);
+    let dwy = smooth_func_deriv(fy);
+    let dwz = smooth_func_deriv(fz);
+
+    // The corners interpolated along z, as value and gradient, indexed by [x][y].
+    let mut a = [[[0.0f32; 4]; 2]; 2];
+    for (i, ai) in a.iter_mut().enumerate() {
+        for (j, aij) in ai.iter_mut().enumerate() {
+            let (ox, oy) = (fx - i as f32, fy - j as f32);
+            let h0 = lattice.at(i as i32, j as i32, 0);
+            let h1 = lattice.at(i as i32, j as i32, 1);
+
+            let c0 = grad3_hashed(h0, ox, oy, fz);
+            let c1 = grad3_hashed(h1, ox, oy, fz - 1.0);
+            let g0 = grad3_vec_hashed(h0);
+            let g1 = grad3_vec_hashed(h1);
+
+            aij[0] = c0 * (1.0 - wz) + c1 * wz;
+            aij[1] = (1.0 - wz) * g0[0] + wz * g1[0];
+            aij[2] = (1.0 - wz) * g0[1] + wz * g1[1];
+            aij[3] = dwz * (c1 - c0) + (1.0 - wz) * g0[2] + wz * g1[2];
+        }
+    }
+
+    let lerp_y = |i: usize, k: usize| (1.0 - wy) * a[i][0][k] + wy * a[i][1][k];
+    let y0 = lerp_y(0, 0);
+    let y1 = lerp_y(1, 0);
+
+    let value = (1.0 - wx) * y0 + wx * y1;
+    let dx = dwx * (y1 - y0) + (1.0 - wx) * lerp_y(0, 1) + wx * lerp_y(1, 1);
+    let dy = (1.0 - wx) * (dwy * (a[0][1][0] - a[0][0][0]) + lerp_y(0, 2))
+        + wx * (dwy * (a[1][1][0] - a[1][0][0]) + lerp_y(1, 2));
+    let dz = (1.0 - wx) * lerp_y(0, 3) + wx * lerp_y(1, 3);
+
+    [value, dx, dy, dz]
+}
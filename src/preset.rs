@@ -0,0 +1,856 @@
+use crate::graph::*;
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+//
+// Text format for noise graphs
+//
+
+/// A noise setup in a human editable text format, which can be built into a
+/// module graph.
+///
+/// ```text
+/// # Eroded hills with some cellular detail
+/// dimension 2
+///
+/// hills = fractal(perlin, kind = ridged, octaves = 6, seed = 1)
+/// cells = source(worley_f1, seed = 7)
+/// mixed = add(hills, cells)
+/// warped = turbulence(mixed, power = 0.2, frequency = 1, roughness = 3, seed = 2)
+///
+/// output warped
+/// ```
+///
+/// Every line is either empty, a comment starting with `#`, `dimension <1-4>`,
+/// `output <node>` or a node definition `<name> = <operation>(<arguments>)`.
+/// Arguments are node names, noise names or numbers, optionally given by name as
+/// `<parameter> = <value>`. Nodes can only refer to nodes defined above them.
+///
+/// | Operation | Arguments |
+/// |-----------|-----------|
+/// | `source` | `noise, seed = 0` |
+/// | `fractal` | `noise, kind = fbm, octaves = 4, freq_falloff = 0.5, lacunarity = 2, offset = 2.5, seed = 0` |
+/// | `constant` | `value` |
+/// | `add`, `multiply`, `min`, `max` | `a, b` |
+/// | `blend` | `a, b, control` |
+/// | `select` | `a, b, control, lower = -1, upper = 1, falloff = 0` |
+/// | `scale_bias` | `source, scale = 1, bias = 0` |
+/// | `clamp` | `source, lower = -1, upper = 1` |
+/// | `abs`, `invert` | `source` |
+/// | `exponent` | `source, exponent = 1` |
+/// | `translate` | `source, x = 0, y = 0, z = 0, t = 0` |
+/// | `scale` | `source, x = 1, y = 1, z = 1, t = 1` |
+/// | `rotate` | `source, x = 0, y = 0, z = 0` (degrees) |
+/// | `turbulence` | `source, power = 1, frequency = 1, roughness = 3, seed = 0` |
+/// | `displace` | `source, x = <node>, y = <node>, z = <node>, t = <node>` (all optional) |
+///
+/// Noise names are the ones of [`NoiseKind::name`], fractal kinds the ones of
+/// [`FractalKind::name`]. Writing a preset with `to_string()` and parsing it again
+/// returns the same preset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preset {
+    pub dimension: usize,
+    pub nodes: Vec<PresetNode>,
+    pub output: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PresetNode {
+    pub name: String,
+    pub op: PresetOp,
+}
+
+/// The operation of a preset node. Sources of other nodes are given by name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PresetOp {
+    Source {
+        noise: NoiseKind,
+        seed: u32,
+    },
+    Fractal {
+        noise: NoiseKind,
+        fractal: Fractal,
+    },
+    Constant(f32),
+    Add(String, String),
+    Multiply(String, String),
+    Min(String, String),
+    Max(String, String),
+    Blend {
+        a: String,
+        b: String,
+        control: String,
+    },
+    Select {
+        a: String,
+        b: String,
+        control: String,
+        lower: f32,
+        upper: f32,
+        falloff: f32,
+    },
+    ScaleBias {
+        source: String,
+        scale: f32,
+        bias: f32,
+    },
+    Clamp {
+        source: String,
+        lower: f32,
+        upper: f32,
+    },
+    Abs(String),
+    Invert(String),
+    Exponent {
+        source: String,
+        exponent: f32,
+    },
+    Translate {
+        source: String,
+        offset: [f32; 4],
+    },
+    Scale {
+        source: String,
+        scale: [f32; 4],
+    },
+    Rotate {
+        source: String,
+        angles: [f32; 3],
+    },
+    Turbulence {
+        source: String,
+        power: f32,
+        frequency: f32,
+        roughness: f32,
+        seed: u32,
+    },
+    Displace {
+        source: String,
+        axes: [Option<String>; 4],
+    },
+}
+
+/// An error in a preset, at a 1-based line and column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PresetError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for PresetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+const AXES: [&str; 4] = ["x", "y", "z", "t"];
+
+impl Preset {
+    pub fn parse(text: &str) -> Result<Self, PresetError> {
+        Parser::default().parse(text)
+    }
+
+    /// The node with the given name.
+    pub fn node(&self, name: &str) -> Option<&PresetNode> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    /// Builds the module graph of the output node. Every node is built once and
+    /// shared by all nodes using it, which still evaluate it once per use.
+    pub fn build(&self) -> BoxedModule {
+        let mut built: HashMap<&str, Arc<dyn NoiseModule>> = HashMap::new();
+        for node in &self.nodes {
+            let module = Self::build_node(&node.op, &built);
+            built.insert(&node.name, Arc::from(module));
+        }
+        Box::new(built[self.output.as_str()].clone())
+    }
+
+    // Builds the module of an operation from the modules of the nodes above it.
+    fn build_node(op: &PresetOp, built: &HashMap<&str, Arc<dyn NoiseModule>>) -> BoxedModule {
+        let m = |name: &str| -> BoxedModule {
+            Box::new(
+                built
+                    .get(name)
+                    .expect("preset nodes are validated by the parser")
+                    .clone(),
+            )
+        };
+
+        match op {
+            PresetOp::Source { noise, seed } => Box::new(Source::new(*noise, *seed)),
+            PresetOp::Fractal { noise, fractal } => Box::new(FractalSource::new(*noise, *fractal)),
+            PresetOp::Constant(value) => Box::new(Constant(*value)),
+            PresetOp::Add(a, b) => Box::new(Add::new(m(a), m(b))),
+            PresetOp::Multiply(a, b) => Box::new(Multiply::new(m(a), m(b))),
+            PresetOp::Min(a, b) => Box::new(Min::new(m(a), m(b))),
+            PresetOp::Max(a, b) => Box::new(Max::new(m(a), m(b))),
+            PresetOp::Blend { a, b, control } => Box::new(Blend::new(m(a), m(b), m(control))),
+            PresetOp::Select {
+                a,
+                b,
+                control,
+                lower,
+                upper,
+                falloff,
+            } => Box::new(Select::new(
+                m(a),
+                m(b),
+                m(control),
+                *lower,
+                *upper,
+                *falloff,
+            )),
+            PresetOp::ScaleBias {
+                source,
+                scale,
+                bias,
+            } => Box::new(ScaleBias::new(m(source), *scale, *bias)),
+            PresetOp::Clamp {
+                source,
+                lower,
+                upper,
+            } => Box::new(Clamp::new(m(source), *lower, *upper)),
+            PresetOp::Abs(source) => Box::new(Abs::new(m(source))),
+            PresetOp::Invert(source) => Box::new(Invert::new(m(source))),
+            PresetOp::Exponent { source, exponent } => {
+                Box::new(Exponent::new(m(source), *exponent))
+            }
+            PresetOp::Translate { source, offset } => Box::new(Translate::new(m(source), *offset)),
+            PresetOp::Scale { source, scale } => Box::new(Scale::new(m(source), *scale)),
+            PresetOp::Rotate { source, angles } => Box::new(Rotate::new(m(source), *angles)),
+            PresetOp::Turbulence {
+                source,
+                power,
+                frequency,
+                roughness,
+                seed,
+            } => Box::new(Turbulence::new(
+                m(source),
+                *power,
+                *frequency,
+                *roughness,
+                *seed,
+            )),
+            PresetOp::Displace { source, axes } => {
+                let last = axes.iter().rposition(|a| a.is_some()).map_or(0, |i| i + 1);
+                let axes = axes[..last]
+                    .iter()
+                    .map(|a| match a {
+                        Some(name) => m(name),
+                        None => Box::new(Constant(0.0)) as BoxedModule,
+                    })
+                    .collect();
+                Box::new(Displace::new(m(source), axes))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Preset {
+    type Err = PresetError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "dimension {}", self.dimension)?;
+        writeln!(f)?;
+        for node in &self.nodes {
+            writeln!(f, "{} = {}", node.name, node.op)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "output {}", self.output)
+    }
+}
+
+impl std::fmt::Display for PresetOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let axes = |values: &[f32]| {
+            values
+                .iter()
+                .zip(AXES)
+                .map(|(v, axis)| format!(", {} = {}", axis, v))
+                .collect::<String>()
+        };
+
+        match self {
+            PresetOp::Source { noise, seed } => write!(f, "source({}, seed = {})", noise, seed),
+            PresetOp::Fractal { noise, fractal } => write!(
+                f,
+                "fractal({}, kind = {}, octaves = {}, freq_falloff = {}, lacunarity = {}, offset = {}, seed = {})",
+                noise,
+                fractal.kind().name(),
+                fractal.octaves(),
+                fractal.freq_falloff(),
+                fractal.lacunarity(),
+                fractal.offset(),
+                fractal.seed()
+            ),
+            PresetOp::Constant(value) => write!(f, "constant({})", value),
+            PresetOp::Add(a, b) => write!(f, "add({}, {})", a, b),
+            PresetOp::Multiply(a, b) => write!(f, "multiply({}, {})", a, b),
+            PresetOp::Min(a, b) => write!(f, "min({}, {})", a, b),
+            PresetOp::Max(a, b) => write!(f, "max({}, {})", a, b),
+            PresetOp::Blend { a, b, control } => write!(f, "blend({}, {}, {})", a, b, control),
+            PresetOp::Select {
+                a,
+                b,
+                control,
+                lower,
+                upper,
+                falloff,
+            } => write!(
+                f,
+                "select({}, {}, {}, lower = {}, upper = {}, falloff = {})",
+                a, b, control, lower, upper, falloff
+            ),
+            PresetOp::ScaleBias {
+                source,
+                scale,
+                bias,
+            } => write!(f, "scale_bias({}, scale = {}, bias = {})", source, scale, bias),
+            PresetOp::Clamp {
+                source,
+                lower,
+                upper,
+            } => write!(f, "clamp({}, lower = {}, upper = {})", source, lower, upper),
+            PresetOp::Abs(source) => write!(f, "abs({})", source),
+            PresetOp::Invert(source) => write!(f, "invert({})", source),
+            PresetOp::Exponent { source, exponent } => {
+                write!(f, "exponent({}, exponent = {})", source, exponent)
+            }
+            PresetOp::Translate { source, offset } => {
+                write!(f, "translate({}{})", source, axes(offset))
+            }
+            PresetOp::Scale { source, scale } => write!(f, "scale({}{})", source, axes(scale)),
+            PresetOp::Rotate { source, angles } => {
+                write!(f, "rotate({}{})", source, axes(angles))
+            }
+            PresetOp::Turbulence {
+                source,
+                power,
+                frequency,
+                roughness,
+                seed,
+            } => write!(
+                f,
+                "turbulence({}, power = {}, frequency = {}, roughness = {}, seed = {})",
+                source, power, frequency, roughness, seed
+            ),
+            PresetOp::Displace { source, axes } => {
+                write!(f, "displace({}", source)?;
+                for (axis, module) in AXES.iter().zip(axes) {
+                    if let Some(module) = module {
+                        write!(f, ", {} = {}", axis, module)?;
+                    }
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+//
+// Parser
+//
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Number(&'a str),
+    Symbol(char),
+}
+
+impl std::fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(s) | Token::Number(s) => write!(f, "'{}'", s),
+            Token::Symbol(c) => write!(f, "'{}'", c),
+        }
+    }
+}
+
+// A token and its 1-based column.
+type Spanned<'a> = (Token<'a>, usize);
+
+fn tokenize(line: &str, line_no: usize) -> Result<Vec<Spanned<'_>>, PresetError> {
+    let mut tokens = vec![];
+    let bytes = line.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        let start = i;
+
+        if c == '#' {
+            break;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(&line[start..i]), start + 1));
+        } else if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' {
+            i += 1;
+            while i < bytes.len() {
+                let b = bytes[i];
+                let exponent_sign = (b == b'-' || b == b'+') && matches!(bytes[i - 1], b'e' | b'E');
+                if b.is_ascii_alphanumeric() || b == b'.' || exponent_sign {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            tokens.push((Token::Number(&line[start..i]), start + 1));
+        } else if "=(),".contains(c) {
+            i += 1;
+            tokens.push((Token::Symbol(c), start + 1));
+        } else {
+            let column = line[..start].chars().count() + 1;
+            return Err(PresetError {
+                line: line_no,
+                column,
+                message: format!(
+                    "unexpected character '{}'",
+                    line[start..].chars().next().unwrap()
+                ),
+            });
+        }
+    }
+
+    Ok(tokens)
+}
+
+// A value of an operation argument.
+#[derive(Clone, Copy, Debug)]
+enum Value<'a> {
+    Ident(&'a str),
+    Number(&'a str),
+}
+
+#[derive(Debug)]
+struct Arg<'a> {
+    key: Option<&'a str>,
+    value: Value<'a>,
+    column: usize,
+}
+
+#[derive(Default)]
+struct Parser {
+    dimension: Option<usize>,
+    nodes: Vec<PresetNode>,
+    output: Option<String>,
+}
+
+impl Parser {
+    fn parse(mut self, text: &str) -> Result<Preset, PresetError> {
+        let mut line_count = 0;
+
+        for (i, line) in text.lines().enumerate() {
+            line_count = i + 1;
+            let tokens = tokenize(line, i + 1)?;
+            self.parse_line(&tokens, i + 1, line.chars().count() + 1)?;
+        }
+
+        let output = self.output.ok_or(PresetError {
+            line: line_count.max(1),
+            column: 1,
+            message: "missing 'output' line".to_string(),
+        })?;
+
+        Ok(Preset {
+            dimension: self.dimension.unwrap_or(2),
+            nodes: self.nodes,
+            output,
+        })
+    }
+
+    fn parse_line(
+        &mut self,
+        tokens: &[Spanned<'_>],
+        line: usize,
+        end: usize,
+    ) -> Result<(), PresetError> {
+        let err = |column: usize, message: String| PresetError {
+            line,
+            column,
+            message,
+        };
+        let mut tokens = tokens.iter().cloned().peekable();
+
+        let (keyword, column) = match tokens.next() {
+            None => return Ok(()),
+            Some((Token::Ident(name), column)) => (name, column),
+            Some((token, column)) => {
+                return Err(err(
+                    column,
+                    format!("expected a node name, found {}", token),
+                ))
+            }
+        };
+
+        match keyword {
+            "dimension" => {
+                let (dimension, column) = match tokens.next() {
+                    Some((Token::Number(n), column)) => (n.parse::<usize>().ok(), column),
+                    Some((_, column)) => (None, column),
+                    None => (None, end),
+                };
+                match dimension {
+                    Some(d @ 1..=4) => self.dimension = Some(d),
+                    _ => return Err(err(column, "expected a dimension of 1 to 4".to_string())),
+                }
+            }
+            "output" => match tokens.next() {
+                Some((Token::Ident(name), column)) => {
+                    self.check_node(name, line, column)?;
+                    self.output = Some(name.to_string());
+                }
+                Some((_, column)) => return Err(err(column, "expected a node name".to_string())),
+                None => return Err(err(end, "expected a node name".to_string())),
+            },
+            name => {
+                if self.node_exists(name) {
+                    return Err(err(column, format!("node '{}' is already defined", name)));
+                }
+                match tokens.next() {
+                    Some((Token::Symbol('='), _)) => {}
+                    Some((_, column)) => return Err(err(column, "expected '='".to_string())),
+                    None => return Err(err(end, "expected '='".to_string())),
+                }
+                let (op, op_column) = match tokens.next() {
+                    Some((Token::Ident(op), column)) => (op, column),
+                    Some((_, column)) => {
+                        return Err(err(column, "expected an operation".to_string()))
+                    }
+                    None => return Err(err(end, "expected an operation".to_string())),
+                };
+                match tokens.next() {
+                    Some((Token::Symbol('('), _)) => {}
+                    Some((_, column)) => return Err(err(column, "expected '('".to_string())),
+                    None => return Err(err(end, "expected '('".to_string())),
+                }
+
+                let mut args = vec![];
+                let close = loop {
+                    let (token, column) = tokens
+                        .next()
+                        .ok_or_else(|| err(end, "expected ')'".to_string()))?;
+                    if token == Token::Symbol(')') && args.is_empty() {
+                        break column;
+                    }
+                    let value = match token {
+                        Token::Ident(s) => Value::Ident(s),
+                        Token::Number(s) => Value::Number(s),
+                        token => {
+                            return Err(err(
+                                column,
+                                format!("expected an argument, found {}", token),
+                            ))
+                        }
+                    };
+                    let arg = if tokens.peek().map(|t| &t.0) == Some(&Token::Symbol('=')) {
+                        let Value::Ident(key) = value else {
+                            return Err(err(column, "expected a parameter name".to_string()));
+                        };
+                        tokens.next();
+                        match tokens.next() {
+                            Some((Token::Ident(s), c)) => Arg {
+                                key: Some(key),
+                                value: Value::Ident(s),
+                                column: c,
+                            },
+                            Some((Token::Number(s), c)) => Arg {
+                                key: Some(key),
+                                value: Value::Number(s),
+                                column: c,
+                            },
+                            Some((_, c)) => return Err(err(c, "expected a value".to_string())),
+                            None => return Err(err(end, "expected a value".to_string())),
+                        }
+                    } else {
+                        Arg {
+                            key: None,
+                            value,
+                            column,
+                        }
+                    };
+                    args.push(arg);
+
+                    match tokens.next() {
+                        Some((Token::Symbol(','), _)) => {}
+                        Some((Token::Symbol(')'), column)) => break column,
+                        Some((_, column)) => {
+                            return Err(err(column, "expected ',' or ')'".to_string()))
+                        }
+                        None => return Err(err(end, "expected ')'".to_string())),
+                    }
+                };
+
+                if let Some((_, column)) = tokens.next() {
+                    return Err(err(column, "unexpected input after ')'".to_string()));
+                }
+
+                let mut args = Args {
+                    parser: self,
+                    args,
+                    line,
+                    op_column,
+                    close,
+                };
+                let op = args.op(op)?;
+                self.nodes.push(PresetNode {
+                    name: name.to_string(),
+                    op,
+                });
+                return Ok(());
+            }
+        }
+
+        if let Some((_, column)) = tokens.next() {
+            return Err(err(column, "unexpected input".to_string()));
+        }
+        Ok(())
+    }
+
+    fn node_exists(&self, name: &str) -> bool {
+        self.nodes.iter().any(|node| node.name == name)
+    }
+
+    fn check_node(&self, name: &str, line: usize, column: usize) -> Result<(), PresetError> {
+        if self.node_exists(name) {
+            Ok(())
+        } else {
+            Err(PresetError {
+                line,
+                column,
+                message: format!("unknown node '{}'", name),
+            })
+        }
+    }
+}
+
+// The arguments of one operation, consumed by name or position.
+struct Args<'p, 'a> {
+    parser: &'p Parser,
+    args: Vec<Arg<'a>>,
+    line: usize,
+    op_column: usize,
+    close: usize,
+}
+
+impl<'a> Args<'_, 'a> {
+    fn err(&self, column: usize, message: String) -> PresetError {
+        PresetError {
+            line: self.line,
+            column,
+            message,
+        }
+    }
+
+    // Removes the argument named `key`, or else the first positional argument.
+    fn take(&mut self, key: &str) -> Option<Arg<'a>> {
+        if let Some(i) = self.args.iter().position(|a| a.key == Some(key)) {
+            return Some(self.args.remove(i));
+        }
+        let i = self.args.iter().position(|a| a.key.is_none())?;
+        Some(self.args.remove(i))
+    }
+
+    // Removes the argument named `key` only, never a positional one.
+    fn take_named(&mut self, key: &str) -> Option<Arg<'a>> {
+        let i = self.args.iter().position(|a| a.key == Some(key))?;
+        Some(self.args.remove(i))
+    }
+
+    fn required(&mut self, key: &str) -> Result<Arg<'a>, PresetError> {
+        self.take(key)
+            .ok_or_else(|| self.err(self.close, format!("missing argument '{}'", key)))
+    }
+
+    fn node(&mut self, key: &str) -> Result<String, PresetError> {
+        let arg = self.required(key)?;
+        self.node_value(arg)
+    }
+
+    fn node_value(&self, arg: Arg<'a>) -> Result<String, PresetError> {
+        match arg.value {
+            Value::Ident(name) => {
+                self.parser.check_node(name, self.line, arg.column)?;
+                Ok(name.to_string())
+            }
+            Value::Number(_) => Err(self.err(arg.column, "expected a node name".to_string())),
+        }
+    }
+
+    fn noise(&mut self) -> Result<NoiseKind, PresetError> {
+        let arg = self.required("noise")?;
+        match arg.value {
+            Value::Ident(name) => NoiseKind::from_name(name)
+                .ok_or_else(|| self.err(arg.column, format!("unknown noise '{}'", name))),
+            Value::Number(_) => Err(self.err(arg.column, "expected a noise name".to_string())),
+        }
+    }
+
+    fn number(&mut self, key: &str, default: Option<f32>) -> Result<f32, PresetError> {
+        match (self.take(key), default) {
+            (Some(arg), _) => match arg.value {
+                Value::Number(s) => s
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| self.err(arg.column, format!("invalid number '{}'", s))),
+                Value::Ident(s) => Err(self.err(
+                    arg.column,
+                    format!("expected a number for '{}', found '{}'", key, s),
+                )),
+            },
+            (None, Some(default)) => Ok(default),
+            (None, None) => Err(self.err(self.close, format!("missing argument '{}'", key))),
+        }
+    }
+
+    fn seed(&mut self) -> Result<u32, PresetError> {
+        match self.take("seed") {
+            Some(arg) => match arg.value {
+                Value::Number(s) => s
+                    .parse::<u32>()
+                    .map_err(|_| self.err(arg.column, format!("invalid seed '{}'", s))),
+                Value::Ident(s) => Err(self.err(arg.column, format!("invalid seed '{}'", s))),
+            },
+            None => Ok(0),
+        }
+    }
+
+    fn numbers<const N: usize>(&mut self, default: f32) -> Result<[f32; N], PresetError> {
+        let mut values = [default; N];
+        for (value, axis) in values.iter_mut().zip(AXES) {
+            *value = self.number(axis, Some(default))?;
+        }
+        Ok(values)
+    }
+
+    fn op(&mut self, op: &str) -> Result<PresetOp, PresetError> {
+        let op = match op {
+            "source" => PresetOp::Source {
+                noise: self.noise()?,
+                seed: self.seed()?,
+            },
+            "fractal" => {
+                let noise = self.noise()?;
+                let kind = match self.take("kind") {
+                    Some(Arg {
+                        value: Value::Ident(name),
+                        column,
+                        ..
+                    }) => FractalKind::from_name(name).ok_or_else(|| {
+                        self.err(column, format!("unknown fractal kind '{}'", name))
+                    })?,
+                    Some(arg) => {
+                        return Err(self.err(arg.column, "expected a fractal kind".to_string()))
+                    }
+                    None => FractalKind::Fbm,
+                };
+                let defaults = Fractal::default();
+                let fractal = Fractal::builder(kind)
+                    .octaves(self.number("octaves", Some(defaults.octaves()))?)
+                    .freq_falloff(self.number("freq_falloff", Some(defaults.freq_falloff()))?)
+                    .lacunarity(self.number("lacunarity", Some(defaults.lacunarity()))?)
+                    .offset(self.number("offset", Some(defaults.offset()))?)
+                    .seed(self.seed()?)
+                    .build()
+                    .map_err(|e| self.err(self.op_column, e.to_string()))?;
+                PresetOp::Fractal { noise, fractal }
+            }
+            "constant" => PresetOp::Constant(self.number("value", None)?),
+            "add" => PresetOp::Add(self.node("a")?, self.node("b")?),
+            "multiply" => PresetOp::Multiply(self.node("a")?, self.node("b")?),
+            "min" => PresetOp::Min(self.node("a")?, self.node("b")?),
+            "max" => PresetOp::Max(self.node("a")?, self.node("b")?),
+            "blend" => PresetOp::Blend {
+                a: self.node("a")?,
+                b: self.node("b")?,
+                control: self.node("control")?,
+            },
+            "select" => PresetOp::Select {
+                a: self.node("a")?,
+                b: self.node("b")?,
+                control: self.node("control")?,
+                lower: self.number("lower", Some(-1.0))?,
+                upper: self.number("upper", Some(1.0))?,
+                falloff: self.number("falloff", Some(0.0))?,
+            },
+            "scale_bias" => PresetOp::ScaleBias {
+                source: self.node("source")?,
+                scale: self.number("scale", Some(1.0))?,
+                bias: self.number("bias", Some(0.0))?,
+            },
+            "clamp" => PresetOp::Clamp {
+                source: self.node("source")?,
+                lower: self.number("lower", Some(-1.0))?,
+                upper: self.number("upper", Some(1.0))?,
+            },
+            "abs" => PresetOp::Abs(self.node("source")?),
+            "invert" => PresetOp::Invert(self.node("source")?),
+            "exponent" => PresetOp::Exponent {
+                source: self.node("source")?,
+                exponent: self.number("exponent", Some(1.0))?,
+            },
+            "translate" => PresetOp::Translate {
+                source: self.node("source")?,
+                offset: self.numbers(0.0)?,
+            },
+            "scale" => PresetOp::Scale {
+                source: self.node("source")?,
+                scale: self.numbers(1.0)?,
+            },
+            "rotate" => PresetOp::Rotate {
+                source: self.node("source")?,
+                angles: self.numbers(0.0)?,
+            },
+            "turbulence" => PresetOp::Turbulence {
+                source: self.node("source")?,
+                power: self.number("power", Some(1.0))?,
+                frequency: self.number("frequency", Some(1.0))?,
+                roughness: self.number("roughness", Some(3.0))?,
+                seed: self.seed()?,
+            },
+            "displace" => {
+                let source = self.node("source")?;
+                let mut axes: [Option<String>; 4] = Default::default();
+                for (module, axis) in axes.iter_mut().zip(AXES) {
+                    if let Some(arg) = self.take_named(axis) {
+                        *module = Some(self.node_value(arg)?);
+                    }
+                }
+                PresetOp::Displace { source, axes }
+            }
+            _ => {
+                return Err(self.err(self.op_column, format!("unknown operation '{}'", op)));
+            }
+        };
+
+        if let Some(arg) = self.args.first() {
+            let message = match arg.key {
+                Some(key) => format!("unknown parameter '{}'", key),
+                None => "too many arguments".to_string(),
+            };
+            return Err(self.err(arg.column, message));
+        }
+
+        Ok(op)
+    }
+}
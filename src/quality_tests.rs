@@ -0,0 +1,290 @@
+use crate::prelude::*;
+#[cfg(not(feature = "std"))]
+use std::{format, vec::Vec};
+
+//
+// Statistical quality tests
+//
+
+// Every noise is sampled at many pseudo random points in every dimension and its
+// range, mean, variance and isotropy are checked against the values the noise is
+// known to have. The bounds leave room for the sampling error but catch changes to
+// hashing, gradients or scaling. Both hash modes have to meet the same bounds.
+
+const SAMPLES: usize = 20000;
+// Points used for the gradient statistics, which need 16 samples per point and plane.
+const GRADIENT_SAMPLES: usize = 5000;
+// Coordinates are in [1, EXTENT + 1), covering many lattice cells and staying positive
+// for the gradient differences, as the random noises overflow on negative cells in
+// debug builds.
+const EXTENT: f32 = 64.0;
+const SEED: u32 = 3;
+
+struct Expected {
+    range: (f32, f32),
+    mean: (f64, f64),
+    variance: (f64, f64),
+    // The largest ratio between the mean squared directional derivatives of the
+    // directions in a plane, `None` for noises without a meaningful gradient.
+    anisotropy: Option<f64>,
+    // The number of leading axes the noise depends on.
+    axes: usize,
+}
+
+impl Expected {
+    // A gradient noise in [-1, 1], centred on 0.
+    fn centred() -> Self {
+        Self {
+            range: (-1.0, 1.0),
+            mean: (-0.15, 0.15),
+            variance: (0.01, 0.5),
+            anisotropy: Some(3.0),
+            axes: 4,
+        }
+    }
+
+    fn range(self, lo: f32, hi: f32) -> Self {
+        Self {
+            range: (lo, hi),
+            ..self
+        }
+    }
+
+    fn mean(self, lo: f64, hi: f64) -> Self {
+        Self {
+            mean: (lo, hi),
+            ..self
+        }
+    }
+
+    fn variance(self, lo: f64, hi: f64) -> Self {
+        Self {
+            variance: (lo, hi),
+            ..self
+        }
+    }
+
+    fn anisotropy(self, anisotropy: Option<f64>) -> Self {
+        Self { anisotropy, ..self }
+    }
+
+    fn axes(self, axes: usize) -> Self {
+        Self { axes, ..self }
+    }
+}
+
+// A deterministic sequence of points, independent of the generators under test.
+fn points(count: usize) -> Vec<[f32; 4]> {
+    let mut state = 0x853c_49e6_748f_ea9bu64;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        1.0 + (state >> 40) as f32 / (1u64 << 24) as f32 * EXTENT
+    };
+    (0..count)
+        .map(|_| [next(), next(), next(), next()])
+        .collect()
+}
+
+fn eval(noise: NoiseKind, mode: HashMode, dim: usize, p: [f32; 4]) -> f32 {
+    let rng = &mut UniformRandomGen::new(SEED).with_hash_mode(mode);
+    match dim {
+        1 => noise.eval_1d(rng, p[0], SEED),
+        2 => noise.eval_2d(rng, p[0], p[1], SEED),
+        3 => noise.eval_3d(rng, p[0], p[1], p[2], SEED),
+        _ => noise.eval_4d(rng, p[0], p[1], p[2], p[3], SEED),
+    }
+}
+
+// The ratio between the largest and smallest mean squared central difference over
+// eight directions in the plane of the axes `a` and `b`.
+fn anisotropy(
+    noise: NoiseKind,
+    mode: HashMode,
+    dim: usize,
+    a: usize,
+    b: usize,
+    points: &[[f32; 4]],
+) -> f64 {
+    let h = 0.01;
+    let energies: Vec<f64> = (0..8)
+        .map(|k| {
+            let angle = k as f32 * std::f32::consts::PI / 8.0;
+            let (s, c) = angle.sin_cos();
+            let mut energy = 0.0;
+            for p in points {
+                let (mut p0, mut p1) = (*p, *p);
+                p0[a] -= h * c;
+                p0[b] -= h * s;
+                p1[a] += h * c;
+                p1[b] += h * s;
+                let d = (eval(noise, mode, dim, p1) - eval(noise, mode, dim, p0)) / (2.0 * h);
+                energy += (d * d) as f64;
+            }
+            energy / points.len() as f64
+        })
+        .collect();
+
+    let max = energies.iter().copied().fold(f64::MIN, f64::max);
+    let min = energies.iter().copied().fold(f64::MAX, f64::min);
+    max / min
+}
+
+fn check(noise: NoiseKind, dim: usize, expected: Expected) {
+    for mode in [HashMode::Legacy, HashMode::V1] {
+        check_mode(noise, mode, dim, &expected);
+    }
+}
+
+fn check_mode(noise: NoiseKind, mode: HashMode, dim: usize, expected: &Expected) {
+    let name = format!("{} {}D ({:?} hash)", noise, dim, mode);
+    let points = points(SAMPLES);
+
+    let values: Vec<f32> = points.iter().map(|p| eval(noise, mode, dim, *p)).collect();
+    assert!(
+        values.iter().all(|v| v.is_finite()),
+        "{} returned NaN or infinity",
+        name
+    );
+
+    let (lo, hi) = values
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+    assert!(
+        lo >= expected.range.0 && hi <= expected.range.1,
+        "{} has range [{}, {}], expected within {:?}",
+        name,
+        lo,
+        hi,
+        expected.range
+    );
+
+    let mean = values.iter().map(|v| *v as f64).sum::<f64>() / values.len() as f64;
+    let variance = values
+        .iter()
+        .map(|v| (*v as f64 - mean) * (*v as f64 - mean))
+        .sum::<f64>()
+        / values.len() as f64;
+    assert!(
+        mean >= expected.mean.0 && mean <= expected.mean.1,
+        "{} has mean {}, expected within {:?}",
+        name,
+        mean,
+        expected.mean
+    );
+    assert!(
+        variance >= expected.variance.0 && variance <= expected.variance.1,
+        "{} has variance {}, expected within {:?}",
+        name,
+        variance,
+        expected.variance
+    );
+
+    if let Some(max_ratio) = expected.anisotropy {
+        let axes = dim.min(expected.axes);
+        for a in 0..axes {
+            for b in a + 1..axes {
+                let ratio = anisotropy(noise, mode, dim, a, b, &points[..GRADIENT_SAMPLES]);
+                assert!(
+                    ratio <= max_ratio,
+                    "{} has a gradient anisotropy of {} in the plane of axes {} and {}",
+                    name,
+                    ratio,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn random_noise_statistics() {
+    // Uniform in [-1, 1] with a variance of 1/3. The noise is constant within its
+    // cells, so it has no gradient to compare.
+    for dim in 1..=4 {
+        let expected = Expected::centred()
+            .mean(-0.05, 0.05)
+            .variance(0.3, 0.37)
+            .anisotropy(None);
+        check(NoiseKind::Random, dim, expected);
+    }
+}
+
+#[test]
+fn random_filtered_noise_statistics() {
+    // The interpolation between the cell values reduces the variance in higher
+    // dimensions.
+    for (dim, variance) in [(1, 0.1), (2, 0.12), (3, 0.06), (4, 0.04)] {
+        let expected = Expected::centred()
+            .mean(-0.2, 0.2)
+            .variance(variance, 0.25)
+            .anisotropy(Some(2.0));
+        check(NoiseKind::RandomFiltered, dim, expected);
+    }
+}
+
+#[test]
+fn perlin_noise_statistics() {
+    // The 3D gradients have a second component of 3 or -1 in `grad3`, which widens
+    // the range of the 3D noise.
+    for (dim, range, variance) in [(1, 1.0, 0.2), (2, 1.0, 0.1), (3, 1.5, 0.1), (4, 1.0, 0.03)] {
+        let expected = Expected::centred()
+            .range(-range, range)
+            .variance(variance, 0.5);
+        check(NoiseKind::Perlin, dim, expected);
+    }
+}
+
+#[test]
+fn simplex_noise_statistics() {
+    // The 3D noise shares the widened gradients of Perlin noise.
+    for (dim, range, variance) in [
+        (1, 1.0, 0.15),
+        (2, 1.0, 0.15),
+        (3, 2.5, 0.3),
+        (4, 1.0, 0.02),
+    ] {
+        let expected = Expected::centred()
+            .range(-range, range)
+            .variance(variance, 0.7);
+        check(NoiseKind::Simplex, dim, expected);
+    }
+}
+
+#[test]
+fn musgrave_noise_statistics() {
+    // In 1D the outer Perlin noise only sees inputs in [-1, 1], so its mean follows
+    // the noise of the hash mode in that interval.
+    for (dim, range, means, variance) in [
+        (1, 1.0, [(-0.7, -0.3), (-0.1, 0.2)], 0.1),
+        (2, 1.0, [(-0.15, 0.15); 2], 0.15),
+        (3, 1.5, [(-0.15, 0.15); 2], 0.05),
+        (4, 1.0, [(-0.15, 0.15); 2], 0.01),
+    ] {
+        for (mode, mean) in [HashMode::Legacy, HashMode::V1].into_iter().zip(means) {
+            let expected = Expected::centred()
+                .range(-range, range)
+                .mean(mean.0, mean.1)
+                .variance(variance, 0.5);
+            check_mode(NoiseKind::Musgrave, mode, dim, &expected);
+        }
+    }
+}
+
+#[test]
+fn worley_noise_statistics() {
+    // The squared distance to the nearest feature point minus 0.4, exceeding 1 in
+    // cells without nearby points. The noise is 2D, higher dimensions ignore the
+    // other axes.
+    for dim in 1..=4 {
+        let expected = Expected::centred()
+            .range(-0.4, 2.0)
+            .mean(-0.33, -0.2)
+            .variance(0.01, 0.05)
+            .anisotropy(Some(2.0))
+            .axes(2);
+        check(NoiseKind::WorleyF1, dim, expected);
+    }
+}
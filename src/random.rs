@@ -1,3 +1,6 @@
+use crate::hash::{hash1, hash2, hash3, hash4, to_signed, to_unit, HashMode};
+#[cfg(all(not(feature = "std"), not(test)))]
+use crate::math::Float;
 use crate::prelude::UniformRandomGen;
 
 //
@@ -5,17 +8,29 @@ use crate::prelude::UniformRandomGen;
 //
 
 pub fn random_noise_1d(rng: &mut UniformRandomGen, x: f32, seed: u32) -> f32 {
+    if rng.hash_mode() == HashMode::V1 {
+        return to_signed(hash1(cell(x), seed));
+    }
+
     let x = x + 0.00137;
     rng.get(x.floor() as u32 * 3290387 + seed) * 2.0 - 1.0
 }
 
 pub fn random_noise_2d(rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32) -> f32 {
+    if rng.hash_mode() == HashMode::V1 {
+        return to_signed(hash2(cell(x), cell(y), seed));
+    }
+
     let x = x + 0.00137;
     let y = y + 0.00137;
     rng.get(x.floor() as u32 * 3290387 + y.floor() as u32 * 4433105 + seed) * 2.0 - 1.0
 }
 
 pub fn random_noise_3d(rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, seed: u32) -> f32 {
+    if rng.hash_mode() == HashMode::V1 {
+        return to_signed(hash3(cell(x), cell(y), cell(z), seed));
+    }
+
     let x = x + 0.00137;
     let y = y + 0.00137;
     let z = z + 0.00137;
@@ -33,6 +48,10 @@ pub fn random_noise_4d(
     t: f32,
     seed: u32,
 ) -> f32 {
+    if rng.hash_mode() == HashMode::V1 {
+        return to_signed(hash4(cell(x), cell(y), cell(z), cell(t), seed));
+    }
+
     let x = x + 0.00137;
     let y = y + 0.00137;
     let z = z + 0.00137;
@@ -52,6 +71,10 @@ pub fn random_noise_4d(
 //
 
 pub fn random_noise_filtered_1d(rng: &mut UniformRandomGen, x: f32, seed: u32) -> f32 {
+    if rng.hash_mode() == HashMode::V1 {
+        return filtered([x], |c| hash1(c[0], seed));
+    }
+
     let x = x + 0.00137;
     let xi = x.floor() as i32;
     let xp = 1.0 + xi as f32 - x;
@@ -63,6 +86,10 @@ pub fn random_noise_filtered_1d(rng: &mut UniformRandomGen, x: f32, seed: u32) -
 }
 
 pub fn random_noise_filtered_2d(rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32) -> f32 {
+    if rng.hash_mode() == HashMode::V1 {
+        return filtered([x, y], |c| hash2(c[0], c[1], seed));
+    }
+
     let x = x + 0.00137;
     let y = y + 0.00137;
 
@@ -97,6 +124,10 @@ pub fn random_noise_filtered_3d(
     z: f32,
     seed: u32,
 ) -> f32 {
+    if rng.hash_mode() == HashMode::V1 {
+        return filtered([x, y, z], |c| hash3(c[0], c[1], c[2], seed));
+    }
+
     let x = x + 0.00137;
     let y = y + 0.00137;
     let z = z + 0.00137;
@@ -141,6 +172,10 @@ pub fn random_noise_filtered_4d(
     t: f32,
     seed: u32,
 ) -> f32 {
+    if rng.hash_mode() == HashMode::V1 {
+        return filtered([x, y, z, t], |c| hash4(c[0], c[1], c[2], c[3], seed));
+    }
+
     let x = x + 0.00137;
     let y = y + 0.00137;
     let z = z + 0.00137;
@@ -189,3 +224,34 @@ pub fn random_noise_filtered_4d(
 
     val * 2.0 - 1.0
 }
+
+//
+// Random noise with the lattice hash
+//
+
+// The cell of a coordinate, offset like the legacy noises.
+fn cell(c: f32) -> i32 {
+    (c + 0.00137).floor() as i32
+}
+
+// Interpolates the hashed values of the corners of the cell around `p` with the weights
+// of the legacy filtered noises.
+fn filtered<const N: usize, H: Fn([i32; N]) -> u32>(p: [f32; N], hash: H) -> f32 {
+    let cells = p.map(cell);
+    let mut val = 0.0;
+    for corner in 0..1 << N {
+        let mut c = cells;
+        let mut weight = 1.0;
+        for a in 0..N {
+            let frac = p[a] + 0.00137 - cells[a] as f32;
+            if corner >> (N - 1 - a) & 1 == 1 {
+                c[a] = c[a].wrapping_add(1);
+                weight *= frac;
+            } else {
+                weight *= 1.0 - frac;
+            }
+        }
+        val += to_unit(hash(c)) * weight;
+    }
+    val * 2.0 - 1.0
+}
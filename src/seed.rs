@@ -0,0 +1,123 @@
+use crate::prelude::UniformRandomGen;
+
+//
+// Seed derivation
+//
+
+// Seeds like `seed + 1` give correlated fields, as several noises add the seed to a
+// linear hash of the cell. A `SeedSequence` instead derives every child seed by
+// mixing the parent state with a label through the SplitMix64 finalizer, so children
+// of the same parent, and children with similar labels, are statistically
+// independent. Labels are strings or integers, which are tagged so that "1" and 1
+// derive different seeds.
+
+/// A root seed from which independent child seeds are derived by labels.
+///
+/// ```
+/// use noiselib::prelude::*;
+///
+/// let world = SeedSequence::from_name("world-42");
+/// let terrain = world.child("terrain");
+/// let chunk = terrain.child(12).child(-3);
+///
+/// let rng = &mut UniformRandomGen::new(chunk.seed());
+/// let height = perlin_noise_2d(rng, 0.5, 0.5, chunk.seed());
+///
+/// // One seed per octave
+/// let octaves: [u32; 6] = terrain.child("octaves").seeds();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SeedSequence {
+    state: u64,
+}
+
+/// A label deriving a child of a [`SeedSequence`].
+pub trait SeedLabel {
+    /// The 64-bit hash of the label, which must differ between label types.
+    fn label_hash(&self) -> u64;
+}
+
+impl SeedSequence {
+    pub fn new(seed: u32) -> Self {
+        Self::from_u64(seed as u64)
+    }
+
+    pub fn from_u64(seed: u64) -> Self {
+        Self {
+            state: mix64(seed ^ 0x243f_6a88_85a3_08d3),
+        }
+    }
+
+    /// A sequence seeded by a name such as `"world-42"`.
+    pub fn from_name(name: &str) -> Self {
+        Self::from_u64(name.label_hash())
+    }
+
+    /// The child sequence of `label`.
+    pub fn child<L: SeedLabel>(&self, label: L) -> Self {
+        Self {
+            state: mix64(self.state ^ mix64(label.label_hash().wrapping_add(GOLDEN))),
+        }
+    }
+
+    /// The seed of this sequence for the functions taking a `seed: u32`.
+    pub fn seed(&self) -> u32 {
+        (self.state >> 32) as u32
+    }
+
+    pub fn seed_u64(&self) -> u64 {
+        self.state
+    }
+
+    /// The seeds of the children `0..N`, for example one per octave.
+    pub fn seeds<const N: usize>(&self) -> [u32; N] {
+        core::array::from_fn(|i| self.child(i as u64).seed())
+    }
+
+    /// A generator seeded by this sequence, on a stream of its own.
+    pub fn rng(&self) -> UniformRandomGen {
+        UniformRandomGen::with_stream(self.state, mix64(self.state ^ GOLDEN))
+    }
+}
+
+impl From<SeedSequence> for u32 {
+    fn from(sequence: SeedSequence) -> u32 {
+        sequence.seed()
+    }
+}
+
+const GOLDEN: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// The SplitMix64 finalizer.
+fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// FNV-1a over the bytes of a string, tagged and mixed with its length.
+impl SeedLabel for &str {
+    fn label_hash(&self) -> u64 {
+        let mut h = 0xcbf2_9ce4_8422_2325u64;
+        for byte in self.bytes() {
+            h ^= byte as u64;
+            h = h.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        mix64(h ^ (self.len() as u64).rotate_left(32) ^ 1)
+    }
+}
+
+macro_rules! integer_labels {
+    ($($t:ty),*) => {
+        $(
+            impl SeedLabel for $t {
+                fn label_hash(&self) -> u64 {
+                    // Sign extended, so that equal values of all types agree.
+                    mix64((*self as i64 as u64) ^ 2)
+                }
+            }
+        )*
+    };
+}
+
+integer_labels!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
@@ -0,0 +1,786 @@
+use crate::prelude::*;
+
+//
+// SIMD evaluation of Perlin and Simplex noise
+//
+
+// The kernels evaluate 4 (SSE2) or 8 (AVX2) points at once with the exact sequence
+// of float operations of the scalar functions and without fused multiply-adds, so
+// they return the same bits as the scalar noise for every finite point whose lattice
+// coordinates fit into an i32. The simplex kernels also sum the lattice coordinates,
+// so chunks with a coordinate beyond `FAR`, where the sum could overflow the i32
+// lanes, are evaluated with the scalar functions. The lattice hashes are gathered
+// from `NOISE_PERM`, whose indices stay below 512 as in the scalar code. A short last
+// chunk is padded to a full vector.
+//
+// The kernels hash with the legacy tables, other hash modes use the scalar functions.
+
+/// The instruction sets the SIMD kernels can use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SimdLevel {
+    /// The scalar functions, one point at a time.
+    Scalar,
+    /// 4 points at once, available on every x86_64 CPU.
+    Sse2,
+    /// 8 points at once.
+    Avx2,
+}
+
+impl SimdLevel {
+    pub const ALL: [SimdLevel; 3] = [SimdLevel::Scalar, SimdLevel::Sse2, SimdLevel::Avx2];
+
+    /// The widest level supported by the running CPU.
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return SimdLevel::Avx2;
+            }
+            SimdLevel::Sse2
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        SimdLevel::Scalar
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            SimdLevel::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Sse2 => true,
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+
+    /// The number of points evaluated at once.
+    pub fn lanes(self) -> usize {
+        match self {
+            SimdLevel::Scalar => 1,
+            SimdLevel::Sse2 => 4,
+            SimdLevel::Avx2 => 8,
+        }
+    }
+
+    // The level actually used for `self`, unsupported levels falling back to scalar.
+    fn supported(self) -> Self {
+        if self.is_supported() {
+            self
+        } else {
+            SimdLevel::Scalar
+        }
+    }
+}
+
+fn check_len(out: &[f32], coords: &[&[f32]]) {
+    for c in coords {
+        assert_eq!(
+            c.len(),
+            out.len(),
+            "coordinates and output differ in length"
+        );
+    }
+}
+
+/// Evaluates [`perlin_noise_2d`] at the points `(xs[i], ys[i])` into `out`.
+pub fn perlin_2d(
+    level: SimdLevel,
+    out: &mut [f32],
+    xs: &[f32],
+    ys: &[f32],
+    seed: u32,
+    mode: HashMode,
+) {
+    check_len(out, &[xs, ys]);
+    match (mode, level.supported()) {
+        // Safety: SSE2 is part of the x86_64 baseline.
+        #[cfg(target_arch = "x86_64")]
+        (HashMode::Legacy, SimdLevel::Sse2) => unsafe { sse2::perlin_2d(out, xs, ys, seed) },
+        // Safety: AVX2 support was checked by `supported`.
+        #[cfg(target_arch = "x86_64")]
+        (HashMode::Legacy, SimdLevel::Avx2) => unsafe { avx2::perlin_2d(out, xs, ys, seed) },
+        _ => {
+            let rng = &mut UniformRandomGen::new(seed).with_hash_mode(mode);
+            for ((o, x), y) in out.iter_mut().zip(xs).zip(ys) {
+                *o = perlin_noise_2d(rng, *x, *y, seed);
+            }
+        }
+    }
+}
+
+/// Evaluates [`perlin_noise_3d`] at the points `(xs[i], ys[i], zs[i])` into `out`.
+pub fn perlin_3d(
+    level: SimdLevel,
+    out: &mut [f32],
+    xs: &[f32],
+    ys: &[f32],
+    zs: &[f32],
+    seed: u32,
+    mode: HashMode,
+) {
+    check_len(out, &[xs, ys, zs]);
+    match (mode, level.supported()) {
+        // Safety: SSE2 is part of the x86_64 baseline.
+        #[cfg(target_arch = "x86_64")]
+        (HashMode::Legacy, SimdLevel::Sse2) => unsafe { sse2::perlin_3d(out, xs, ys, zs, seed) },
+        // Safety: AVX2 support was checked by `supported`.
+        #[cfg(target_arch = "x86_64")]
+        (HashMode::Legacy, SimdLevel::Avx2) => unsafe { avx2::perlin_3d(out, xs, ys, zs, seed) },
+        _ => {
+            let rng = &mut UniformRandomGen::new(seed).with_hash_mode(mode);
+            for (((o, x), y), z) in out.iter_mut().zip(xs).zip(ys).zip(zs) {
+                *o = perlin_noise_3d(rng, *x, *y, *z, seed);
+            }
+        }
+    }
+}
+
+/// Evaluates [`simplex_noise_2d`] at the points `(xs[i], ys[i])` into `out`.
+pub fn simplex_2d(
+    level: SimdLevel,
+    out: &mut [f32],
+    xs: &[f32],
+    ys: &[f32],
+    seed: u32,
+    mode: HashMode,
+) {
+    check_len(out, &[xs, ys]);
+    match (mode, level.supported()) {
+        // Safety: SSE2 is part of the x86_64 baseline.
+        #[cfg(target_arch = "x86_64")]
+        (HashMode::Legacy, SimdLevel::Sse2) => unsafe { sse2::simplex_2d(out, xs, ys, seed) },
+        // Safety: AVX2 support was checked by `supported`.
+        #[cfg(target_arch = "x86_64")]
+        (HashMode::Legacy, SimdLevel::Avx2) => unsafe { avx2::simplex_2d(out, xs, ys, seed) },
+        _ => {
+            let rng = &mut UniformRandomGen::new(seed).with_hash_mode(mode);
+            for ((o, x), y) in out.iter_mut().zip(xs).zip(ys) {
+                *o = simplex_noise_2d(rng, *x, *y, seed);
+            }
+        }
+    }
+}
+
+/// Evaluates [`simplex_noise_3d`] at the points `(xs[i], ys[i], zs[i])` into `out`.
+pub fn simplex_3d(
+    level: SimdLevel,
+    out: &mut [f32],
+    xs: &[f32],
+    ys: &[f32],
+    zs: &[f32],
+    seed: u32,
+    mode: HashMode,
+) {
+    check_len(out, &[xs, ys, zs]);
+    match (mode, level.supported()) {
+        // Safety: SSE2 is part of the x86_64 baseline.
+        #[cfg(target_arch = "x86_64")]
+        (HashMode::Legacy, SimdLevel::Sse2) => unsafe { sse2::simplex_3d(out, xs, ys, zs, seed) },
+        // Safety: AVX2 support was checked by `supported`.
+        #[cfg(target_arch = "x86_64")]
+        (HashMode::Legacy, SimdLevel::Avx2) => unsafe { avx2::simplex_3d(out, xs, ys, zs, seed) },
+        _ => {
+            let rng = &mut UniformRandomGen::new(seed).with_hash_mode(mode);
+            for (((o, x), y), z) in out.iter_mut().zip(xs).zip(ys).zip(zs) {
+                *o = simplex_noise_3d(rng, *x, *y, *z, seed);
+            }
+        }
+    }
+}
+
+//
+// Kernels
+//
+
+// The kernels are written once against a small set of vector helpers, which every
+// instruction set module defines before expanding them with its target features.
+#[cfg(target_arch = "x86_64")]
+macro_rules! kernels {
+    ($(#[$attr:meta])*) => {
+        const N: usize = std::mem::size_of::<F>() / 4;
+        // Below 2^28 the skewed coordinates stay below 2^29 and the sum of three
+        // lattice coordinates fits into an i32.
+        const FAR: f32 = 268435456.0;
+
+        // Copies chunk `c` of the coordinates into full vectors, padding with zeros.
+        fn lanes(coords: &[f32], c: usize) -> [f32; N] {
+            let mut lanes = [0.0; N];
+            let chunk = &coords[c * N..coords.len().min((c + 1) * N)];
+            lanes[..chunk.len()].copy_from_slice(chunk);
+            lanes
+        }
+
+        // Whether any lane lies beyond `FAR` or is NaN.
+        fn far(lanes: &[f32; N]) -> bool {
+            lanes.iter().any(|v| !(-FAR < *v && *v < FAR))
+        }
+
+        fn store(out: &mut [f32], c: usize, lanes: [f32; N]) {
+            let end = out.len().min((c + 1) * N);
+            let chunk = &mut out[c * N..end];
+            let len = chunk.len();
+            chunk.copy_from_slice(&lanes[..len]);
+        }
+
+        $(#[$attr])*
+        fn smooth(t: F) -> F {
+            let inner = add(mul(t, sub(mul(t, splat(6.0)), splat(15.0))), splat(10.0));
+            mul(mul(mul(t, t), t), inner)
+        }
+
+        $(#[$attr])*
+        fn hash2(ix: I, iy: I) -> I {
+            gather(add_i(gather(ix), iy))
+        }
+
+        $(#[$attr])*
+        fn hash3(ix: I, iy: I, iz: I) -> I {
+            gather(add_i(gather(add_i(gather(ix), iy)), iz))
+        }
+
+        $(#[$attr])*
+        fn grad2(h: I, fx: F, fy: F) -> F {
+            let h = and_i(h, splat_i(7));
+            let low = lt_i(h, splat_i(4));
+            let even = eq_i(and_i(h, splat_i(1)), splat_i(0));
+
+            let u = select(low, fx, fy);
+            let v = select(low, fy, fx);
+
+            let u_val = select(even, mul(splat(1.8), u), neg(u));
+            let v_val = select(even, v, mul(splat(-1.8), v));
+            add(u_val, v_val)
+        }
+
+        $(#[$attr])*
+        fn grad3(h: I, fx: F, fy: F, fz: F) -> F {
+            let h = and_i(h, splat_i(15));
+            let pair = or_m(eq_i(h, splat_i(12)), eq_i(h, splat_i(13)));
+            let u = select(or_m(lt_i(h, splat_i(8)), pair), fx, fy);
+            let v = select(or_m(lt_i(h, splat_i(4)), pair), fy, fz);
+
+            let sign = |bit: I| to_f(add_i(add_i(bit, bit), splat_i(-1)));
+            let su = sign(and_i(h, splat_i(1)));
+            let sv = sign(and_i(h, splat_i(2)));
+            add(mul(su, u), mul(sv, v))
+        }
+
+        $(#[$attr])*
+        pub(super) fn perlin_2d(out: &mut [f32], xs: &[f32], ys: &[f32], seed: u32) {
+            let seed = (seed & (N_PERM as u32 - 1)) as usize;
+            let (ox, oy) = (splat_i(NOISE_PERM[seed]), splat_i(NOISE_PERM[seed + 1]));
+            let (mask, one) = (splat_i(N_PERM - 1), splat_i(1));
+
+            for c in 0..out.len().div_ceil(N) {
+                let x = load(lanes(xs, c));
+                let y = load(lanes(ys, c));
+
+                let ix = floor_i(x);
+                let iy = floor_i(y);
+                let fx = sub(x, to_f(ix));
+                let fy = sub(y, to_f(iy));
+
+                let ix = and_i(add_i(ix, ox), mask);
+                let iy = and_i(add_i(iy, oy), mask);
+                let (ix1, iy1) = (add_i(ix, one), add_i(iy, one));
+                let (fx1, fy1) = (sub(fx, splat(1.0)), sub(fy, splat(1.0)));
+
+                let w00 = grad2(hash2(ix, iy), fx, fy);
+                let w01 = grad2(hash2(ix, iy1), fx, fy1);
+                let w10 = grad2(hash2(ix1, iy), fx1, fy);
+                let w11 = grad2(hash2(ix1, iy1), fx1, fy1);
+
+                let wx = smooth(fx);
+                let wy = smooth(fy);
+                let (vx, vy) = (sub(splat(1.0), wx), sub(splat(1.0), wy));
+
+                let lower = add(mul(vy, w00), mul(wy, w01));
+                let upper = add(mul(vy, w10), mul(wy, w11));
+                store(out, c, unload(add(mul(vx, lower), mul(wx, upper))));
+            }
+        }
+
+        $(#[$attr])*
+        pub(super) fn perlin_3d(out: &mut [f32], xs: &[f32], ys: &[f32], zs: &[f32], seed: u32) {
+            let seed = (seed & (N_PERM as u32 - 1)) as usize;
+            let ox = splat_i(NOISE_PERM[seed]);
+            let oy = splat_i(NOISE_PERM[seed + 1]);
+            let oz = splat_i(NOISE_PERM[seed + 2]);
+            let (mask, one) = (splat_i(N_PERM - 1), splat_i(1));
+
+            for c in 0..out.len().div_ceil(N) {
+                let x = load(lanes(xs, c));
+                let y = load(lanes(ys, c));
+                let z = load(lanes(zs, c));
+
+                let ix = floor_i(x);
+                let iy = floor_i(y);
+                let iz = floor_i(z);
+                let fx = sub(x, to_f(ix));
+                let fy = sub(y, to_f(iy));
+                let fz = sub(z, to_f(iz));
+
+                let ix = and_i(add_i(ix, ox), mask);
+                let iy = and_i(add_i(iy, oy), mask);
+                let iz = and_i(add_i(iz, oz), mask);
+                let (ix1, iy1, iz1) = (add_i(ix, one), add_i(iy, one), add_i(iz, one));
+                let fx1 = sub(fx, splat(1.0));
+                let fy1 = sub(fy, splat(1.0));
+                let fz1 = sub(fz, splat(1.0));
+
+                let wz = smooth(fz);
+                let vz = sub(splat(1.0), wz);
+
+                let w000 = mul(grad3(hash3(ix, iy, iz), fx, fy, fz), vz);
+                let w001 = mul(grad3(hash3(ix, iy, iz1), fx, fy, fz1), wz);
+                let w010 = mul(grad3(hash3(ix, iy1, iz), fx, fy1, fz), vz);
+                let w011 = mul(grad3(hash3(ix, iy1, iz1), fx, fy1, fz1), wz);
+                let w100 = mul(grad3(hash3(ix1, iy, iz), fx1, fy, fz), vz);
+                let w101 = mul(grad3(hash3(ix1, iy, iz1), fx1, fy, fz1), wz);
+                let w110 = mul(grad3(hash3(ix1, iy1, iz), fx1, fy1, fz), vz);
+                let w111 = mul(grad3(hash3(ix1, iy1, iz1), fx1, fy1, fz1), wz);
+
+                let wx = smooth(fx);
+                let wy = smooth(fy);
+                let (vx, vy) = (sub(splat(1.0), wx), sub(splat(1.0), wy));
+
+                let lower = add(mul(vy, add(w000, w001)), mul(wy, add(w010, w011)));
+                let upper = add(mul(vy, add(w100, w101)), mul(wy, add(w110, w111)));
+                store(out, c, unload(add(mul(vx, lower), mul(wx, upper))));
+            }
+        }
+
+        $(#[$attr])*
+        pub(super) fn simplex_2d(out: &mut [f32], xs: &[f32], ys: &[f32], seed: u32) {
+            const F2: f32 = 0.3660254;
+            const G2: f32 = 0.211325;
+
+            let (rng, raw_seed) = (&mut UniformRandomGen::new(seed), seed);
+            let seed = (seed & (N_PERM as u32 - 1)) as usize;
+            let (ox, oy) = (splat_i(NOISE_PERM[seed]), splat_i(NOISE_PERM[seed + 1]));
+            let (mask, one) = (splat_i(N_PERM - 1), splat_i(1));
+
+            for c in 0..out.len().div_ceil(N) {
+                let (lx, ly) = (lanes(xs, c), lanes(ys, c));
+                if far(&lx) || far(&ly) {
+                    let r = c * N..out.len().min((c + 1) * N);
+                    for ((o, x), y) in out[r.clone()].iter_mut().zip(&xs[r.clone()]).zip(&ys[r]) {
+                        *o = simplex_noise_2d(rng, *x, *y, raw_seed);
+                    }
+                    continue;
+                }
+                let x = load(lx);
+                let y = load(ly);
+
+                let skew = mul(add(x, y), splat(F2));
+                let ix = floor_i(add(x, skew));
+                let iy = floor_i(add(y, skew));
+
+                let unskew = mul(to_f(add_i(ix, iy)), splat(G2));
+                let fx = sub(x, sub(to_f(ix), unskew));
+                let fy = sub(y, sub(to_f(iy), unskew));
+
+                let above = gt(fx, fy);
+                let off = and_i(mask_i(above), one);
+                let off_f = select(above, splat(1.0), splat(0.0));
+
+                let x1 = add(sub(fx, off_f), splat(G2));
+                let y1 = add(sub(fy, sub(splat(1.0), off_f)), splat(G2));
+                let x2 = add(sub(fx, splat(1.0)), splat(2.0 * G2));
+                let y2 = add(sub(fy, splat(1.0)), splat(2.0 * G2));
+
+                let ix = and_i(add_i(ix, ox), mask);
+                let iy = and_i(add_i(iy, oy), mask);
+
+                let corners = [
+                    (ix, iy, fx, fy),
+                    (add_i(ix, off), add_i(iy, sub_i(one, off)), x1, y1),
+                    (add_i(ix, one), add_i(iy, one), x2, y2),
+                ];
+
+                let mut sum = splat(0.0);
+                for (cx, cy, dx, dy) in corners {
+                    let t = sub(sub(splat(0.5), mul(dx, dx)), mul(dy, dy));
+                    let t2 = mul(t, t);
+                    let contribution = mul(mul(t2, t2), grad2(hash2(cx, cy), dx, dy));
+                    sum = select(gt(t, splat(0.0)), add(sum, contribution), sum);
+                }
+
+                store(out, c, unload(mul(sum, splat(49.5))));
+            }
+        }
+
+        $(#[$attr])*
+        pub(super) fn simplex_3d(out: &mut [f32], xs: &[f32], ys: &[f32], zs: &[f32], seed: u32) {
+            const F3: f32 = 1.0 / 3.0;
+            const G3: f32 = 1.0 / 6.0;
+
+            let (rng, raw_seed) = (&mut UniformRandomGen::new(seed), seed);
+            let seed = (seed & (N_PERM as u32 - 1)) as usize;
+            let ox = splat_i(NOISE_PERM[seed]);
+            let oy = splat_i(NOISE_PERM[seed + 1]);
+            let oz = splat_i(NOISE_PERM[seed + 2]);
+            let (mask, one) = (splat_i(N_PERM - 1), splat_i(1));
+
+            for c in 0..out.len().div_ceil(N) {
+                let (lx, ly, lz) = (lanes(xs, c), lanes(ys, c), lanes(zs, c));
+                if far(&lx) || far(&ly) || far(&lz) {
+                    let r = c * N..out.len().min((c + 1) * N);
+                    let points = xs[r.clone()].iter().zip(&ys[r.clone()]).zip(&zs[r.clone()]);
+                    for (o, ((x, y), z)) in out[r].iter_mut().zip(points) {
+                        *o = simplex_noise_3d(rng, *x, *y, *z, raw_seed);
+                    }
+                    continue;
+                }
+                let x = load(lx);
+                let y = load(ly);
+                let z = load(lz);
+
+                let skew = mul(add(add(x, y), z), splat(F3));
+                let ix = floor_i(add(x, skew));
+                let iy = floor_i(add(y, skew));
+                let iz = floor_i(add(z, skew));
+
+                let unskew = mul(to_f(add_i(add_i(ix, iy), iz)), splat(G3));
+                let fx = sub(x, sub(to_f(ix), unskew));
+                let fy = sub(y, sub(to_f(iy), unskew));
+                let fz = sub(z, sub(to_f(iz), unskew));
+
+                // The second and third corners of the simplex from the ordering of
+                // the offsets, as chosen by the branches of the scalar version.
+                let xy = ge(fx, fy);
+                let yz = ge(fy, fz);
+                let xz = ge(fx, fz);
+                let i1 = and_m(xy, xz);
+                let j1 = andnot_m(xy, yz);
+                let k1 = andnot_m(or_m(i1, j1), splat_m());
+                let i2 = or_m(xy, xz);
+                let j2 = or_m(andnot_m(xy, splat_m()), yz);
+                let k2 = andnot_m(and_m(yz, xz), splat_m());
+
+                let corner = |ci: F, cj: F, ck: F, idx: f32| {
+                    let (ci, cj, ck) = (and_i(mask_i(ci), one), and_i(mask_i(cj), one), and_i(mask_i(ck), one));
+                    let g = splat(idx * G3);
+                    (
+                        (ci, cj, ck),
+                        add(sub(fx, to_f(ci)), g),
+                        add(sub(fy, to_f(cj)), g),
+                        add(sub(fz, to_f(ck)), g),
+                    )
+                };
+                let zero = splat_i(0);
+                let corners = [
+                    ((zero, zero, zero), fx, fy, fz),
+                    corner(i1, j1, k1, 1.0),
+                    corner(i2, j2, k2, 2.0),
+                    corner(splat_m(), splat_m(), splat_m(), 3.0),
+                ];
+
+                let ix = and_i(add_i(ix, ox), mask);
+                let iy = and_i(add_i(iy, oy), mask);
+                let iz = and_i(add_i(iz, oz), mask);
+
+                let mut sum = splat(0.0);
+                for ((ci, cj, ck), dx, dy, dz) in corners {
+                    let t = sub(sub(sub(splat(0.6), mul(dx, dx)), mul(dy, dy)), mul(dz, dz));
+                    let t2 = mul(t, t);
+                    let h = hash3(add_i(ix, ci), add_i(iy, cj), add_i(iz, ck));
+                    let contribution = mul(mul(t2, t2), grad3(h, dx, dy, dz));
+                    sum = select(gt(t, splat(0.0)), add(sum, contribution), sum);
+                }
+
+                store(out, c, unload(mul(sum, splat(32.5))));
+            }
+        }
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use crate::perlin::{NOISE_PERM, N_PERM};
+    use crate::simplex::{simplex_noise_2d, simplex_noise_3d};
+    use crate::uniform::UniformRandomGen;
+    use std::arch::x86_64::*;
+
+    type F = __m128;
+    type I = __m128i;
+
+    #[target_feature(enable = "sse2")]
+    fn load(lanes: [f32; 4]) -> F {
+        // Safety: the array holds four floats.
+        unsafe { _mm_loadu_ps(lanes.as_ptr()) }
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn unload(v: F) -> [f32; 4] {
+        let mut lanes = [0.0; 4];
+        // Safety: the array holds four floats.
+        unsafe { _mm_storeu_ps(lanes.as_mut_ptr(), v) };
+        lanes
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn splat(v: f32) -> F {
+        _mm_set1_ps(v)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn splat_i(v: i32) -> I {
+        _mm_set1_epi32(v)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn splat_m() -> F {
+        _mm_castsi128_ps(_mm_set1_epi32(-1))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn add(a: F, b: F) -> F {
+        _mm_add_ps(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn sub(a: F, b: F) -> F {
+        _mm_sub_ps(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn mul(a: F, b: F) -> F {
+        _mm_mul_ps(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn neg(a: F) -> F {
+        _mm_xor_ps(a, _mm_set1_ps(-0.0))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn gt(a: F, b: F) -> F {
+        _mm_cmpgt_ps(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn ge(a: F, b: F) -> F {
+        _mm_cmpge_ps(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn and_m(a: F, b: F) -> F {
+        _mm_and_ps(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn or_m(a: F, b: F) -> F {
+        _mm_or_ps(a, b)
+    }
+
+    // !a & b
+    #[target_feature(enable = "sse2")]
+    fn andnot_m(a: F, b: F) -> F {
+        _mm_andnot_ps(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn select(mask: F, a: F, b: F) -> F {
+        _mm_or_ps(_mm_and_ps(mask, a), _mm_andnot_ps(mask, b))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn mask_i(mask: F) -> I {
+        _mm_castps_si128(mask)
+    }
+
+    // SSE2 has no floor, truncation is corrected for negative fractions.
+    #[target_feature(enable = "sse2")]
+    fn floor_i(v: F) -> I {
+        let t = _mm_cvttps_epi32(v);
+        let above = _mm_castps_si128(_mm_cmpgt_ps(_mm_cvtepi32_ps(t), v));
+        _mm_add_epi32(t, above)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn to_f(v: I) -> F {
+        _mm_cvtepi32_ps(v)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn add_i(a: I, b: I) -> I {
+        _mm_add_epi32(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn sub_i(a: I, b: I) -> I {
+        _mm_sub_epi32(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn and_i(a: I, b: I) -> I {
+        _mm_and_si128(a, b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn lt_i(a: I, b: I) -> F {
+        _mm_castsi128_ps(_mm_cmplt_epi32(a, b))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn eq_i(a: I, b: I) -> F {
+        _mm_castsi128_ps(_mm_cmpeq_epi32(a, b))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn gather(index: I) -> I {
+        let mut lanes = [0i32; 4];
+        // Safety: the array holds four integers.
+        unsafe {
+            _mm_storeu_si128(lanes.as_mut_ptr() as *mut I, index);
+            let lanes = lanes.map(|i| NOISE_PERM[i as usize]);
+            _mm_loadu_si128(lanes.as_ptr() as *const I)
+        }
+    }
+
+    kernels!(#[target_feature(enable = "sse2")]);
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use crate::perlin::{NOISE_PERM, N_PERM};
+    use crate::simplex::{simplex_noise_2d, simplex_noise_3d};
+    use crate::uniform::UniformRandomGen;
+    use std::arch::x86_64::*;
+
+    type F = __m256;
+    type I = __m256i;
+
+    #[target_feature(enable = "avx2")]
+    fn load(lanes: [f32; 8]) -> F {
+        // Safety: the array holds eight floats.
+        unsafe { _mm256_loadu_ps(lanes.as_ptr()) }
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn unload(v: F) -> [f32; 8] {
+        let mut lanes = [0.0; 8];
+        // Safety: the array holds eight floats.
+        unsafe { _mm256_storeu_ps(lanes.as_mut_ptr(), v) };
+        lanes
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn splat(v: f32) -> F {
+        _mm256_set1_ps(v)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn splat_i(v: i32) -> I {
+        _mm256_set1_epi32(v)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn splat_m() -> F {
+        _mm256_castsi256_ps(_mm256_set1_epi32(-1))
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn add(a: F, b: F) -> F {
+        _mm256_add_ps(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn sub(a: F, b: F) -> F {
+        _mm256_sub_ps(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn mul(a: F, b: F) -> F {
+        _mm256_mul_ps(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn neg(a: F) -> F {
+        _mm256_xor_ps(a, _mm256_set1_ps(-0.0))
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn gt(a: F, b: F) -> F {
+        _mm256_cmp_ps::<_CMP_GT_OQ>(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn ge(a: F, b: F) -> F {
+        _mm256_cmp_ps::<_CMP_GE_OQ>(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn and_m(a: F, b: F) -> F {
+        _mm256_and_ps(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn or_m(a: F, b: F) -> F {
+        _mm256_or_ps(a, b)
+    }
+
+    // !a & b
+    #[target_feature(enable = "avx2")]
+    fn andnot_m(a: F, b: F) -> F {
+        _mm256_andnot_ps(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn select(mask: F, a: F, b: F) -> F {
+        _mm256_blendv_ps(b, a, mask)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn mask_i(mask: F) -> I {
+        _mm256_castps_si256(mask)
+    }
+
+    // Truncation corrected for negative fractions, as in the SSE2 kernels.
+    #[target_feature(enable = "avx2")]
+    fn floor_i(v: F) -> I {
+        let t = _mm256_cvttps_epi32(v);
+        let above = _mm256_castps_si256(_mm256_cmp_ps::<_CMP_GT_OQ>(_mm256_cvtepi32_ps(t), v));
+        _mm256_add_epi32(t, above)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn to_f(v: I) -> F {
+        _mm256_cvtepi32_ps(v)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn add_i(a: I, b: I) -> I {
+        _mm256_add_epi32(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn sub_i(a: I, b: I) -> I {
+        _mm256_sub_epi32(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn and_i(a: I, b: I) -> I {
+        _mm256_and_si256(a, b)
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn lt_i(a: I, b: I) -> F {
+        _mm256_castsi256_ps(_mm256_cmpgt_epi32(b, a))
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn eq_i(a: I, b: I) -> F {
+        _mm256_castsi256_ps(_mm256_cmpeq_epi32(a, b))
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn gather(index: I) -> I {
+        // Safety: the kernels only form indices below 512, the length of NOISE_PERM.
+        unsafe { _mm256_i32gather_epi32::<4>(NOISE_PERM.as_ptr(), index) }
+    }
+
+    kernels!(#[target_feature(enable = "avx2")]);
+}
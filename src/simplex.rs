@@ -1,20 +1,18 @@
+use crate::hash::{lattice1, Lattice2, Lattice3, Lattice4};
+#[cfg(all(not(feature = "std"), not(test)))]
+use crate::math::Float;
+use crate::perlin::{grad2_hashed, grad2_vec_hashed, grad3_hashed, grad3_vec_hashed, grad4_hashed};
 use crate::prelude::*;
 
 //
 // Simplex noise
 //
 
-pub fn simplex_noise_1d(_rng: &mut UniformRandomGen, x: f32, seed: u32) -> f32 {
-    let mut ix = x.floor() as i32;
+pub fn simplex_noise_1d(rng: &mut UniformRandomGen, x: f32, seed: u32) -> f32 {
+    let ix = x.floor() as i32;
     let fx = x - ix as f32;
 
-    ix += NOISE_PERM[(seed & (N_PERM as u32 - 1)) as usize];
-
-    let ixp1 = ix & (N_PERM - 1);
-    let ixp2 = (ix / N_PERM) & (N_PERM - 1);
-
-    let h1 = NOISE_PERM[NOISE_PERM[ixp1 as usize] as usize + ixp2 as usize] & 15;
-    let h2 = NOISE_PERM[NOISE_PERM[ixp1 as usize + 1] as usize + ixp2 as usize] & 15;
+    let [h1, h2] = lattice1(rng.hash_mode(), ix, seed);
 
     let mut t0 = 1.0 - fx * fx;
     t0 *= t0;
@@ -22,18 +20,18 @@ pub fn simplex_noise_1d(_rng: &mut UniformRandomGen, x: f32, seed: u32) -> f32 {
     let mut t1 = fx * (2.0 - fx);
     t1 *= t1;
 
-    t0 * t0 * GRAD1[h1 as usize] + t1 * t1 * GRAD1[h2 as usize]
+    t0 * t0 * GRAD1[(h1 & 15) as usize] + t1 * t1 * GRAD1[(h2 & 15) as usize]
 }
 
 const F2: f32 = 0.3660254;
 const G2: f32 = 0.211325;
 
-pub fn simplex_noise_2d(_rng: &mut UniformRandomGen, x: f32, y: f32, mut seed: u32) -> f32 {
+pub fn simplex_noise_2d(rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32) -> f32 {
     let skew = (x + y) * F2;
-    let mut ix = (x + skew).floor() as i32;
-    let mut iy = (y + skew).floor() as i32;
+    let ix = (x + skew).floor() as i32;
+    let iy = (y + skew).floor() as i32;
 
-    let unskew = (ix + iy) as f32 * G2;
+    let unskew = (ix as i64 + iy as i64) as f32 * G2;
 
     let fx = x - (ix as f32 - unskew);
     let fy = y - (iy as f32 - unskew);
@@ -45,12 +43,7 @@ pub fn simplex_noise_2d(_rng: &mut UniformRandomGen, x: f32, y: f32, mut seed: u
     let x2 = fx - 1.0 + 2.0 * G2;
     let y2 = fy - 1.0 + 2.0 * G2;
 
-    seed &= N_PERM as u32 - 1;
-    ix += NOISE_PERM[seed as usize];
-    iy += NOISE_PERM[seed as usize + 1];
-
-    ix &= N_PERM - 1;
-    iy &= N_PERM - 1;
+    let lattice = Lattice2::new(rng.hash_mode(), ix, iy, seed);
 
     let mut sum = 0.0;
 
@@ -58,21 +51,21 @@ pub fn simplex_noise_2d(_rng: &mut UniformRandomGen, x: f32, y: f32, mut seed: u
 
     if t > 0.0 {
         t *= t;
-        sum += t * t * grad2(ix, iy, fx, fy);
+        sum += t * t * grad2_hashed(lattice.at(0, 0), fx, fy);
     }
 
     t = 0.5 - x1 * x1 - y1 * y1;
 
     if t > 0.0 {
         t *= t;
-        sum += t * t * grad2(ix + off as i32, iy + (1.0 - off) as i32, x1, y1);
+        sum += t * t * grad2_hashed(lattice.at(off as i32, (1.0 - off) as i32), x1, y1);
     }
 
     t = 0.5 - x2 * x2 - y2 * y2;
 
     if t > 0.0 {
         t *= t;
-        sum += t * t * grad2(ix + 1, iy + 1, x2, y2);
+        sum += t * t * grad2_hashed(lattice.at(1, 1), x2, y2);
     }
 
     sum * 49.5
@@ -81,13 +74,13 @@ pub fn simplex_noise_2d(_rng: &mut UniformRandomGen, x: f32, y: f32, mut seed: u
 const F3: f32 = 1.0 / 3.0;
 const G3: f32 = 1.0 / 6.0;
 
-pub fn simplex_noise_3d(_rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, mut seed: u32) -> f32 {
+pub fn simplex_noise_3d(rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, seed: u32) -> f32 {
     let skew = (x + y + z) * F3;
-    let mut ix = (x + skew).floor() as i32;
-    let mut iy = (y + skew).floor() as i32;
-    let mut iz = (z + skew).floor() as i32;
+    let ix = (x + skew).floor() as i32;
+    let iy = (y + skew).floor() as i32;
+    let iz = (z + skew).floor() as i32;
 
-    let unskew = (ix + iy + iz) as f32 * G3;
+    let unskew = (ix as i64 + iy as i64 + iz as i64) as f32 * G3;
 
     let mut fx: [f32; 4] = [0.0; 4];
     let mut fy: [f32; 4] = [0.0; 4];
@@ -160,14 +153,7 @@ pub fn simplex_noise_3d(_rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, mut
         fz[idx] = fz[0] - k[idx] as f32 + idx as f32 * G3;
     }
 
-    seed &= N_PERM as u32 - 1;
-    ix += NOISE_PERM[seed as usize];
-    iy += NOISE_PERM[seed as usize + 1];
-    iz += NOISE_PERM[seed as usize + 2];
-
-    ix &= N_PERM - 1;
-    iy &= N_PERM - 1;
-    iz &= N_PERM - 1;
+    let lattice = Lattice3::new(rng.hash_mode(), ix, iy, iz, seed);
 
     let mut sum = 0.0;
 
@@ -178,10 +164,8 @@ pub fn simplex_noise_3d(_rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, mut
             t *= t;
             sum += t
                 * t
-                * grad3(
-                    ix + i[idx],
-                    iy + j[idx],
-                    iz + k[idx],
+                * grad3_hashed(
+                    lattice.at(i[idx], j[idx], k[idx]),
                     fx[idx],
                     fy[idx],
                     fz[idx],
@@ -192,6 +176,118 @@ pub fn simplex_noise_3d(_rng: &mut UniformRandomGen, x: f32, y: f32, z: f32, mut
     sum * 32.5
 }
 
+/// [`simplex_noise_2d`] together with its analytic partial derivatives,
+/// returned as `[value, d/dx, d/dy]`.
+pub fn simplex_noise_deriv_2d(rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32) -> [f32; 3] {
+    let skew = (x + y) * F2;
+    let ix = (x + skew).floor() as i32;
+    let iy = (y + skew).floor() as i32;
+
+    let unskew = (ix as i64 + iy as i64) as f32 * G2;
+
+    let fx = x - (ix as f32 - unskew);
+    let fy = y - (iy as f32 - unskew);
+
+    let off = if fx > fy { 1 } else { 0 };
+
+    let lattice = Lattice2::new(rng.hash_mode(), ix, iy, seed);
+
+    let corners = [
+        (0, 0, fx, fy),
+        (
+            off,
+            1 - off,
+            fx - off as f32 + G2,
+            fy - (1 - off) as f32 + G2,
+        ),
+        (1, 1, fx - 1.0 + 2.0 * G2, fy - 1.0 + 2.0 * G2),
+    ];
+
+    let mut sum = [0.0; 3];
+
+    for (ci, cj, dx, dy) in corners {
+        let t = 0.5 - dx * dx - dy * dy;
+
+        if t > 0.0 {
+            let t2 = t * t;
+            let h = lattice.at(ci, cj);
+            let g = grad2_vec_hashed(h);
+            let dot = grad2_hashed(h, dx, dy);
+
+            sum[0] += t2 * t2 * dot;
+            sum[1] += t2 * t2 * g[0] - 8.0 * t2 * t * dot * dx;
+            sum[2] += t2 * t2 * g[1] - 8.0 * t2 * t * dot * dy;
+        }
+    }
+
+    sum.map(|v| v * 49.5)
+}
+
+/// [`simplex_noise_3d`] together with its analytic partial derivatives,
+/// returned as `[value, d/dx, d/dy, d/dz]`.
+pub fn simplex_noise_deriv_3d(
+    rng: &mut UniformRandomGen,
+    x: f32,
+    y: f32,
+    z: f32,
+    seed: u32,
+) -> [f32; 4] {
+    let skew = (x + y + z) * F3;
+    let ix = (x + skew).floor() as i32;
+    let iy = (y + skew).floor() as i32;
+    let iz = (z + skew).floor() as i32;
+
+    let unskew = (ix as i64 + iy as i64 + iz as i64) as f32 * G3;
+
+    let x0 = x - (ix as f32 - unskew);
+    let y0 = y - (iy as f32 - unskew);
+    let z0 = z - (iz as f32 - unskew);
+
+    // The offsets of the second and third simplex corner, the fourth is (1, 1, 1).
+    let (c1, c2) = if x0 >= y0 {
+        if y0 >= z0 {
+            ([1, 0, 0], [1, 1, 0])
+        } else if x0 >= z0 {
+            ([1, 0, 0], [1, 0, 1])
+        } else {
+            ([0, 0, 1], [1, 0, 1])
+        }
+    } else if y0 < z0 {
+        ([0, 0, 1], [0, 1, 1])
+    } else if x0 < z0 {
+        ([0, 1, 0], [0, 1, 1])
+    } else {
+        ([0, 1, 0], [1, 1, 0])
+    };
+    let corners = [[0, 0, 0], c1, c2, [1, 1, 1]];
+
+    let lattice = Lattice3::new(rng.hash_mode(), ix, iy, iz, seed);
+
+    let mut sum = [0.0; 4];
+
+    for (idx, c) in corners.iter().enumerate() {
+        let dx = x0 - c[0] as f32 + idx as f32 * G3;
+        let dy = y0 - c[1] as f32 + idx as f32 * G3;
+        let dz = z0 - c[2] as f32 + idx as f32 * G3;
+
+        let t = 0.6 - dx * dx - dy * dy - dz * dz;
+
+        if t > 0.0 {
+            let t2 = t * t;
+            let h = lattice.at(c[0], c[1], c[2]);
+            let g = grad3_vec_hashed(h);
+            let dot = grad3_hashed(h, dx, dy, dz);
+
+            sum[0] += t2 * t2 * dot;
+            sum[1] += t2 * t2 * g[0] - 8.0 * t2 * t * dot * dx;
+            sum[2] += t2 * t2 * g[1] - 8.0 * t2 * t * dot * dy;
+            sum[3] += t2 * t2 * g[2] - 8.0 * t2 * t * dot * dz;
+        }
+    }
+
+    sum.map(|v| v * 32.5)
+}
+
 static SIMPLEX: [[u8; 4]; 64] = [
     [0, 1, 2, 3],
     [0, 1, 3, 2],
@@ -263,20 +359,20 @@ const F4: f32 = 0.309017;
 const G4: f32 = 0.1381966;
 
 pub fn simplex_noise_4d(
-    _rng: &mut UniformRandomGen,
+    rng: &mut UniformRandomGen,
     x: f32,
     y: f32,
     z: f32,
     t: f32,
-    mut seed: u32,
+    seed: u32,
 ) -> f32 {
     let skew = (x + y + z + t) * F4;
-    let mut ix = (x + skew).floor() as i32;
-    let mut iy = (y + skew).floor() as i32;
-    let mut iz = (z + skew).floor() as i32;
-    let mut it = (t + skew).floor() as i32;
+    let ix = (x + skew).floor() as i32;
+    let iy = (y + skew).floor() as i32;
+    let iz = (z + skew).floor() as i32;
+    let it = (t + skew).floor() as i32;
 
-    let unskew = (ix + iy + iz + it) as f32 * G4;
+    let unskew = (ix as i64 + iy as i64 + iz as i64 + it as i64) as f32 * G4;
 
     let mut fx: [f32; 5] = [0.0; 5];
     let mut fy: [f32; 5] = [0.0; 5];
@@ -339,16 +435,7 @@ pub fn simplex_noise_4d(
         ft[idx] = ft[0] - l[idx] as f32 + idx as f32 * G4;
     }
 
-    seed &= N_PERM as u32 - 1;
-    ix += NOISE_PERM[seed as usize];
-    iy += NOISE_PERM[seed as usize + 1];
-    iz += NOISE_PERM[seed as usize + 2];
-    it += NOISE_PERM[seed as usize + 3];
-
-    ix &= N_PERM - 1;
-    iy &= N_PERM - 1;
-    iz &= N_PERM - 1;
-    it &= N_PERM - 1;
+    let lattice = Lattice4::new(rng.hash_mode(), ix, iy, iz, it, seed);
 
     let mut sum = 0.0;
 
@@ -360,11 +447,8 @@ pub fn simplex_noise_4d(
             w *= w;
             sum += w
                 * w
-                * grad4(
-                    ix + i[idx],
-                    iy + j[idx],
-                    iz + k[idx],
-                    it + l[idx],
+                * grad4_hashed(
+                    lattice.at(i[idx], j[idx], k[idx], l[idx]),
                     fx[idx],
                     fy[idx],
                     fz[idx],
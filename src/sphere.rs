@@ -0,0 +1,229 @@
+use crate::prelude::*;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+//
+// Spherical noise
+//
+
+// Planets and skies sample a 3D noise on the sphere, at the unit direction of every
+// texel scaled by `radius` noise units. As neighbouring texels map to neighbouring
+// points of one 3D field there are no seams at the date line, between cube faces or
+// at the poles, where an equirectangular row collapses onto a single point.
+//
+// Normals are those of the sphere displaced by the noise, `d * (1 + strength * h)`,
+// in object space. They use the tangential part of the 3D gradient of the noise,
+// from central differences of half a texel, so they are continuous everywhere too.
+
+/// The faces of a cube map in the order and orientation of OpenGL cube map textures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PosX,
+        CubeFace::NegX,
+        CubeFace::PosY,
+        CubeFace::NegY,
+        CubeFace::PosZ,
+        CubeFace::NegZ,
+    ];
+
+    /// The unit direction through the point `(u, v)` of the face, both in [-1, 1]
+    /// with `u` growing to the right and `v` downwards.
+    pub fn direction(self, u: f32, v: f32) -> [f32; 3] {
+        let d = match self {
+            CubeFace::PosX => [1.0, -v, -u],
+            CubeFace::NegX => [-1.0, -v, u],
+            CubeFace::PosY => [u, 1.0, v],
+            CubeFace::NegY => [u, -1.0, -v],
+            CubeFace::PosZ => [u, -v, 1.0],
+            CubeFace::NegZ => [-u, -v, -1.0],
+        };
+        normalize(d)
+    }
+
+    /// The face a direction points at and the point `(u, v)` on it, the inverse of
+    /// [`CubeFace::direction`].
+    pub fn project(d: [f32; 3]) -> (CubeFace, f32, f32) {
+        let [x, y, z] = d;
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        if ax >= ay && ax >= az {
+            if x > 0.0 {
+                (CubeFace::PosX, -z / ax, -y / ax)
+            } else {
+                (CubeFace::NegX, z / ax, -y / ax)
+            }
+        } else if ay >= az {
+            if y > 0.0 {
+                (CubeFace::PosY, x / ay, z / ay)
+            } else {
+                (CubeFace::NegY, x / ay, -z / ay)
+            }
+        } else if z > 0.0 {
+            (CubeFace::PosZ, x / az, -y / az)
+        } else {
+            (CubeFace::NegZ, -x / az, -y / az)
+        }
+    }
+}
+
+/// The unit direction of a latitude and longitude in radians. Latitude is positive
+/// towards +y (north), longitude 0 points at +z and grows towards +x.
+pub fn direction(lat: f32, lon: f32) -> [f32; 3] {
+    let (sin_lat, cos_lat) = lat.sin_cos();
+    let (sin_lon, cos_lon) = lon.sin_cos();
+    [cos_lat * sin_lon, sin_lat, cos_lat * cos_lon]
+}
+
+/// The latitude and longitude in radians of a unit direction, the inverse of
+/// [`direction`], with the longitude in [-pi, pi].
+pub fn lat_long(d: [f32; 3]) -> (f32, f32) {
+    (d[1].clamp(-1.0, 1.0).asin(), d[0].atan2(d[2]))
+}
+
+/// The direction of the centre of texel `(i, j)` of an equirectangular map, row 0
+/// being the north pole and column 0 the longitude -pi.
+pub fn equirectangular_direction(width: usize, height: usize, i: usize, j: usize) -> [f32; 3] {
+    let lat = FRAC_PI_2 - (j as f32 + 0.5) / height as f32 * PI;
+    let lon = (i as f32 + 0.5) / width as f32 * TAU - PI;
+    direction(lat, lon)
+}
+
+/// The direction of the centre of texel `(i, j)` of a cube map face of `size` x `size`.
+pub fn cube_direction(face: CubeFace, size: usize, i: usize, j: usize) -> [f32; 3] {
+    let uv = |k: usize| (k as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+    face.direction(uv(i), uv(j))
+}
+
+/// An equirectangular map of `width` x `height` texels of any 3D noise on the
+/// sphere of `radius` noise units, row by row from the north pole.
+///
+/// ```
+/// use noiselib::prelude::*;
+/// use noiselib::sphere;
+///
+/// let mut rng = UniformRandomGen::new(1);
+/// let fractal = Fractal::default();
+/// let map = sphere::equirectangular(&mut rng, 128, 64, 2.0, |rng, x, y, z| {
+///     fractal.eval_3d(rng, x, y, z, perlin_noise_3d)
+/// });
+/// ```
+pub fn equirectangular<F: FnMut(&mut UniformRandomGen, f32, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    width: usize,
+    height: usize,
+    radius: f32,
+    mut noise: F,
+) -> Vec<f32> {
+    let mut values = Vec::with_capacity(width * height);
+    for j in 0..height {
+        for i in 0..width {
+            let [x, y, z] = equirectangular_direction(width, height, i, j).map(|c| c * radius);
+            values.push(noise(rng, x, y, z));
+        }
+    }
+    values
+}
+
+/// The six faces of a cube map of `size` x `size` texels of any 3D noise on the
+/// sphere of `radius` noise units, in the order of [`CubeFace::ALL`].
+pub fn cube_map<F: FnMut(&mut UniformRandomGen, f32, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    size: usize,
+    radius: f32,
+    mut noise: F,
+) -> [Vec<f32>; 6] {
+    CubeFace::ALL.map(|face| {
+        let mut values = Vec::with_capacity(size * size);
+        for j in 0..size {
+            for i in 0..size {
+                let [x, y, z] = cube_direction(face, size, i, j).map(|c| c * radius);
+                values.push(noise(rng, x, y, z));
+            }
+        }
+        values
+    })
+}
+
+/// The object space normals of the sphere displaced by `strength` times the noise,
+/// for the texels of an equirectangular map, see [`equirectangular`].
+pub fn equirectangular_normals<F: FnMut(&mut UniformRandomGen, f32, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    width: usize,
+    height: usize,
+    radius: f32,
+    strength: f32,
+    mut noise: F,
+) -> Vec<[f32; 3]> {
+    let h = 0.5 * PI / height as f32 * radius;
+    let mut normals = Vec::with_capacity(width * height);
+    for j in 0..height {
+        for i in 0..width {
+            let d = equirectangular_direction(width, height, i, j);
+            normals.push(displaced_normal(rng, d, radius, strength, h, &mut noise));
+        }
+    }
+    normals
+}
+
+/// The object space normals of the sphere displaced by `strength` times the noise,
+/// for the texels of the faces of a cube map, see [`cube_map`].
+pub fn cube_map_normals<F: FnMut(&mut UniformRandomGen, f32, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    size: usize,
+    radius: f32,
+    strength: f32,
+    mut noise: F,
+) -> [Vec<[f32; 3]>; 6] {
+    let h = 1.0 / size as f32 * radius;
+    CubeFace::ALL.map(|face| {
+        let mut normals = Vec::with_capacity(size * size);
+        for j in 0..size {
+            for i in 0..size {
+                let d = cube_direction(face, size, i, j);
+                normals.push(displaced_normal(rng, d, radius, strength, h, &mut noise));
+            }
+        }
+        normals
+    })
+}
+
+// The normal of the surface `d * (1 + strength * noise(d * radius))` at the unit
+// direction `d`, with the gradient from central differences `h` noise units apart.
+fn displaced_normal<F: FnMut(&mut UniformRandomGen, f32, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    d: [f32; 3],
+    radius: f32,
+    strength: f32,
+    h: f32,
+    noise: &mut F,
+) -> [f32; 3] {
+    let p = d.map(|c| c * radius);
+    let value = noise(rng, p[0], p[1], p[2]);
+    let mut gradient = [0.0; 3];
+    for (axis, g) in gradient.iter_mut().enumerate() {
+        let (mut p0, mut p1) = (p, p);
+        p0[axis] -= h;
+        p1[axis] += h;
+        *g = (noise(rng, p1[0], p1[1], p1[2]) - noise(rng, p0[0], p0[1], p0[2])) / (2.0 * h);
+    }
+
+    // The gradient of the radius on the unit sphere is the tangential part of the
+    // 3D gradient, scaled by the radius of the sampled sphere.
+    let radial = gradient[0] * d[0] + gradient[1] * d[1] + gradient[2] * d[2];
+    let r = 1.0 + strength * value;
+    let scale = strength * radius / r;
+    normalize([0, 1, 2].map(|a| d[a] - scale * (gradient[a] - radial * d[a])))
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    v.map(|c| c / length)
+}
@@ -0,0 +1,98 @@
+#[cfg(all(not(feature = "std"), not(test)))]
+use crate::math::Float;
+use crate::prelude::UniformRandomGen;
+
+//
+// Triplanar mapping
+//
+
+// A 2D noise is projected along each axis onto a mesh and the three projections
+// are blended by the weights `|n|^sharpness` of the normal, normalized to sum to 1.
+// The planes use the coordinates (z, y) for the x axis, (x, z) for the y axis and
+// (x, y) for the z axis. Planes without weight are not evaluated.
+//
+// The blended gradient combines the gradients of the projections in world space.
+// It ignores the change of the weights along the surface, as is usual for bump
+// mapping, so it is the exact gradient of the blend for a fixed normal.
+
+/// The blend weights of the x, y and z planes for a normal, summing to 1. Higher
+/// sharpness gives narrower transitions between the planes.
+pub fn weights(normal: [f32; 3], sharpness: f32) -> [f32; 3] {
+    let w = normal.map(|c| c.abs().powf(sharpness));
+    let sum = w[0] + w[1] + w[2];
+    if sum > 0.0 {
+        w.map(|c| c / sum)
+    } else {
+        [1.0 / 3.0; 3]
+    }
+}
+
+/// Any 2D noise or fractal mapped triplanarly at `position` with the surface
+/// `normal`.
+///
+/// ```
+/// use noiselib::prelude::*;
+/// use noiselib::triplanar;
+///
+/// let mut rng = UniformRandomGen::new(1);
+/// let fractal = Fractal::default();
+/// let v = triplanar::sample(&mut rng, [1.0, 2.0, 3.0], [0.6, 0.0, 0.8], 4.0, |rng, u, v| {
+///     fractal.eval_2d(rng, u, v, perlin_noise_2d)
+/// });
+/// ```
+pub fn sample<F: FnMut(&mut UniformRandomGen, f32, f32) -> f32>(
+    rng: &mut UniformRandomGen,
+    position: [f32; 3],
+    normal: [f32; 3],
+    sharpness: f32,
+    mut noise: F,
+) -> f32 {
+    let [x, y, z] = position;
+    let w = weights(normal, sharpness);
+    let planes = [[z, y], [x, z], [x, y]];
+
+    let mut value = 0.0;
+    for (w, [u, v]) in w.into_iter().zip(planes) {
+        if w > 0.0 {
+            value += w * noise(rng, u, v);
+        }
+    }
+    value
+}
+
+/// Like [`sample`] for a 2D noise returning its derivatives as `[value, d/du, d/dv]`,
+/// such as [`perlin_noise_deriv_2d`](crate::perlin::perlin_noise_deriv_2d), returning
+/// the blended value and world space gradient as `[value, d/dx, d/dy, d/dz]`.
+pub fn sample_gradient<F: FnMut(&mut UniformRandomGen, f32, f32) -> [f32; 3]>(
+    rng: &mut UniformRandomGen,
+    position: [f32; 3],
+    normal: [f32; 3],
+    sharpness: f32,
+    mut noise: F,
+) -> [f32; 4] {
+    let [x, y, z] = position;
+    let w = weights(normal, sharpness);
+    // The coordinates of each plane and the world axes of its u and v.
+    let planes = [([z, y], 2, 1), ([x, z], 0, 2), ([x, y], 0, 1)];
+
+    let mut out = [0.0; 4];
+    for (w, ([u, v], u_axis, v_axis)) in w.into_iter().zip(planes) {
+        if w > 0.0 {
+            let [value, du, dv] = noise(rng, u, v);
+            out[0] += w * value;
+            out[1 + u_axis] += w * du;
+            out[1 + v_axis] += w * dv;
+        }
+    }
+    out
+}
+
+/// The normal bumped by a world space gradient, such as the one of
+/// [`sample_gradient`], scaled by `strength`. Only the part of the gradient along
+/// the surface tilts the normal.
+pub fn bump_normal(normal: [f32; 3], gradient: [f32; 3], strength: f32) -> [f32; 3] {
+    let along = gradient[0] * normal[0] + gradient[1] * normal[1] + gradient[2] * normal[2];
+    let n = [0, 1, 2].map(|a| normal[a] - strength * (gradient[a] - along * normal[a]));
+    let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    n.map(|c| c / length)
+}
@@ -1,16 +1,75 @@
+use crate::hash::HashMode;
+#[cfg(all(not(feature = "std"), not(test)))]
+use crate::math::Float;
+use core::ops::Range;
+
+//
+// Random number generator
+//
+
+// The generator holds two independent states. `get` and `get_` are the original
+// 23-bit hash the noise functions are built on, kept unchanged so all noise outputs
+// stay reproducible. The `next_*` and distribution functions use a PCG32 generator
+// (PCG-XSH-RR with 64-bit state) seeded from the same seed. The generator also
+// carries the hash mode of the noise functions it is passed to.
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+// The stream of the reference PCG32 implementation.
+const DEFAULT_STREAM: u64 = 0xda3e_39cb_94b9_5bdb >> 1;
+
+#[derive(Clone, Debug)]
 pub struct UniformRandomGen {
     seed: u32,
+    state: u64,
+    inc: u64,
+    hash_mode: HashMode,
 }
 
 impl UniformRandomGen {
     pub fn new(seed: u32) -> Self {
-        Self { seed }
+        Self::with_stream(seed as u64, DEFAULT_STREAM)
     }
 
+    /// A generator with a 64-bit PCG seed and one of 2^63 independent streams. The
+    /// legacy hash is seeded with the low 32 bits of `seed`.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            seed: seed as u32,
+            state: 0,
+            inc: (stream << 1) | 1,
+            hash_mode: HashMode::Legacy,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Selects the lattice hash of the noise functions evaluated with this generator.
+    pub fn with_hash_mode(mut self, mode: HashMode) -> Self {
+        self.hash_mode = mode;
+        self
+    }
+
+    pub fn set_hash_mode(&mut self, mode: HashMode) {
+        self.hash_mode = mode;
+    }
+
+    pub fn hash_mode(&self) -> HashMode {
+        self.hash_mode
+    }
+
+    /// Reseeds both the legacy hash and the PCG generator, keeping the stream and the
+    /// hash mode.
     pub fn reset(&mut self, seed: u32) {
-        self.seed = seed;
+        *self = Self::with_stream(seed as u64, self.inc >> 1).with_hash_mode(self.hash_mode);
     }
 
+    //
+    // Legacy hash
+    //
+
+    /// The next value of the legacy hash in [0, 1) with 23 bits of precision.
     pub fn get_(&mut self) -> f32 {
         self.seed = self
             .seed
@@ -24,6 +83,8 @@ impl UniformRandomGen {
         result - 1.0
     }
 
+    /// The legacy hash of `seed` in [0, 1) with 23 bits of precision, mixed with the
+    /// previous value. This is the hash of the noise functions.
     pub fn get(&mut self, seed: u32) -> f32 {
         self.seed = seed
             .wrapping_mul(987654323)
@@ -35,4 +96,188 @@ impl UniformRandomGen {
         let result = f32::from_bits(bitval);
         result - 1.0
     }
+
+    //
+    // PCG32
+    //
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let hi = self.next_u32() as u64;
+        (hi << 32) | self.next_u32() as u64
+    }
+
+    /// A float in [0, 1) with 24 bits of precision.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+
+    /// A float in [0, 1) with 53 bits of precision.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// An unbiased integer in `range`, which must not be empty.
+    pub fn range_u32(&mut self, range: Range<u32>) -> u32 {
+        assert!(range.start < range.end, "empty range {:?}", range);
+        range.start + self.below_u32(range.end - range.start)
+    }
+
+    /// An unbiased integer in `range`, which must not be empty.
+    pub fn range_i32(&mut self, range: Range<i32>) -> i32 {
+        assert!(range.start < range.end, "empty range {:?}", range);
+        let span = range.end.wrapping_sub(range.start) as u32;
+        range.start.wrapping_add(self.below_u32(span) as i32)
+    }
+
+    /// A float in [lo, hi).
+    pub fn range_f32(&mut self, lo: f32, hi: f32) -> f32 {
+        let v = lo + (hi - lo) * self.next_f32();
+        // Rounding can reach `hi` for wide ranges.
+        if v < hi {
+            v
+        } else {
+            lo
+        }
+    }
+
+    //
+    // Distributions
+    //
+
+    /// A normally distributed value, using the Box-Muller transform.
+    pub fn normal(&mut self, mean: f32, std_dev: f32) -> f32 {
+        let u1 = 1.0 - self.next_f32();
+        let u2 = self.next_f32();
+        let r = (-2.0 * u1.ln()).sqrt();
+        let (_, cos) = (core::f32::consts::TAU * u2).sin_cos();
+        mean + std_dev * r * cos
+    }
+
+    /// An exponentially distributed value with the rate `lambda` and mean `1 / lambda`.
+    pub fn exponential(&mut self, lambda: f32) -> f32 {
+        -(1.0 - self.next_f32()).ln() / lambda
+    }
+
+    /// A Poisson distributed count with the mean `lambda`, using multiplication of
+    /// uniforms for small means and the PTRS rejection method of Hörmann otherwise.
+    pub fn poisson(&mut self, lambda: f32) -> u32 {
+        let lambda = lambda as f64;
+        if lambda <= 0.0 {
+            return 0;
+        }
+
+        if lambda < 10.0 {
+            let limit = (-lambda).exp();
+            let mut k = 0;
+            let mut p = self.next_f64();
+            while p > limit {
+                k += 1;
+                p *= self.next_f64();
+            }
+            return k;
+        }
+
+        let slam = lambda.sqrt();
+        let loglam = lambda.ln();
+        let b = 0.931 + 2.53 * slam;
+        let a = -0.059 + 0.02483 * b;
+        let invalpha = 1.1239 + 1.1328 / (b - 3.4);
+        let vr = 0.9277 - 3.6224 / (b - 2.0);
+        loop {
+            let u = self.next_f64() - 0.5;
+            let v = self.next_f64();
+            let us = 0.5 - u.abs();
+            let k = ((2.0 * a / us + b) * u + lambda + 0.43).floor();
+            if us >= 0.07 && v <= vr {
+                return k as u32;
+            }
+            if k < 0.0 || (us < 0.013 && v > us) {
+                continue;
+            }
+            if v.ln() + invalpha.ln() - (a / (us * us) + b).ln()
+                <= -lambda + k * loglam - ln_gamma(k + 1.0)
+            {
+                return k as u32;
+            }
+        }
+    }
+
+    /// A uniformly distributed point on the unit circle.
+    pub fn unit_circle(&mut self) -> [f32; 2] {
+        let (sin, cos) = (core::f32::consts::TAU * self.next_f32()).sin_cos();
+        [cos, sin]
+    }
+
+    /// A uniformly distributed point on the unit sphere.
+    pub fn unit_sphere(&mut self) -> [f32; 3] {
+        let z = self.range_f32(-1.0, 1.0);
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let (sin, cos) = (core::f32::consts::TAU * self.next_f32()).sin_cos();
+        [r * cos, r * sin, z]
+    }
+
+    /// Shuffles `values` uniformly with the Fisher-Yates algorithm.
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            let j = if i < u32::MAX as usize {
+                self.below_u32(i as u32 + 1) as usize
+            } else {
+                (self.next_u64() % (i as u64 + 1)) as usize
+            };
+            values.swap(i, j);
+        }
+    }
+
+    // An unbiased integer in [0, span) with Lemire's multiply and reject method.
+    fn below_u32(&mut self, span: u32) -> u32 {
+        let mut m = self.next_u32() as u64 * span as u64;
+        if (m as u32) < span {
+            let threshold = span.wrapping_neg() % span;
+            while (m as u32) < threshold {
+                m = self.next_u32() as u64 * span as u64;
+            }
+        }
+        (m >> 32) as u32
+    }
+}
+
+// The logarithm of the gamma function for x >= 1 with the Stirling series, shifting
+// small arguments up by the recurrence.
+fn ln_gamma(x: f64) -> f64 {
+    const A: [f64; 10] = [
+        8.333333333333333e-02,
+        -2.777777777777778e-03,
+        7.936507936507937e-04,
+        -5.952380952380952e-04,
+        8.417508417508418e-04,
+        -1.917526917526918e-03,
+        6.41025641025641e-03,
+        -2.955065359477124e-02,
+        1.796443723688307e-01,
+        -1.39243221690590e+00,
+    ];
+    if x == 1.0 || x == 2.0 {
+        return 0.0;
+    }
+
+    let n = if x <= 7.0 { (7.0 - x) as i32 } else { 0 };
+    let mut x0 = x + n as f64;
+    let x2 = 1.0 / (x0 * x0);
+    let mut series = A[9];
+    for a in A[..9].iter().rev() {
+        series = series * x2 + a;
+    }
+    let mut gl = series / x0 + 0.5 * core::f64::consts::TAU.ln() + (x0 - 0.5) * x0.ln() - x0;
+    for _ in 0..n {
+        x0 -= 1.0;
+        gl -= x0.ln();
+    }
+    gl
 }
@@ -1,3 +1,6 @@
+use crate::hash::{hash1_unit, hash2, hash3, HashMode};
+#[cfg(all(not(feature = "std"), not(test)))]
+use crate::math::Float;
 use crate::prelude::UniformRandomGen;
 
 //
@@ -26,15 +29,26 @@ pub fn worley_f1_add_points_2d(
     y: f32,
     seed: u32,
 ) {
-    let rseed = 702395077u64
-        .wrapping_mul(ix as u64)
-        .wrapping_add(915488749u64.wrapping_mul(iy as u64))
-        .wrapping_add(seed as u64) as u32;
+    let mode = rng.hash_mode();
+    let rseed = match mode {
+        HashMode::Legacy => 702395077u64
+            .wrapping_mul(ix as u64)
+            .wrapping_add(915488749u64.wrapping_mul(iy as u64))
+            .wrapping_add(seed as u64) as u32,
+        HashMode::V1 => hash2(ix, iy, seed),
+    };
     let n_points = POISSON_COUNT[(rseed >> 24) as usize];
 
     for i in 0..n_points {
-        let dx = rng.get(rseed + i as u32 * 16) + ix as f32 - x;
-        let dy = rng.get(rseed + i as u32 * 16 + 1) + iy as f32 - y;
+        let (px, py) = match mode {
+            HashMode::Legacy => (
+                rng.get(rseed + i as u32 * 16),
+                rng.get(rseed + i as u32 * 16 + 1),
+            ),
+            HashMode::V1 => (hash1_unit(i * 2, rseed), hash1_unit(i * 2 + 1, rseed)),
+        };
+        let dx = px + ix as f32 - x;
+        let dy = py + iy as f32 - y;
 
         let d2 = dx * dx + dy * dy;
 
@@ -56,17 +70,33 @@ pub fn worley_f1_add_points_3d(
     z: f32,
     seed: u32,
 ) {
-    let rseed = 702395077u64
-        .wrapping_mul(ix as u64)
-        .wrapping_add(915488749u64.wrapping_mul(iy as u64))
-        .wrapping_add(2120969693u64.wrapping_mul(iz as u64))
-        .wrapping_add(seed as u64) as u32;
+    let mode = rng.hash_mode();
+    let rseed = match mode {
+        HashMode::Legacy => 702395077u64
+            .wrapping_mul(ix as u64)
+            .wrapping_add(915488749u64.wrapping_mul(iy as u64))
+            .wrapping_add(2120969693u64.wrapping_mul(iz as u64))
+            .wrapping_add(seed as u64) as u32,
+        HashMode::V1 => hash3(ix, iy, iz, seed),
+    };
     let n_points = POISSON_COUNT[(rseed >> 24) as usize];
 
     for i in 0..n_points {
-        let dx = rng.get(rseed + i as u32 * 16) + ix as f32 - x;
-        let dy = rng.get(rseed + i as u32 * 16 + 1) + iy as f32 - y;
-        let dz = rng.get(rseed + i as u32 * 16 + 2) + iz as f32 - z;
+        let (px, py, pz) = match mode {
+            HashMode::Legacy => (
+                rng.get(rseed + i as u32 * 16),
+                rng.get(rseed + i as u32 * 16 + 1),
+                rng.get(rseed + i as u32 * 16 + 2),
+            ),
+            HashMode::V1 => (
+                hash1_unit(i * 3, rseed),
+                hash1_unit(i * 3 + 1, rseed),
+                hash1_unit(i * 3 + 2, rseed),
+            ),
+        };
+        let dx = px + ix as f32 - x;
+        let dy = py + iy as f32 - y;
+        let dz = pz + iz as f32 - z;
 
         let d2 = dx * dx + dy * dy + dz * dz;
 
@@ -93,7 +123,7 @@ pub fn worley_f1_noise_2d(rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32)
     let fx = x - ix as f32;
     let fy = y - iy as f32;
 
-    let mut dist2 = std::f32::MAX;
+    let mut dist2 = f32::MAX;
 
     worley_f1_add_points_2d(rng, &mut dist2, ix, iy, x, y, seed);
 
@@ -180,8 +210,15 @@ pub fn worley_f1_noise_2d(rng: &mut UniformRandomGen, x: f32, y: f32, seed: u32)
         cell_idx += 1;
     }
 
-    // Sorting cells by distance
-    cells[..cell_idx].sort_by(|a, b| a.dist.partial_cmp(&b.dist).unwrap());
+    // Sorting cells by distance. A stable insertion sort, which needs no allocation
+    // and keeps cells of equal distance in the order of the slice sort used before.
+    for i in 1..cell_idx {
+        let mut j = i;
+        while j > 0 && cells[j - 1].dist > cells[j].dist {
+            cells.swap(j - 1, j);
+            j -= 1;
+        }
+    }
 
     worley_f1_add_points_2d(
         rng,